@@ -0,0 +1,17 @@
+use std::process::Command;
+
+/// Embeds the git version of the source tree in the binary (as `HINDSIGHT_GIT_VERSION`),
+/// so scan results can be traced back to the code that produced them.
+fn main() {
+    let version = Command::new("git")
+        .args(["describe", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=HINDSIGHT_GIT_VERSION={}", version);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+}
@@ -1,5 +1,5 @@
 use clap::{Parser, Subcommand};
-use hindsight::data::db::DbEngine;
+use hindsight::data::{arbs::ConflictPolicy, db::DbEngine};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -7,6 +7,10 @@ pub struct Cli {
     #[arg(short, long, default_value = "20")]
     pub ws_max_reconnects: Option<usize>,
 
+    /// After ctrl-c, wait at most this many seconds for work in progress to be saved. Press ctrl-c again to exit right away.
+    #[arg(long, value_name = "SECONDS", default_value = "30", global = true)]
+    pub shutdown_timeout: u64,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -43,6 +47,37 @@ pub enum Commands {
             help = &format!("<{}>: DB engine to store arb data, defaults to mongo", DbEngine::enum_flags())
         )]
         db_engine: Option<DbEngine>,
+        /// Resume the scan run with this id from its last checkpoint.
+        ///
+        /// If no start params are given, the latest unfinished run is resumed automatically.
+        #[arg(long, value_name = "RUN_ID", conflicts_with_all = ["block_start", "timestamp_start", "block_end", "timestamp_end"])]
+        resume: Option<String>,
+        /// Once caught up with the latest events, wait at most this many seconds for a new block before polling for events again.
+        #[arg(long, value_name = "SECONDS", default_value = "12")]
+        poll_interval: u64,
+        #[arg(
+            long,
+            default_value = "replace",
+            help = &format!("<{}>: what to do when an arb for the same event & run is already saved", ConflictPolicy::enum_flags())
+        )]
+        on_conflict: ConflictPolicy,
+    },
+    /// Listen to new MEV-Share events and simulate arbitrage opportunities as their transactions land. Automatically saves results to DB.
+    Live {
+        /// Number of transactions to simulate concurrently. Defaults to 1/2 the CPU cores on host.
+        #[arg(short = 'n', long)]
+        batch_size: Option<usize>,
+        #[arg(
+            long = "db",
+            help = &format!("<{}>: DB engine to store arb data, defaults to mongo", DbEngine::enum_flags())
+        )]
+        db_engine: Option<DbEngine>,
+        #[arg(
+            long,
+            default_value = "replace",
+            help = &format!("<{}>: what to do when an arb for the same event & run is already saved", ConflictPolicy::enum_flags())
+        )]
+        on_conflict: ConflictPolicy,
     },
     /// Export arbs from DB to a JSON file.
     Export {
@@ -66,6 +101,9 @@ pub enum Commands {
         /// Minimum profit of arb to export, in ETH decimal format (e.g. 0.01 => 1e16 wei)
         #[arg(short = 'p', long)]
         min_profit: Option<f64>,
+        /// Only export arbs produced by this scan run.
+        #[arg(long, value_name = "RUN_ID")]
+        run_id: Option<String>,
         /// DB Engine to use to store arb data. Defaults to "mongo".
         /// TODO: DRY this up
         #[arg(
@@ -79,5 +117,88 @@ pub enum Commands {
             help = &format!("<{}>: DB engine to write arb data to, default None (save to file). Ignored if --filename is specified.", DbEngine::enum_flags())
         )]
         write_db: Option<DbEngine>,
+        #[arg(
+            long,
+            default_value = "replace",
+            help = &format!("<{}>: what to do when an arb for the same event & run is already saved in --db-out", ConflictPolicy::enum_flags())
+        )]
+        on_conflict: ConflictPolicy,
+    },
+    /// Print a summary (count & total profit) of arbs in the DB.
+    Stats {
+        /// Summarize arbs starting from this timestamp.
+        #[arg(short, long)]
+        timestamp_start: Option<u32>,
+        /// Stop summarizing arbs at this timestamp.
+        #[arg(long)]
+        timestamp_end: Option<u32>,
+        /// Summarize arbs starting from this block.
+        #[arg(short, long)]
+        block_start: Option<u32>,
+        /// Stop summarizing arbs at this block.
+        #[arg(long)]
+        block_end: Option<u32>,
+        /// Only summarize arbs produced by this scan run.
+        #[arg(long, value_name = "RUN_ID")]
+        run_id: Option<String>,
+        #[arg(
+            long = "db",
+            help = &format!("<{}>: DB engine to read arb data from, defaults to mongo", DbEngine::enum_flags())
+        )]
+        read_db: Option<DbEngine>,
+    },
+    /// Print a report of failed simulations in the DB, grouped by reason.
+    Failures {
+        /// Report failures starting from this timestamp.
+        #[arg(short, long)]
+        timestamp_start: Option<u32>,
+        /// Stop reporting failures at this timestamp.
+        #[arg(long)]
+        timestamp_end: Option<u32>,
+        /// Report failures starting from this block.
+        #[arg(short, long)]
+        block_start: Option<u32>,
+        /// Stop reporting failures at this block.
+        #[arg(long)]
+        block_end: Option<u32>,
+        /// Only report failures from this scan run.
+        #[arg(long, value_name = "RUN_ID")]
+        run_id: Option<String>,
+        #[arg(
+            long = "db",
+            help = &format!("<{}>: DB engine to read failures from, defaults to mongo", DbEngine::enum_flags())
+        )]
+        read_db: Option<DbEngine>,
+    },
+    /// Print the provenance (versions, config, optimizer params) of previous scan runs.
+    Runs {
+        /// Only print this run.
+        #[arg(long, value_name = "RUN_ID")]
+        run_id: Option<String>,
+        #[arg(
+            long = "db",
+            help = &format!("<{}>: DB engine to read runs from, defaults to mongo", DbEngine::enum_flags())
+        )]
+        read_db: Option<DbEngine>,
+    },
+    /// Manage the database.
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Apply pending schema migrations & print the schema version.
+    ///
+    /// Connecting to a new DB applies every migration, but an existing DB is only upgraded
+    /// by this command.
+    Migrate {
+        #[arg(
+            long = "db",
+            help = &format!("<{}>: DB engine to migrate, defaults to mongo", DbEngine::enum_flags())
+        )]
+        db_engine: Option<DbEngine>,
     },
 }
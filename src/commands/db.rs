@@ -0,0 +1,17 @@
+use crate::data::migrations::SchemaDatabase;
+use crate::{info, Result};
+
+/// Applies any pending schema migrations & prints the DB's schema version.
+/// Fails if the DB's schema is newer than this build's.
+pub async fn migrate(db: &SchemaDatabase) -> Result<()> {
+    let applied = db.migrate().await?;
+    if !applied.is_empty() {
+        info!("applied migrations {:?}", applied);
+    }
+    println!(
+        "schema version {} (latest known: {})",
+        db.schema_version().await?,
+        db.latest_schema_version()
+    );
+    Ok(())
+}
@@ -1,12 +1,24 @@
-use crate::data::arbs::{ArbDatabase, ArbFilterParams, WriteEngine};
+use crate::data::arbs::{export_arbs_core, ArbDatabase, ArbFilterParams, WriteEngine};
+use crate::data::{db::Db, FileWriter};
+use crate::shutdown::Shutdown;
 use crate::Result;
 
 pub async fn run(
     params: ArbFilterParams,
     read_db: &ArbDatabase,
     write_dest: WriteEngine,
+    shutdown: &Shutdown,
 ) -> Result<()> {
     println!("exporting arbs... {:?}", params);
-    read_db.export_arbs(write_dest, &params).await?;
+    let src = read_db.as_reader();
+    match write_dest {
+        WriteEngine::File(filename) => {
+            export_arbs_core(src, &FileWriter::new(filename), &params, shutdown).await?
+        }
+        WriteEngine::Db(db_engine) => {
+            let dest = Db::new(db_engine).await.connect;
+            export_arbs_core(src, dest.as_writer(), &params, shutdown).await?
+        }
+    }
     Ok(())
 }
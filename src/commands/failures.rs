@@ -0,0 +1,79 @@
+use crate::data::arbs::ArbFilterParams;
+use crate::data::failures::FailureDatabase;
+use crate::error::FailureReason;
+use crate::interfaces::SimFailure;
+use crate::Result;
+use std::collections::HashMap;
+
+/// Number of failures per reason, with the most recent failure of each as an example.
+#[derive(Clone, Debug, Default)]
+pub struct FailureReport {
+    pub total: usize,
+    pub by_reason: Vec<(FailureReason, usize, SimFailure)>,
+}
+
+impl FailureReport {
+    pub fn new(failures: Vec<SimFailure>) -> Self {
+        let total = failures.len();
+        let mut by_reason = HashMap::<FailureReason, (usize, SimFailure)>::new();
+        for failure in failures {
+            let entry = by_reason
+                .entry(failure.reason)
+                .or_insert((0, failure.clone()));
+            entry.0 += 1;
+            if failure.timestamp > entry.1.timestamp {
+                entry.1 = failure;
+            }
+        }
+        let mut by_reason = by_reason
+            .into_iter()
+            .map(|(reason, (count, example))| (reason, count, example))
+            .collect::<Vec<_>>();
+        by_reason.sort_by(|a, b| b.1.cmp(&a.1));
+        Self { total, by_reason }
+    }
+}
+
+impl std::fmt::Display for FailureReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed sims:\t{}", self.total)?;
+        for (reason, count, example) in &self.by_reason {
+            write!(
+                f,
+                "\n{}:\t{}\n\te.g. {:?} (block {}): {}",
+                reason, count, example.hash, example.block, example.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarizes failed sims in `read_db` that match `params`.
+pub async fn run(params: ArbFilterParams, read_db: &FailureDatabase) -> Result<FailureReport> {
+    println!("summarizing failed sims... {:?}", params);
+    let report = FailureReport::new(read_db.read_failures(&params).await?);
+    println!("{}", report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::HindsightError, interfaces::SimArbResultBatch};
+    use ethers::types::Address;
+
+    #[test]
+    fn it_groups_failures_by_reason() {
+        let event = SimArbResultBatch::test_example().event;
+        let failure = |err: HindsightError| SimFailure::new(None, &event, &err.into());
+        let report = FailureReport::new(vec![
+            failure(HindsightError::AllSwapsReverted),
+            failure(HindsightError::PoolNotFound(Address::zero())),
+            failure(HindsightError::AllSwapsReverted),
+        ]);
+        assert_eq!(report.total, 3);
+        assert_eq!(report.by_reason[0].0, FailureReason::AllSwapsReverted);
+        assert_eq!(report.by_reason[0].1, 2);
+        assert_eq!(report.by_reason[1].0, FailureReason::PoolNotFound);
+    }
+}
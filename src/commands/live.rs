@@ -0,0 +1,285 @@
+use crate::commands::scan::uniswap_topics;
+use crate::data::arbs::ArbDatabase;
+use crate::data::failures::FailureDatabase;
+use crate::error::HindsightError;
+use crate::event_history::event_stream_url;
+use crate::hindsight::Hindsight;
+use crate::pipeline::{Pipeline, PipelineConfig};
+use crate::rpc::blocks::{NewBlocks, Wake};
+use crate::shutdown::Shutdown;
+use crate::util::{filter_events_by_topic, WsClient};
+use crate::{debug, info, warn, Result};
+use ethers::providers::Middleware;
+use ethers::types::{Block, H256};
+use futures::StreamExt;
+use mev_share_sse::{Event, EventClient, EventHistory, Hint};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+/// Hints are dropped if their tx hasn't landed after this many blocks.
+const MAX_PENDING_BLOCKS: u64 = 25;
+/// Max time to wait for a new block before asking the node for the latest one.
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+/// A hint whose tx hasn't landed yet.
+#[derive(Debug)]
+struct PendingHint {
+    event: EventHistory,
+    /// Head block when the hint arrived.
+    seen_at: u64,
+}
+
+/// Hints waiting for their txs to land, by tx hash.
+#[derive(Debug, Default)]
+struct PendingHints {
+    hints: HashMap<H256, PendingHint>,
+}
+
+impl PendingHints {
+    fn add(&mut self, event: EventHistory, head: u64) {
+        self.hints.insert(
+            event.hint.hash,
+            PendingHint {
+                event,
+                seen_at: head,
+            },
+        );
+    }
+
+    /// Removes & returns the hints whose txs landed in `block`, tagged w/ the block's number
+    /// & timestamp. Hints that have waited more than `MAX_PENDING_BLOCKS` are dropped.
+    fn take_landed(&mut self, block: &Block<H256>) -> Vec<EventHistory> {
+        let number = block.number.unwrap_or_default().as_u64();
+        let mut landed = vec![];
+        for tx_hash in &block.transactions {
+            if let Some(pending) = self.hints.remove(tx_hash) {
+                landed.push(EventHistory {
+                    block: number,
+                    timestamp: block.timestamp.as_u64(),
+                    ..pending.event
+                });
+            }
+        }
+        let num_pending = self.hints.len();
+        self.hints
+            .retain(|_, pending| pending.seen_at + MAX_PENDING_BLOCKS >= number);
+        if self.hints.len() < num_pending {
+            debug!(
+                "dropped {} hints that didn't land",
+                num_pending - self.hints.len()
+            );
+        }
+        landed
+    }
+
+    fn len(&self) -> usize {
+        self.hints.len()
+    }
+}
+
+/// Converts a live event into the shape of a historical one.
+/// `block` & `timestamp` are set once its tx lands.
+fn into_event_history(event: Event) -> EventHistory {
+    EventHistory {
+        block: 0,
+        timestamp: 0,
+        hint: Hint {
+            txs: event.transactions,
+            hash: event.hash,
+            logs: event.logs,
+            gas_used: None,
+            mev_gas_price: None,
+        },
+    }
+}
+
+/// Listens to the MEV-Share event stream & simulates each Uniswap event as soon as
+/// its tx lands, saving results to the DB. Runs until `shutdown` is requested, then
+/// waits (up to the shutdown timeout) for landed events to be saved.
+pub async fn run(
+    batch_size: usize,
+    mevshare: &EventClient,
+    hindsight: &Hindsight,
+    write_db: &ArbDatabase,
+    failure_db: &FailureDatabase,
+    shutdown: &Shutdown,
+) -> Result<()> {
+    let client = &hindsight.client;
+    let pipeline = Arc::new(Pipeline::spawn(
+        hindsight.to_owned(),
+        PipelineConfig::new(batch_size),
+        Some(write_db.clone()),
+        Some(failure_db.clone()),
+        None,
+    ));
+    // landed events are submitted by their own task, so a full pipeline doesn't hold up
+    // the event stream or the block watcher
+    let (landed_tx, landed_rx) = mpsc::unbounded_channel();
+    let submitter = spawn_submitter(pipeline.clone(), landed_rx, shutdown.clone());
+    let mut heads = watch_blocks(client.clone(), shutdown.token().clone());
+    let filter_topics = uniswap_topics();
+    let mut pending = PendingHints::default();
+    let mut events = Box::pin(mevshare.events(&event_stream_url()).await?);
+    let mut head = client.get_block_number().await?.as_u64();
+    info!("listening for MEV-Share events at block {}", head);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.requested() => break,
+            _ = landed_tx.closed() => {
+                warn!("simulation pipeline stopped");
+                break;
+            }
+            event = events.next() => match event {
+                Some(Ok(event)) => {
+                    let event = into_event_history(event);
+                    if !filter_events_by_topic(&[event.to_owned()], &filter_topics).is_empty() {
+                        pending.add(event, head);
+                    }
+                }
+                Some(Err(err)) => warn!("MEV-Share event stream error: {:?}", err),
+                None => {
+                    warn!("MEV-Share event stream ended, reconnecting");
+                    events = Box::pin(mevshare.events(&event_stream_url()).await?);
+                }
+            },
+            latest = heads.recv() => {
+                let Some(latest) = latest else { break };
+                // catch up on blocks we missed, but not on ones whose hints would be dropped anyway
+                let first = (head + 1).max(latest.saturating_sub(MAX_PENDING_BLOCKS));
+                for number in first..=latest {
+                    let block = match client.get_block(number).await {
+                        Ok(Some(block)) => block,
+                        Ok(None) => {
+                            let err = HindsightError::BlockNotFound(number);
+                            warn!("{}, retrying on the next block", err);
+                            break;
+                        }
+                        Err(err) => {
+                            warn!(
+                                "failed to fetch block {}, retrying on the next block: {:?}",
+                                number, err
+                            );
+                            break;
+                        }
+                    };
+                    head = number;
+                    let landed = pending.take_landed(&block);
+                    info!(
+                        "block {}: {} hinted txs landed, {} pending",
+                        number,
+                        landed.len(),
+                        pending.len()
+                    );
+                    if !landed.is_empty() {
+                        // only fails if the submitter stopped, which is caught above
+                        let _ = landed_tx.send((landed, head));
+                    }
+                }
+            }
+        }
+    }
+    info!(
+        "stopping; dropping {} hints that haven't landed yet",
+        pending.len()
+    );
+    drop(landed_tx);
+    if let Err(err) = submitter.await? {
+        // the pipeline's own error (if any) is returned by `finish_or_abandon`
+        warn!("failed to submit landed events: {:?}", err);
+    }
+    let pipeline = Arc::try_unwrap(pipeline)
+        .map_err(|_| anyhow::format_err!("simulation pipeline is still in use"))?;
+    let stats = pipeline.finish_or_abandon(shutdown).await?;
+    info!(
+        "simulated {} events ({} ok, {} failed, {} panicked)",
+        stats.succeeded + stats.failed,
+        stats.succeeded,
+        stats.failed,
+        stats.panicked
+    );
+    Ok(())
+}
+
+/// Feeds pages of landed events into `pipeline`, in order, until `landed` is closed.
+/// Gives up on whatever is left once the shutdown timeout expires.
+fn spawn_submitter(
+    pipeline: Arc<Pipeline>,
+    mut landed: mpsc::UnboundedReceiver<(Vec<EventHistory>, u64)>,
+    shutdown: Shutdown,
+) -> JoinHandle<Result<()>> {
+    tokio::spawn(async move {
+        while let Some((events, block)) = landed.recv().await {
+            tokio::select! {
+                _ = shutdown.expired() => return Ok(()),
+                res = pipeline.submit_page(events, block) => res?,
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Sends the latest block number each time a new block arrives. Resubscribes when the
+/// subscription drops, and asks the node for its latest block when none arrives in time,
+/// so errors are only logged & retried. Stops when `shutdown` is cancelled.
+fn watch_blocks(client: WsClient, shutdown: CancellationToken) -> mpsc::Receiver<u64> {
+    let (heads_tx, heads_rx) = mpsc::channel(MAX_PENDING_BLOCKS as usize);
+    tokio::spawn(async move {
+        let mut new_blocks = NewBlocks::subscribe(&client, BLOCK_POLL_INTERVAL).await;
+        loop {
+            let latest = match new_blocks.wait(&shutdown).await {
+                Wake::NewBlock(number) => number,
+                Wake::Timeout => match client.get_block_number().await {
+                    Ok(number) => number.as_u64(),
+                    Err(err) => {
+                        warn!("failed to get the latest block: {:?}", err);
+                        continue;
+                    }
+                },
+                Wake::Shutdown => return,
+            };
+            if heads_tx.send(latest).await.is_err() {
+                return;
+            }
+        }
+    });
+    heads_rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(hash: H256) -> EventHistory {
+        into_event_history(Event {
+            hash,
+            transactions: vec![],
+            logs: vec![],
+        })
+    }
+
+    #[test]
+    fn it_releases_hints_when_their_txs_land() {
+        let (landing, waiting, stale) = (H256::random(), H256::random(), H256::random());
+        let mut pending = PendingHints::default();
+        pending.add(event(landing), 100);
+        pending.add(event(waiting), 100);
+        pending.add(event(stale), 100 - MAX_PENDING_BLOCKS);
+
+        let block = Block {
+            number: Some(101.into()),
+            timestamp: 1_690_000_000.into(),
+            transactions: vec![H256::random(), landing],
+            ..Default::default()
+        };
+        let landed = pending.take_landed(&block);
+        assert_eq!(landed.len(), 1);
+        assert_eq!(landed[0].hint.hash, landing);
+        assert_eq!(landed[0].block, 101);
+        assert_eq!(landed[0].timestamp, 1_690_000_000);
+        // the stale hint is dropped, the other one keeps waiting
+        assert_eq!(pending.len(), 1);
+        assert!(pending.hints.contains_key(&waiting));
+    }
+}
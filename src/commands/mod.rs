@@ -1,2 +1,7 @@
+pub mod db;
 pub mod export;
+pub mod failures;
+pub mod live;
+pub mod runs;
 pub mod scan;
+pub mod stats;
@@ -0,0 +1,18 @@
+use crate::data::runs::RunDatabase;
+use crate::Result;
+
+/// Prints the provenance of the given scan run, or of all runs if `run_id` is None.
+pub async fn run(run_id: Option<String>, db: &RunDatabase) -> Result<()> {
+    let runs = if let Some(run_id) = run_id {
+        vec![db
+            .get_run(&run_id)
+            .await?
+            .ok_or(anyhow::format_err!("no record found for run {}", run_id))?]
+    } else {
+        db.list_runs().await?
+    };
+    for run in runs {
+        println!("{}", serde_json::to_string_pretty(&run)?);
+    }
+    Ok(())
+}
@@ -1,14 +1,26 @@
 use crate::data::arbs::ArbDatabase;
+use crate::data::checkpoints::{CheckpointDatabase, ScanProgress};
 use crate::data::db::DbEngine;
-use crate::event_history::event_history_url;
+use crate::data::failures::FailureDatabase;
+use crate::event_history::{event_history_info_url, fetch_pages, page_size, FETCH_CONCURRENCY};
 use crate::hindsight::Hindsight;
-use crate::info;
-use crate::sim::processor::H256Map;
-use crate::util::{fetch_txs, filter_events_by_topic, WsClient};
+use crate::interfaces::{ScanCheckpoint, ScanRun};
+use crate::pipeline::{Pipeline, PipelineConfig};
+use crate::rpc::{
+    self,
+    blocks::{NewBlocks, Wake},
+};
+use crate::shutdown::Shutdown;
+use crate::sim::backend::SimBackendKind;
+use crate::sim::core::{MAX_DEPTH, STEP_INTERVALS};
+use crate::sim::pricing::EthPriceSource;
+use crate::util::{filter_events_by_topic, pool_registry, WsClient};
 use crate::Result;
+use crate::{debug, info};
+use ethers::providers::Middleware;
 use ethers::types::H256;
-use mev_share_sse::{EventClient, EventHistory, EventHistoryParams};
-use std::str::FromStr;
+use mev_share_sse::{EventClient, EventHistoryParams};
+use std::{str::FromStr, time::Duration};
 
 #[derive(Clone, Debug)]
 pub struct ScanOptions {
@@ -18,6 +30,34 @@ pub struct ScanOptions {
     pub timestamp_start: u32,
     pub timestamp_end: Option<u32>,
     pub db_engine: DbEngine,
+    /// Identifies this scan's progress in the DB.
+    pub run_id: String,
+    /// API offset to start from; non-zero when resuming a run.
+    pub offset: u64,
+    /// Max time to wait for a new block once caught up with the latest events.
+    pub poll_interval: Duration,
+}
+
+impl ScanOptions {
+    /// Scan options that pick up exactly where the given checkpoint left off.
+    pub fn resume(
+        checkpoint: ScanCheckpoint,
+        batch_size: usize,
+        db_engine: DbEngine,
+        poll_interval: Duration,
+    ) -> Self {
+        Self {
+            batch_size,
+            block_start: checkpoint.block_start as u32,
+            block_end: checkpoint.block_end.map(|b| b as u32),
+            timestamp_start: checkpoint.timestamp_start as u32,
+            timestamp_end: checkpoint.timestamp_end.map(|t| t as u32),
+            db_engine,
+            run_id: checkpoint.run_id,
+            offset: checkpoint.offset,
+            poll_interval,
+        }
+    }
 }
 
 impl From<ScanOptions> for EventHistoryParams {
@@ -28,12 +68,62 @@ impl From<ScanOptions> for EventHistoryParams {
             timestamp_start: Some(val.timestamp_start.into()),
             timestamp_end: val.timestamp_end.map(|x| x.into()),
             limit: Some(500),
-            offset: Some(0),
+            offset: Some(val.offset),
         }
     }
 }
 
-fn uniswap_topics() -> Vec<H256> {
+impl From<&ScanOptions> for ScanCheckpoint {
+    fn from(val: &ScanOptions) -> Self {
+        ScanCheckpoint {
+            run_id: val.run_id.to_owned(),
+            block_start: val.block_start.into(),
+            block_end: val.block_end.map(|x| x.into()),
+            timestamp_start: val.timestamp_start.into(),
+            timestamp_end: val.timestamp_end.map(|x| x.into()),
+            offset: val.offset,
+            done: false,
+            updated_at: 0,
+        }
+    }
+}
+
+/// Generates a new (unique enough) scan run id, e.g. `1690000000-1a2b3c4d`.
+pub fn new_run_id() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    format!("{}-{:08x}", now, rand::random::<u32>())
+}
+
+/// Records everything that affects the results of a new scan run.
+pub async fn new_scan_run(
+    run_id: &str,
+    client: &WsClient,
+    eth_price_source: EthPriceSource,
+    sim_backend: SimBackendKind,
+) -> Result<ScanRun> {
+    Ok(ScanRun {
+        run_id: run_id.to_owned(),
+        started_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+        git_version: env!("HINDSIGHT_GIT_VERSION").to_owned(),
+        hindsight_version: env!("CARGO_PKG_VERSION").to_owned(),
+        cli_args: std::env::args().collect(),
+        chain_id: client.get_chainid().await?.as_u64(),
+        rpc_host: rpc::transport(client).inner().hosts().join(","),
+        eth_price_source: eth_price_source.to_string(),
+        sim_backend: Some(sim_backend.to_string()),
+        max_depth: MAX_DEPTH,
+        step_intervals: STEP_INTERVALS,
+        pool_registry: pool_registry(),
+    })
+}
+
+/// Topics of the swap events we can simulate backruns for.
+pub fn uniswap_topics() -> Vec<H256> {
     vec![
         // univ3
         // Swap(address,address,int256,int256,uint160,uint128,int24)
@@ -46,95 +136,120 @@ fn uniswap_topics() -> Vec<H256> {
     ]
 }
 
+/// Scans events through the simulation pipeline, page by page. Without an end bound,
+/// keeps following new events as they're indexed, waking up on each new block.
+///
+/// Stops taking new events once `shutdown` is requested; events already submitted are
+/// still simulated & saved (up to the shutdown timeout), and the run is left unfinished
+/// so it can be resumed.
 pub async fn run(
     params: ScanOptions,
-    ws_client: &WsClient,
     mevshare: &EventClient,
     hindsight: &Hindsight,
     write_db: &ArbDatabase,
+    failure_db: &FailureDatabase,
+    checkpoints: &CheckpointDatabase,
+    shutdown: &Shutdown,
 ) -> Result<()> {
     info!(
-        "scanning events starting at block={:?} timestamp={:?}",
-        params.block_start, params.timestamp_start
+        "scanning events starting at block={:?} timestamp={:?} (run={}, offset={})",
+        params.block_start, params.timestamp_start, params.run_id, params.offset
     );
 
     let mut event_params: EventHistoryParams = params.clone().into();
+    let mut progress = ScanProgress::new(checkpoints.clone(), (&params).into());
+    // register the run right away so it can be resumed even if the first page never finishes
+    progress.save_offset(progress.offset()).await?;
+
+    /* Events are streamed through the pipeline as pages come in from the API.
+       The pipeline saves the checkpoint offset as pages are completed, and `submit_page`
+       waits while the pipeline is full, so we never fetch more than `FETCH_CONCURRENCY`
+       pages further ahead than we simulate.
+    */
+    let pipeline = Pipeline::spawn(
+        hindsight.to_owned(),
+        PipelineConfig::new(params.batch_size),
+        Some(write_db.clone()),
+        Some(failure_db.clone()),
+        Some(progress.clone()),
+    );
 
     let filter_topics = uniswap_topics();
+    let mut new_blocks = NewBlocks::subscribe(&hindsight.client, params.poll_interval).await;
+    let info = mevshare
+        .event_history_info(&event_history_info_url())
+        .await?;
+    let page_size = page_size(&event_params, &info);
+    // pages fetched at once; just one while we're waiting for new events at the tip
+    let mut num_pages = FETCH_CONCURRENCY;
     /* ========================== event processing ====================================== */
-    loop {
-        // fetch events
-        let events = mevshare
-            .event_history(&event_history_url(), event_params.to_owned())
-            .await?;
-        // if the api returns 0 results, we've completely run out of events to process
-        // so wait, then restart loop
-        if events.is_empty() {
-            // sleep 12s to allow for new events to be indexed
-            std::thread::sleep(std::time::Duration::from_secs(12));
-            continue;
+    // true if we reached the end of the range; false if we were told to stop
+    let range_done = loop {
+        if shutdown.is_requested() {
+            break false;
         }
-
-        // update params for next batch of events
-        event_params.offset = Some(event_params.offset.unwrap() + events.len() as u64);
-
-        info!(
-            "fetched {} events. first event timestamp={}",
-            events.len(),
-            events[0].timestamp
-        );
-        // filter out irrelevant events
-        let events = filter_events_by_topic(&events, &filter_topics);
-        info!(
-            "filtered for uniswap events. {} events ready to process.",
-            events.len()
-        );
-        // map events by hash for fast lookups
-        let event_map = events
-            .iter()
-            .map(|event| (event.hint.hash, event.to_owned()))
-            .collect::<H256Map<EventHistory>>();
-
-        let mut events_offset = 0;
-        let mut txs = vec![];
-
-        // Concurrently fetch all landed txs for each event.
-        // Only request `batch_size` at a time to avoid overloading the RPC endpoint.
-        while events_offset < events.len() {
-            let this_batch = events
-                .iter()
-                .skip(events_offset)
-                .take(params.batch_size)
-                .map(|event| event.to_owned())
-                .collect::<Vec<EventHistory>>();
-            events_offset += this_batch.len();
-            // get txs for relevant events
-            txs.append(&mut fetch_txs(ws_client, &this_batch).await?);
+        // fetch the next few pages of events at once; they're still submitted in order
+        let pages = fetch_pages(mevshare, &event_params, page_size, num_pages).await?;
+        let mut caught_up = false;
+        for (events, next_offset) in pages {
+            // if the api returns < page_size (duplicates included), we're processing the most recent events
+            caught_up = next_offset - event_params.offset.unwrap_or_default() < page_size;
+            event_params.offset = Some(next_offset);
+            let page_len = events.len();
+            if events.is_empty() {
+                continue;
+            }
+            info!(
+                "fetched {} events. first event timestamp={}",
+                page_len, events[0].timestamp
+            );
+            // filter out irrelevant events
+            let events = filter_events_by_topic(&events, &filter_topics);
+            // skip events already processed in this run (e.g. before a crash mid-page)
+            let events = progress.filter_unprocessed(events).await?;
+            info!(
+                "filtered for uniswap events. {} events ready to process.",
+                events.len()
+            );
+            pipeline.submit_page(events, next_offset).await?;
         }
 
-        /* ========================== batch-sized arb processing ========================
-           Here, *at least* `batch_size` txs should be passed to `process_orderflow`.
-           In `process_orderflow`, *at most* `batch_size` txs are simulated at a time.
-           The last iteration will process only (remaining_txs % batch_size) txs, so it's
-           most efficient when (txs.len() % batch_size == 0) and/or (txs.len() much greater than batch_size).
-        */
-        hindsight
-            .to_owned()
-            .process_orderflow(&txs, params.batch_size, Some(write_db.clone()), event_map)
-            .await?;
-        info!("simulated arbs for {} transactions", txs.len());
-        info!("offset: {:?}", event_params.offset);
-
-        // if the api returns < limit, we're processing the most recent events
-        // so we pause to avoid the loop spamming the api
-        if events.len() < event_params.limit.unwrap_or(500) as usize {
+        num_pages = if caught_up { 1 } else { FETCH_CONCURRENCY };
+        if caught_up {
             if params.block_end.is_some() || params.timestamp_end.is_some() {
                 // if we're processing a specific block range, we're done
-                break;
+                break true;
+            }
+            // wait for the next block, so its events can be indexed
+            match new_blocks.wait(shutdown.token()).await {
+                Wake::NewBlock(block) => debug!("block {} arrived, polling for events", block),
+                Wake::Timeout => debug!("no new block, polling for events"),
+                Wake::Shutdown => break false,
             }
-            // sleep 12s to allow for new events to be indexed
-            std::thread::sleep(std::time::Duration::from_secs(12));
         }
+    };
+    if !range_done {
+        info!("stopping scan; waiting for events in flight to finish");
+    }
+
+    let stats = pipeline.finish_or_abandon(shutdown).await?;
+    info!(
+        "simulated {} events ({} ok, {} failed, {} panicked)",
+        stats.succeeded + stats.failed,
+        stats.succeeded,
+        stats.failed,
+        stats.panicked
+    );
+    // some events may have been abandoned if we were told to stop
+    if range_done && !shutdown.is_requested() {
+        // every page is saved by now, so the pipeline's last offset is the end of the range
+        progress
+            .save_offset(event_params.offset.unwrap_or_default())
+            .await?;
+        progress.finish().await?;
+    } else {
+        // the pipeline has checkpointed every page it completed
+        info!("scan stopped; resume with --resume {}", params.run_id);
     }
     Ok(())
 }
@@ -0,0 +1,63 @@
+use crate::data::arbs::{ArbDatabase, ArbFilterParams};
+use crate::Result;
+use ethers::{types::U256, utils::format_ether};
+
+const NUM_ARBS_PER_READ: i64 = 3000;
+
+/// Summary of the arbs matching a filter.
+#[derive(Clone, Debug, Default)]
+pub struct ArbStats {
+    pub num_arbs: u64,
+    /// Number of arbs with non-zero profit.
+    pub num_profitable: u64,
+    pub total_profit: U256,
+    pub total_profit_usd: f64,
+    /// Number of arbs with no USD price; these are excluded from `total_profit_usd`.
+    pub num_unpriced: u64,
+}
+
+impl std::fmt::Display for ArbStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "arbs:\t\t{}", self.num_arbs)?;
+        writeln!(f, "profitable:\t{}", self.num_profitable)?;
+        writeln!(f, "total profit:\t{} Ξ", format_ether(self.total_profit))?;
+        write!(
+            f,
+            "total profit:\t${:.2} ({} arbs unpriced)",
+            self.total_profit_usd, self.num_unpriced
+        )
+    }
+}
+
+/// Sums up the arbs in `read_db` that match `params`.
+pub async fn run(params: ArbFilterParams, read_db: &ArbDatabase) -> Result<ArbStats> {
+    println!("summarizing arbs... {:?}", params);
+    // count first so a concurrent scan can't keep us reading forever
+    let total_arbs = read_db.get_num_arbs(&params).await?;
+    let mut stats = ArbStats::default();
+    let mut num_read = 0;
+    let mut cursor = None;
+    while num_read < total_arbs {
+        let page = read_db
+            .read_arbs_page(&params, cursor, NUM_ARBS_PER_READ)
+            .await?;
+        num_read += page.arbs.len() as u64;
+        for arb in page.arbs {
+            stats.num_arbs += 1;
+            if !arb.max_profit.is_zero() {
+                stats.num_profitable += 1;
+            }
+            stats.total_profit += arb.max_profit;
+            match arb.max_profit_usd {
+                Some(usd) => stats.total_profit_usd += usd,
+                None => stats.num_unpriced += 1,
+            }
+        }
+        cursor = match page.next {
+            Some(next) => Some(next),
+            None => break,
+        };
+    }
+    println!("{}", stats);
+    Ok(stats)
+}
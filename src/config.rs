@@ -1,12 +1,35 @@
-use crate::debug;
+use crate::{
+    debug,
+    sim::{backend::SimBackendKind, pricing::EthPriceSource},
+};
 use std::{env, path::PathBuf};
 
 #[derive(Clone, Debug)]
 pub struct Config {
-    pub rpc_url_ws: String,
-    pub mongo_url: String,
+    /// RPC nodes to send requests to (ws://, wss://, http:// or https://).
+    pub rpc_urls: Vec<String>,
+    pub mongo_url: Option<String>,
     pub postgres_url: Option<String>,
+    /// File the sqlite engine stores data in.
+    pub sqlite_path: PathBuf,
+    /// HTTP interface of the ClickHouse server, e.g. http://localhost:8123.
+    pub clickhouse_url: Option<String>,
+    pub clickhouse_user: String,
+    pub clickhouse_password: String,
     pub tls_ca_file_mongo: Option<PathBuf>,
+    pub eth_price_source: EthPriceSource,
+    /// Max requests per second sent to the RPC node; 0 disables the limit.
+    pub rpc_requests_per_second: f64,
+    /// Times an RPC request is retried after a transient error (e.g. rate limited).
+    pub rpc_max_retries: u32,
+    /// EVM that simulations run on.
+    pub sim_backend: SimBackendKind,
+    /// Url of a running Anvil-compatible node for the anvil sim backend; if None, anvil is spawned.
+    pub anvil_url: Option<String>,
+    /// Anvil binary spawned by the anvil sim backend.
+    pub anvil_path: String,
+    /// Number of anvil nodes spawned, so sims of different blocks can run at once.
+    pub anvil_nodes: usize,
 }
 
 impl Default for Config {
@@ -17,10 +40,54 @@ impl Default for Config {
             debug!("{}", err);
         }
         Config {
-            mongo_url: env::var("MONGO_URL").expect("MONGO_URL must be set"),
+            mongo_url: env::var("MONGO_URL").ok(),
             postgres_url: env::var("POSTGRES_URL").ok(),
-            rpc_url_ws: env::var("RPC_URL_WS").expect("RPC_URL_WS must be set"),
+            sqlite_path: env::var("SQLITE_PATH")
+                .unwrap_or("hindsight.sqlite".to_owned())
+                .into(),
+            clickhouse_url: env::var("CLICKHOUSE_URL").ok(),
+            clickhouse_user: env::var("CLICKHOUSE_USER").unwrap_or("default".to_owned()),
+            clickhouse_password: env::var("CLICKHOUSE_PASSWORD").unwrap_or_default(),
+            rpc_urls: rpc_urls_from_env(),
             tls_ca_file_mongo: env::var("TLS_CA_FILE_MONGO").map(|s| s.into()).ok(),
+            eth_price_source: env::var("ETH_USD_PRICE_SOURCE")
+                .map(|s| s.parse().expect("invalid ETH_USD_PRICE_SOURCE"))
+                .unwrap_or_default(),
+            rpc_requests_per_second: env::var("RPC_REQUESTS_PER_SECOND")
+                .map(|s| s.parse().expect("invalid RPC_REQUESTS_PER_SECOND"))
+                .unwrap_or(100.0),
+            rpc_max_retries: env::var("RPC_MAX_RETRIES")
+                .map(|s| s.parse().expect("invalid RPC_MAX_RETRIES"))
+                .unwrap_or(5),
+            sim_backend: env::var("SIM_BACKEND")
+                .map(|s| s.parse().expect("invalid SIM_BACKEND"))
+                .unwrap_or_default(),
+            anvil_url: env::var("ANVIL_URL").ok(),
+            anvil_path: env::var("ANVIL_PATH").unwrap_or("anvil".to_owned()),
+            anvil_nodes: env::var("ANVIL_NODES")
+                .map(|s| s.parse().expect("invalid ANVIL_NODES"))
+                .unwrap_or(4),
         }
     }
 }
+
+/// Reads RPC urls from `RPC_URLS` (comma-separated), plus `RPC_URL_WS` if it's set.
+fn rpc_urls_from_env() -> Vec<String> {
+    let mut rpc_urls = env::var("RPC_URLS")
+        .map(|urls| {
+            urls.split(',')
+                .map(|url| url.trim().to_owned())
+                .filter(|url| !url.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if let Ok(url) = env::var("RPC_URL_WS") {
+        if !rpc_urls.contains(&url) {
+            rpc_urls.insert(0, url);
+        }
+    }
+    if rpc_urls.is_empty() {
+        panic!("RPC_URL_WS or RPC_URLS must be set");
+    }
+    rpc_urls
+}
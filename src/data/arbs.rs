@@ -1,17 +1,20 @@
-use futures::future::join_all;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::db::DbEngine;
 use crate::{
-    data::{db::Db, file::FileWriter},
-    info,
+    debug, info,
     interfaces::{SimArbResultBatch, StoredArbsRanges},
-    Result,
+    shutdown::Shutdown,
+    Error, Result,
 };
 use async_trait::async_trait;
 use deadqueue::unlimited::Queue;
-use ethers::{types::U256, utils::format_ether};
+use ethers::{
+    types::{H256, U256},
+    utils::format_ether,
+};
+use strum::IntoEnumIterator;
 
 const NUM_ARBS_PER_READ: i64 = 3000;
 
@@ -22,6 +25,8 @@ pub struct ArbFilterParams {
     pub timestamp_start: Option<u32>,
     pub timestamp_end: Option<u32>,
     pub min_profit: Option<U256>,
+    /// Only match arbs produced by this scan run.
+    pub run_id: Option<String>,
 }
 
 impl Default for ArbFilterParams {
@@ -38,79 +43,196 @@ impl ArbFilterParams {
             timestamp_start: None,
             timestamp_end: None,
             min_profit: None,
+            run_id: None,
+        }
+    }
+}
+
+/// What a writer does when an arb for the same event (hint hash) & run id is already saved.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumIter,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Overwrite the saved arb.
+    #[default]
+    Replace,
+    /// Keep whichever arb has the higher `max_profit`.
+    KeepBestProfit,
+    /// Save the new arb alongside the saved one.
+    KeepBoth,
+}
+
+/// How to save an arb, as decided by a `ConflictPolicy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictAction {
+    /// Save the arb; keep any saved arbs for the same event & run.
+    Insert,
+    /// Delete the saved arbs for the same event & run, then save the arb.
+    Replace,
+    /// Don't save the arb.
+    Skip,
+}
+
+impl ConflictPolicy {
+    pub fn enum_flags() -> String {
+        ConflictPolicy::iter()
+            .map(|policy| policy.to_string())
+            .reduce(|a, b| format!("{} | {}", a, b))
+            .expect("failed to reduce conflict policies to string")
+    }
+
+    /// Decides how to save an arb w/ `new_profit`, given the profits of the arbs
+    /// already saved for the same event & run.
+    pub fn resolve(&self, saved_profits: &[U256], new_profit: U256) -> ConflictAction {
+        if saved_profits.is_empty() {
+            return ConflictAction::Insert;
+        }
+        match self {
+            ConflictPolicy::Replace => ConflictAction::Replace,
+            ConflictPolicy::KeepBestProfit => {
+                if saved_profits.iter().any(|profit| *profit >= new_profit) {
+                    ConflictAction::Skip
+                } else {
+                    ConflictAction::Replace
+                }
+            }
+            ConflictPolicy::KeepBoth => ConflictAction::Insert,
         }
     }
 }
 
+/// Where a page of arbs left off. Paged reads return arbs in (timestamp, hash) order,
+/// so the next page starts right after the last arb of the previous one.
+/// Pages never end partway through the arbs of one event, so no arbs are skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ArbCursor {
+    pub timestamp: u64,
+    pub hash: H256,
+}
+
+impl From<&SimArbResultBatch> for ArbCursor {
+    fn from(arb: &SimArbResultBatch) -> Self {
+        Self {
+            timestamp: arb.event.timestamp,
+            hash: arb.event.hint.hash,
+        }
+    }
+}
+
+impl ArbCursor {
+    /// Cursor for the page after `read`, which was read w/ `limit`; None if `read` was the last page.
+    pub fn next(read: &[SimArbResultBatch], limit: i64) -> Option<Self> {
+        if (read.len() as i64) < limit {
+            return None;
+        }
+        read.last().map(Self::from)
+    }
+}
+
+/// A page of arbs read with `ArbReader::read_arbs_page`.
+#[derive(Clone, Debug, Default)]
+pub struct ArbPage {
+    pub arbs: Vec<SimArbResultBatch>,
+    /// Where to read the next page from; None if this was the last page.
+    pub next: Option<ArbCursor>,
+}
+
 #[derive(Clone, Debug)]
 pub enum WriteEngine {
     File(Option<String>),
     Db(DbEngine),
 }
 
+/// Reads saved arbs.
 #[async_trait]
-pub trait ArbDb: Sync + Send {
-    async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()>;
+pub trait ArbReader: Sync + Send {
     async fn read_arbs(
         &self,
         filter_params: &ArbFilterParams,
         offset: Option<u64>,
         limit: Option<i64>,
     ) -> Result<Vec<SimArbResultBatch>>;
-    async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64>;
-    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges>;
-    async fn export_arbs(
+    /// Reads up to `limit` arbs matching `filter_params` that come after `after`, in (timestamp, hash) order.
+    /// Pages are longer than `limit` when needed to include every arb of their last event.
+    ///
+    /// Unlike paging w/ `read_arbs`' offset, every page costs the same no matter how deep it is.
+    async fn read_arbs_page(
         &self,
-        write_dest: WriteEngine,
         filter_params: &ArbFilterParams,
-    ) -> Result<()>;
+        after: Option<ArbCursor>,
+        limit: i64,
+    ) -> Result<ArbPage>;
+    async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64>;
+    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges>;
 }
 
-/// Saves arbs to given write engine (file or db).
+/// Saves arbs. An arb whose event & run id are already saved is handled according
+/// to the writer's `ConflictPolicy`, so writing the same arbs twice is safe.
+#[async_trait]
+pub trait ArbWriter: Sync + Send {
+    async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()>;
+}
+
+/// A DB that arbs can be both read from & written to.
+pub trait ArbStore: ArbReader + ArbWriter {
+    fn as_reader(&self) -> &dyn ArbReader;
+    fn as_writer(&self) -> &dyn ArbWriter;
+}
+
+impl<T: ArbReader + ArbWriter> ArbStore for T {
+    fn as_reader(&self) -> &dyn ArbReader {
+        self
+    }
+    fn as_writer(&self) -> &dyn ArbWriter {
+        self
+    }
+}
+
+/// Copies the arbs in `src` that match `filter_params` to `dest`.
+///
+/// Once `shutdown` is requested, no more arbs are read, but the ones already read are still written.
 pub async fn export_arbs_core(
-    src: Arc<dyn ArbDb>,
-    write_dest: WriteEngine,
+    src: &dyn ArbReader,
+    dest: &dyn ArbWriter,
     filter_params: &ArbFilterParams,
+    shutdown: &Shutdown,
 ) -> Result<()> {
-    /* Spawns a reader thread and a writer thread.
-       Reader thread reads arbs from `src` and pushes them to a thread-safe queue.
-       Writer thread pops arbs from the queue and writes them to `write_dest`.
-       When the reader thread is done, it unlocks a mutex that the writer thread is waiting on.
-       When the writer thread is done, it quits and the function returns.
+    /* Runs a reader and a writer concurrently.
+       The reader reads arbs from `src` and pushes them to a queue.
+       The writer pops arbs from the queue and writes them to `dest`.
+       When the reader is done, it unlocks a mutex that the writer is waiting on.
+       When the writer is done, it quits and the function returns.
     */
 
     // determine total number of arbs now to prevent running forever in case `scan` is running concurrently
     let total_arbs = src.get_num_arbs(filter_params).await?;
     info!("total arbs: {}", total_arbs);
-    let offset_lock = Arc::new(Mutex::new(0));
-
-    // thread-safe queue
-    let arb_queue_handle: Arc<Queue<SimArbResultBatch>> = Arc::new(Queue::new());
-    // thread-safe mutex to keep writer thread from quitting before we're done reading
-    let process_done = Arc::new(Mutex::new(()));
-
-    // arc clones to give to the reader thread
-    let arb_queue = arb_queue_handle.clone();
-    let filter_params = filter_params.clone();
-    let lock = process_done.clone();
-
-    // spawn reader thread
-    let read_handle = tokio::spawn(async move {
-        info!("starting reader thread...");
-        // lock process_done to keep writer thread from quitting before we're done reading
-        let _process_lock = lock.lock().await;
+
+    let arb_queue: Queue<SimArbResultBatch> = Queue::new();
+    // mutex to keep the writer from quitting before we're done reading
+    let process_done = Mutex::new(());
+    // locked before either side starts, so the writer can't see it unlocked too early
+    let process_lock = process_done.lock().await;
+
+    let read = async {
+        info!("starting reader...");
+        // process_lock is dropped when the reader returns, unlocking the process_done mutex
+        let _process_lock = process_lock;
         // read NUM_ARBS_PER_READ arbs at a time
-        let mut offset = offset_lock.lock().await;
-        while *offset < total_arbs {
-            let arbs = src
-                .read_arbs(&filter_params, Some(*offset), Some(NUM_ARBS_PER_READ))
-                .await
-                .expect("failed to read arbs");
-            if arbs.is_empty() {
+        let mut num_read = 0;
+        let mut cursor = None;
+        while num_read < total_arbs {
+            if shutdown.is_requested() {
+                info!("stopping reader after {} arbs", num_read);
                 break;
             }
-            *offset += NUM_ARBS_PER_READ as u64;
-            println!("offset {}", offset);
+            let page = src
+                .read_arbs_page(filter_params, cursor, NUM_ARBS_PER_READ)
+                .await?;
+            let arbs = page.arbs;
+            num_read += arbs.len() as u64;
+            debug!("read {} arbs, up to {:?}", num_read, page.next);
             let start_block = arbs.iter().map(|arb| arb.event.block).min().unwrap_or(0);
             let end_block = arbs
                 .iter()
@@ -138,67 +260,87 @@ pub async fn export_arbs_core(
             );
 
             for arb in arbs {
-                println!("im arb: {:?}", arb.event.hint.hash);
                 arb_queue.push(arb);
-                println!("arb q: len {}", arb_queue.len());
             }
-            // arb_lock is dropped here, unlocking the arb_queue mutex
+            cursor = match page.next {
+                Some(next) => Some(next),
+                None => break,
+            };
         }
-        // _process_lock is dropped here, unlocking the process_done mutex
-    });
-
-    // arc clone to give to the writer thread
-    let arb_queue = arb_queue_handle.clone();
-
-    // init chosen write engine
-    let write_engine = match write_dest.clone() {
-        WriteEngine::File(filename) => Arc::new(FileWriter::new(filename)),
-        WriteEngine::Db(db_engine) => Db::new(db_engine).await.connect,
+        Ok::<_, Error>(())
     };
 
-    let total_arbs = Arc::new(Mutex::new(0));
-    // start writer thread
-    let all_arbs = total_arbs.clone();
-    let write_handle = tokio::spawn(async move {
-        info!("starting writer thread...");
+    let write = async {
+        info!("starting writer...");
+        let mut total_written = 0;
         loop {
-            println!("[w] arb q {}", arb_queue.len());
             let mut batch_arbs = vec![];
             for _ in 0..arb_queue.len() {
                 let arb = arb_queue.pop().await;
                 batch_arbs.push(arb);
             }
 
-            info!("finna write {} arbs", batch_arbs.len());
             let batch_len = batch_arbs.len();
             if batch_len > 0 {
-                write_engine
-                    .write_arbs(&batch_arbs)
-                    .await
-                    .expect("failed to write arbs");
+                dest.write_arbs(&batch_arbs).await?;
                 info!("exported {} arbs", batch_len);
-                let total_arbs = all_arbs.clone();
-                let mut total_arbs = total_arbs.lock().await;
-                *total_arbs += batch_len;
+                total_written += batch_len;
             } else {
-                info!("no arbs to write, sleeping...");
+                debug!("no arbs to write, sleeping...");
                 tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
             }
 
-            // if process_done is unlocked, reader thread is done
+            // if process_done is unlocked, the reader is done
             // then if our queue is empty, we can quit
             if process_done.try_lock().is_ok() && arb_queue.len() == 0 {
-                info!("reader thread done, writer thread quitting...");
+                info!("reader done, writer quitting...");
                 break;
             }
         }
-    });
+        Ok::<_, Error>(total_written)
+    };
 
-    join_all(vec![read_handle, write_handle]).await;
+    // if the writer fails, the reader still finishes (the queue is unbounded), so joining both is safe
+    let (read_res, write_res) = tokio::join!(read, write);
+    read_res?;
+    let total_written = write_res?;
 
-    info!("wrote total of {} arbs", total_arbs.lock().await);
+    info!("wrote total of {} arbs", total_written);
 
     Ok(())
 }
 
-pub type ArbDatabase = Arc<dyn ArbDb>;
+pub type ArbDatabase = Arc<dyn ArbStore>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_resolves_conflicts() {
+        let saved = [U256::from(10)];
+        for policy in ConflictPolicy::iter() {
+            assert_eq!(policy.resolve(&[], 1.into()), ConflictAction::Insert);
+        }
+        assert_eq!(
+            ConflictPolicy::Replace.resolve(&saved, 1.into()),
+            ConflictAction::Replace
+        );
+        assert_eq!(
+            ConflictPolicy::KeepBestProfit.resolve(&saved, 10.into()),
+            ConflictAction::Skip
+        );
+        assert_eq!(
+            ConflictPolicy::KeepBestProfit.resolve(&saved, 11.into()),
+            ConflictAction::Replace
+        );
+        assert_eq!(
+            ConflictPolicy::KeepBoth.resolve(&saved, 1.into()),
+            ConflictAction::Insert
+        );
+        assert_eq!(
+            "keep-best-profit".parse::<ConflictPolicy>().unwrap(),
+            ConflictPolicy::KeepBestProfit
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    interfaces::{ProcessedEvent, ScanCheckpoint},
+    Result,
+};
+use async_trait::async_trait;
+use ethers::types::H256;
+use mev_share_sse::EventHistory;
+use std::sync::Arc;
+
+/// Stores scan progress separately from arb results, so that a scan can be resumed
+/// exactly where it left off, even if it hasn't found any arbs recently.
+#[async_trait]
+pub trait CheckpointDb: Sync + Send {
+    /// Insert or update the checkpoint for `checkpoint.run_id`.
+    async fn save_checkpoint(&self, checkpoint: &ScanCheckpoint) -> Result<()>;
+    /// Get the checkpoint for the given run.
+    async fn get_checkpoint(&self, run_id: &str) -> Result<Option<ScanCheckpoint>>;
+    /// Get the most recently updated checkpoint of a run that hasn't finished its range.
+    async fn get_latest_unfinished_checkpoint(&self) -> Result<Option<ScanCheckpoint>>;
+    /// Record events as processed for a run.
+    async fn mark_events_processed(&self, events: &[ProcessedEvent]) -> Result<()>;
+    /// Returns the subset of `hashes` that have already been processed in the given run.
+    async fn filter_processed(&self, run_id: &str, hashes: &[H256]) -> Result<Vec<H256>>;
+}
+
+pub type CheckpointDatabase = Arc<dyn CheckpointDb>;
+
+/// Tracks progress of a single scan run.
+#[derive(Clone)]
+pub struct ScanProgress {
+    db: CheckpointDatabase,
+    checkpoint: ScanCheckpoint,
+}
+
+impl ScanProgress {
+    pub fn new(db: CheckpointDatabase, checkpoint: ScanCheckpoint) -> Self {
+        Self { db, checkpoint }
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.checkpoint.run_id
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.checkpoint.offset
+    }
+
+    /// Saves the API offset of the next page to fetch.
+    pub async fn save_offset(&mut self, offset: u64) -> Result<()> {
+        self.checkpoint.offset = offset;
+        self.save().await
+    }
+
+    /// Marks the run as having reached the end of its range.
+    pub async fn finish(&mut self) -> Result<()> {
+        self.checkpoint.done = true;
+        self.save().await
+    }
+
+    async fn save(&mut self) -> Result<()> {
+        self.checkpoint.updated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        self.db.save_checkpoint(&self.checkpoint).await
+    }
+
+    /// Records `events` as processed in this run.
+    pub async fn mark_processed(&self, events: &[EventHistory]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let processed = events
+            .iter()
+            .map(|event| ProcessedEvent {
+                run_id: self.checkpoint.run_id.to_owned(),
+                hash: event.hint.hash,
+                block: event.block,
+                timestamp: event.timestamp,
+            })
+            .collect::<Vec<_>>();
+        self.db.mark_events_processed(&processed).await
+    }
+
+    /// Removes events that have already been processed in this run.
+    pub async fn filter_unprocessed(&self, events: Vec<EventHistory>) -> Result<Vec<EventHistory>> {
+        if events.is_empty() {
+            return Ok(events);
+        }
+        let hashes = events.iter().map(|e| e.hint.hash).collect::<Vec<_>>();
+        let processed = self
+            .db
+            .filter_processed(&self.checkpoint.run_id, &hashes)
+            .await?;
+        Ok(events
+            .into_iter()
+            .filter(|event| !processed.contains(&event.hint.hash))
+            .collect())
+    }
+}
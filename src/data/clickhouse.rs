@@ -0,0 +1,1056 @@
+use super::arbs::{
+    ArbCursor, ArbFilterParams, ArbPage, ArbReader, ArbWriter, ConflictAction, ConflictPolicy,
+};
+use super::checkpoints::CheckpointDb;
+use super::failures::FailureDb;
+use super::migrations::{
+    check_version, ensure_writable, latest_version, Migration, SchemaDb, CLICKHOUSE_MIGRATIONS,
+};
+use super::runs::RunDb;
+use super::tokens::TokenDb;
+use crate::{
+    debug, info,
+    interfaces::{
+        ProcessedEvent, ScanCheckpoint, ScanRun, SimArbResultBatch, SimFailure, StoredArbsRanges,
+        TokenMetadata,
+    },
+    Result,
+};
+use async_trait::async_trait;
+use ethers::{
+    types::{Address, H256, U256},
+    utils::format_ether,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr};
+
+const DB_NAME: &str = "hindsight";
+const ARBS_TABLE: &str = "arbs";
+const ARB_TRADES_TABLE: &str = "arb_trades";
+const TOKENS_TABLE: &str = "tokens";
+const CHECKPOINTS_TABLE: &str = "scan_checkpoints";
+const PROCESSED_EVENTS_TABLE: &str = "processed_events";
+const RUNS_TABLE: &str = "scan_runs";
+const FAILURES_TABLE: &str = "sim_failures";
+const MIGRATIONS_TABLE: &str = "schema_migrations";
+
+/// Talks to ClickHouse over its HTTP interface.
+pub struct ClickhouseConnect {
+    client: reqwest::Client,
+    config: ClickhouseConfig,
+    database: String,
+    /// Schema version of the DB when we connected. Writes are refused if it's newer than ours.
+    schema_version: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClickhouseConfig {
+    pub url: String,
+    pub user: String,
+    pub password: String,
+    pub conflict_policy: ConflictPolicy,
+}
+
+impl Default for ClickhouseConfig {
+    fn default() -> Self {
+        let config = crate::config::Config::default();
+        Self {
+            url: config.clickhouse_url.unwrap_or_default(),
+            user: config.clickhouse_user,
+            password: config.clickhouse_password,
+            conflict_policy: ConflictPolicy::default(),
+        }
+    }
+}
+
+/// `value` as a ClickHouse string literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Matches rows w/ `run_id`, where a missing run id only matches missing run ids.
+fn run_id_filter(run_id: &Option<String>) -> String {
+    match run_id {
+        Some(run_id) => format!("run_id = {}", quote(run_id)),
+        None => "run_id IS NULL".to_owned(),
+    }
+}
+
+/// Filters rows by the block, timestamp & run id of `filter`.
+fn event_filter(filter: &ArbFilterParams) -> String {
+    let mut params = vec!["1".to_owned()];
+    if let Some(block_start) = filter.block_start {
+        params.push(format!("event_block >= {}", block_start));
+    }
+    if let Some(block_end) = filter.block_end {
+        params.push(format!("event_block <= {}", block_end));
+    }
+    if let Some(timestamp_start) = filter.timestamp_start {
+        params.push(format!(
+            "event_timestamp >= toDateTime({})",
+            timestamp_start
+        ));
+    }
+    if let Some(timestamp_end) = filter.timestamp_end {
+        params.push(format!("event_timestamp <= toDateTime({})", timestamp_end));
+    }
+    if let Some(run_id) = &filter.run_id {
+        params.push(format!("run_id = {}", quote(run_id)));
+    }
+    params.join(" AND ")
+}
+
+fn where_filter(filter: &ArbFilterParams) -> String {
+    let mut params = event_filter(filter);
+    if let Some(min_profit) = filter.min_profit {
+        params.push_str(&format!(" AND max_profit >= toUInt256('{}')", min_profit));
+    }
+    params
+}
+
+/// Selects arbs matching `filter` in (timestamp, hash) order, as `ArbRow`s.
+fn select_arbs_query(filter: &str, offset: Option<u64>, limit: Option<i64>) -> String {
+    let mut query = format!(
+        "SELECT toString(arb_id) AS arb_id, arb FROM {} WHERE {} ORDER BY event_timestamp, tx_hash, arb_id",
+        ARBS_TABLE, filter
+    );
+    if let Some(limit) = limit {
+        query.push_str(&format!(" LIMIT {}", limit));
+    }
+    if let Some(offset) = offset {
+        query.push_str(&format!(" OFFSET {}", offset));
+    }
+    query
+}
+
+/// Splits a migration script into statements, since the HTTP interface runs one per request.
+///
+/// Comments are stripped first (ClickHouse rejects a comment-only query), then the script is
+/// split on `;` at the end of a line.
+fn split_statements(script: &str) -> Vec<String> {
+    let uncommented = script
+        .lines()
+        .map(|line| line.split("--").next().unwrap_or_default().trim_end())
+        .collect::<Vec<_>>()
+        .join("\n");
+    uncommented
+        .split(";\n")
+        .map(|statement| statement.trim().trim_end_matches(';').trim().to_owned())
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+/// A random id to link an arb's summary to its trades.
+fn new_arb_id() -> String {
+    let id = format!("{:032x}", rand::random::<u128>());
+    format!(
+        "{}-{}-{}-{}-{}",
+        &id[..8],
+        &id[8..12],
+        &id[12..16],
+        &id[16..20],
+        &id[20..]
+    )
+}
+
+fn eth(wei: U256) -> f64 {
+    format_ether(wei).parse().unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize)]
+struct ArbRow {
+    arb_id: String,
+    arb: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ArbSummaryRow {
+    arb_id: String,
+    tx_hash: String,
+    run_id: Option<String>,
+    event_block: u64,
+    event_timestamp: u64,
+    max_profit: String,
+    max_profit_eth: f64,
+    max_profit_usd: Option<f64>,
+    arb: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ArbTradeRow {
+    arb_id: String,
+    result_index: u16,
+    tx_hash: String,
+    run_id: Option<String>,
+    event_block: u64,
+    event_timestamp: u64,
+    token: String,
+    token_symbol: Option<String>,
+    user_pool: String,
+    user_dex: String,
+    start_pool: String,
+    start_dex: String,
+    end_pool: String,
+    end_dex: String,
+    amount_in: String,
+    profit: String,
+    profit_eth: f64,
+}
+
+/// Rows saved for `arb`: its summary & one per trade.
+fn arb_rows(arb: &SimArbResultBatch) -> Result<(ArbSummaryRow, Vec<ArbTradeRow>)> {
+    let arb_id = new_arb_id();
+    let tx_hash = format!("{:?}", arb.event.hint.hash);
+    let trades = arb
+        .results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let (user_trade, backrun) = (&result.user_trade, &result.backrun_trade);
+            ArbTradeRow {
+                arb_id: arb_id.to_owned(),
+                result_index: i as u16,
+                tx_hash: tx_hash.to_owned(),
+                run_id: arb.run_id.to_owned(),
+                event_block: arb.event.block,
+                event_timestamp: arb.event.timestamp,
+                token: format!("{:?}", user_trade.tokens.token),
+                token_symbol: user_trade
+                    .tokens
+                    .token_metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.symbol.to_owned()),
+                user_pool: format!("{:?}", user_trade.pool),
+                user_dex: user_trade.pool_variant.to_string(),
+                start_pool: format!("{:?}", backrun.start_pool),
+                start_dex: backrun.start_variant.to_string(),
+                end_pool: format!("{:?}", backrun.end_pool),
+                end_dex: backrun.end_variant.to_string(),
+                amount_in: backrun.amount_in.to_string(),
+                profit: backrun.profit.to_string(),
+                profit_eth: eth(backrun.profit),
+            }
+        })
+        .collect();
+    let summary = ArbSummaryRow {
+        arb_id,
+        tx_hash,
+        run_id: arb.run_id.to_owned(),
+        event_block: arb.event.block,
+        event_timestamp: arb.event.timestamp,
+        max_profit: arb.max_profit.to_string(),
+        max_profit_eth: eth(arb.max_profit),
+        max_profit_usd: arb.max_profit_usd,
+        arb: serde_json::to_string(arb)?,
+    };
+    Ok((summary, trades))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CheckpointRow {
+    run_id: String,
+    block_start: u64,
+    block_end: Option<u64>,
+    timestamp_start: u64,
+    timestamp_end: Option<u64>,
+    event_offset: u64,
+    done: bool,
+    updated_at: u64,
+}
+
+impl From<CheckpointRow> for ScanCheckpoint {
+    fn from(row: CheckpointRow) -> Self {
+        ScanCheckpoint {
+            run_id: row.run_id,
+            block_start: row.block_start,
+            block_end: row.block_end,
+            timestamp_start: row.timestamp_start,
+            timestamp_end: row.timestamp_end,
+            offset: row.event_offset,
+            done: row.done,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+impl From<&ScanCheckpoint> for CheckpointRow {
+    fn from(checkpoint: &ScanCheckpoint) -> Self {
+        CheckpointRow {
+            run_id: checkpoint.run_id.to_owned(),
+            block_start: checkpoint.block_start,
+            block_end: checkpoint.block_end,
+            timestamp_start: checkpoint.timestamp_start,
+            timestamp_end: checkpoint.timestamp_end,
+            event_offset: checkpoint.offset,
+            done: checkpoint.done,
+            updated_at: checkpoint.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct FailureRow {
+    run_id: Option<String>,
+    tx_hash: String,
+    event_block: u64,
+    event_timestamp: u64,
+    reason: String,
+    message: String,
+}
+
+impl ClickhouseConnect {
+    /// Connects to the server & creates the DB if needed. A new DB is migrated right away; an
+    /// existing one isn't (see `check_version`), & one w/ a newer schema than this build's is
+    /// opened read-only.
+    pub async fn new(config: ClickhouseConfig) -> Result<Self> {
+        if config.url.is_empty() {
+            return Err(anyhow::format_err!(
+                "CLICKHOUSE_URL must be set to use clickhouse"
+            ));
+        }
+        let database = if cfg!(test) {
+            // separate test db
+            "test_hindsight"
+        } else {
+            DB_NAME
+        };
+        let mut connect = Self {
+            client: reqwest::Client::new(),
+            config,
+            database: database.to_owned(),
+            schema_version: 0,
+        };
+        connect
+            .send(
+                &[],
+                format!("CREATE DATABASE IF NOT EXISTS {}", connect.database),
+            )
+            .await?;
+        let mut version = connect.read_schema_version().await?;
+        if version == 0 {
+            connect.apply_migrations(CLICKHOUSE_MIGRATIONS).await?;
+            version = connect.read_schema_version().await?;
+        }
+        check_version("clickhouse", version, CLICKHOUSE_MIGRATIONS);
+        connect.schema_version = version;
+        Ok(connect)
+    }
+
+    /// Errors if the DB's schema is newer than this build's.
+    fn ensure_writable(&self) -> Result<()> {
+        ensure_writable(self.schema_version, CLICKHOUSE_MIGRATIONS)
+    }
+
+    /// POSTs `body` w/ the URL `params`. Returns the response body.
+    async fn send(&self, params: &[(&str, &str)], body: String) -> Result<String> {
+        let res = self
+            .client
+            .post(&self.config.url)
+            .header("X-ClickHouse-User", &self.config.user)
+            .header("X-ClickHouse-Key", &self.config.password)
+            .query(params)
+            .body(body)
+            .send()
+            .await?;
+        let status = res.status();
+        let text = res.text().await?;
+        if !status.is_success() {
+            return Err(anyhow::format_err!(
+                "clickhouse error ({}): {}",
+                status,
+                text
+            ));
+        }
+        Ok(text)
+    }
+
+    /// Runs a statement that doesn't return rows.
+    async fn execute(&self, statement: &str) -> Result<()> {
+        self.send(&[("database", &self.database)], statement.to_owned())
+            .await?;
+        Ok(())
+    }
+
+    /// Runs `query`, parsing each row it returns as a `T`.
+    async fn select<T: DeserializeOwned>(&self, query: &str) -> Result<Vec<T>> {
+        let res = self
+            .send(
+                &[
+                    ("database", &self.database),
+                    // so 64-bit ints parse as numbers
+                    ("output_format_json_quote_64bit_integers", "0"),
+                ],
+                format!("{} FORMAT JSONEachRow", query),
+            )
+            .await?;
+        res.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Inserts `rows` into `table` in one batch.
+    async fn insert<T: Serialize>(&self, table: &str, rows: &[T]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let body = rows
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n");
+        let query = format!("INSERT INTO {} FORMAT JSONEachRow", table);
+        self.send(
+            &[
+                ("database", &self.database),
+                ("query", &query),
+                // lets the server merge small batches into fewer parts
+                ("async_insert", "1"),
+                ("wait_for_async_insert", "1"),
+            ],
+            body,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Version of the newest migration applied to the DB; 0 if it's never been migrated.
+    async fn read_schema_version(&self) -> Result<i32> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: i32,
+        }
+        let has_migrations = !self
+            .select::<serde_json::Value>(&format!(
+                "SELECT 1 FROM system.tables WHERE database = currentDatabase() AND name = {}",
+                quote(MIGRATIONS_TABLE)
+            ))
+            .await?
+            .is_empty();
+        if !has_migrations {
+            return Ok(0);
+        }
+        Ok(self
+            .select::<Version>(&format!(
+                "SELECT toInt32(max(version)) AS version FROM {}",
+                MIGRATIONS_TABLE
+            ))
+            .await?
+            .first()
+            .map_or(0, |row| row.version))
+    }
+
+    /// Applies each migration in `migrations` that the DB hasn't seen yet. Returns the versions applied.
+    ///
+    /// ClickHouse has no transactions, so a migration that fails partway is retried from the start;
+    /// every statement must be safe to run again.
+    async fn apply_migrations(&self, migrations: &[Migration]) -> Result<Vec<i32>> {
+        ensure_writable(self.read_schema_version().await?, migrations)?;
+        self.execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version Int32,
+                name String,
+                applied_at UInt64
+            ) ENGINE = ReplacingMergeTree ORDER BY version",
+            MIGRATIONS_TABLE
+        ))
+        .await?;
+        let current = self.read_schema_version().await?;
+        let mut applied_versions = vec![];
+        for migration in migrations.iter().filter(|m| m.version > current) {
+            info!(
+                "applying clickhouse migration {} ({})",
+                migration.version, migration.name
+            );
+            for statement in split_statements(migration.up) {
+                self.execute(&statement).await?;
+            }
+            self.insert(
+                MIGRATIONS_TABLE,
+                &[serde_json::json!({
+                    "version": migration.version,
+                    "name": migration.name,
+                    "applied_at": std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)?
+                        .as_secs(),
+                })],
+            )
+            .await?;
+            applied_versions.push(migration.version);
+        }
+        Ok(applied_versions)
+    }
+
+    /// Reads (id, arb) of the arbs selected by `query`.
+    async fn read_arb_rows(&self, query: &str) -> Result<Vec<(String, SimArbResultBatch)>> {
+        self.select::<ArbRow>(query)
+            .await?
+            .into_iter()
+            .map(|row| Ok((row.arb_id, serde_json::from_str(&row.arb)?)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ArbWriter for ClickhouseConnect {
+    /// Saves `arbs` w/ one insert per table, resolving conflicts w/ saved arbs by the configured policy.
+    ///
+    /// Conflicts are resolved before inserting w/o a lock, so concurrent writers
+    /// saving the same event at once may both insert it.
+    async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()> {
+        self.ensure_writable()?;
+        #[derive(Deserialize)]
+        struct SavedProfit {
+            tx_hash: String,
+            run_id: Option<String>,
+            max_profit: String,
+        }
+        let hashes = arbs
+            .iter()
+            .map(|arb| quote(&format!("{:?}", arb.event.hint.hash)))
+            .collect::<Vec<_>>();
+        // saved arbs of the same events have the same timestamps, so lookups & deletes only
+        // need to read the parts of the tables (& the partitions) in the batch's time range
+        let timestamps = arbs.iter().map(|arb| arb.event.timestamp);
+        let time_range = format!(
+            "event_timestamp BETWEEN toDateTime({}) AND toDateTime({})",
+            timestamps.clone().min().unwrap_or_default(),
+            timestamps.max().unwrap_or_default()
+        );
+        // profits of the arbs already saved for each (hash, run id)
+        let mut saved_profits: HashMap<(String, Option<String>), Vec<U256>> = HashMap::new();
+        if !hashes.is_empty() {
+            for saved in self
+                .select::<SavedProfit>(&format!(
+                    "SELECT tx_hash, run_id, toString(max_profit) AS max_profit FROM {} WHERE {} AND tx_hash IN ({})",
+                    ARBS_TABLE,
+                    time_range,
+                    hashes.join(", ")
+                ))
+                .await?
+            {
+                saved_profits
+                    .entry((saved.tx_hash, saved.run_id))
+                    .or_default()
+                    .push(U256::from_dec_str(&saved.max_profit)?);
+            }
+        }
+
+        let mut replaced = vec![];
+        let mut new_arbs: Vec<&SimArbResultBatch> = vec![];
+        for arb in arbs {
+            let key = (format!("{:?}", arb.event.hint.hash), arb.run_id.to_owned());
+            let profits = saved_profits.entry(key.clone()).or_default();
+            match self.config.conflict_policy.resolve(profits, arb.max_profit) {
+                ConflictAction::Insert => profits.push(arb.max_profit),
+                ConflictAction::Replace => {
+                    *profits = vec![arb.max_profit];
+                    // arbs from earlier in this batch are replaced too
+                    new_arbs.retain(|new_arb| {
+                        new_arb.event.hint.hash != arb.event.hint.hash
+                            || new_arb.run_id != arb.run_id
+                    });
+                    let (hash, run_id) = key;
+                    replaced.push(format!(
+                        "(tx_hash = {} AND {})",
+                        quote(&hash),
+                        run_id_filter(&run_id)
+                    ));
+                }
+                ConflictAction::Skip => continue,
+            }
+            new_arbs.push(arb);
+        }
+        if !replaced.is_empty() {
+            let replaced = replaced.join(" OR ");
+            for table in [ARBS_TABLE, ARB_TRADES_TABLE] {
+                self.execute(&format!(
+                    "DELETE FROM {} WHERE {} AND ({})",
+                    table, time_range, replaced
+                ))
+                .await?;
+            }
+        }
+
+        let mut summaries = vec![];
+        let mut trades = vec![];
+        for arb in new_arbs {
+            let (summary, arb_trades) = arb_rows(arb)?;
+            summaries.push(summary);
+            trades.extend(arb_trades);
+        }
+        debug!(
+            "writing {} arbs ({} trades) to clickhouse",
+            summaries.len(),
+            trades.len()
+        );
+        // trades first, so a summary is never visible w/o its trades
+        self.insert(ARB_TRADES_TABLE, &trades).await?;
+        self.insert(ARBS_TABLE, &summaries).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ArbReader for ClickhouseConnect {
+    async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64> {
+        #[derive(Deserialize)]
+        struct Count {
+            count: u64,
+        }
+        Ok(self
+            .select::<Count>(&format!(
+                "SELECT count() AS count FROM {} WHERE {}",
+                ARBS_TABLE,
+                where_filter(filter_params)
+            ))
+            .await?
+            .first()
+            .map_or(0, |row| row.count))
+    }
+
+    async fn read_arbs(
+        &self,
+        filter_params: &ArbFilterParams,
+        offset: Option<u64>,
+        limit: Option<i64>,
+    ) -> Result<Vec<SimArbResultBatch>> {
+        let query = select_arbs_query(&where_filter(filter_params), offset, limit);
+        Ok(self
+            .read_arb_rows(&query)
+            .await?
+            .into_iter()
+            .map(|(_, arb)| arb)
+            .collect())
+    }
+
+    async fn read_arbs_page(
+        &self,
+        filter_params: &ArbFilterParams,
+        after: Option<ArbCursor>,
+        limit: i64,
+    ) -> Result<ArbPage> {
+        let mut filter = where_filter(filter_params);
+        if let Some(after) = after {
+            filter.push_str(&format!(
+                " AND (event_timestamp, tx_hash) > (toDateTime({}), '{:?}')",
+                after.timestamp, after.hash
+            ));
+        }
+        let (ids, mut arbs): (Vec<_>, Vec<_>) = self
+            .read_arb_rows(&select_arbs_query(&filter, None, Some(limit)))
+            .await?
+            .into_iter()
+            .unzip();
+        let next = ArbCursor::next(&arbs, limit);
+        if let (Some(last), Some(last_id)) = (next, ids.last()) {
+            // finish the last event, so the next page can start after it
+            let filter = format!(
+                "{} AND event_timestamp = toDateTime({}) AND tx_hash = '{:?}' AND arb_id > toUUID({})",
+                where_filter(filter_params),
+                last.timestamp,
+                last.hash,
+                quote(last_id)
+            );
+            arbs.extend(
+                self.read_arb_rows(&select_arbs_query(&filter, None, None))
+                    .await?
+                    .into_iter()
+                    .map(|(_, arb)| arb),
+            );
+        }
+        Ok(ArbPage { arbs, next })
+    }
+
+    /// Gets the extrema of the blocks and timestamps of the arbs in the DB.
+    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges> {
+        #[derive(Deserialize)]
+        struct Ranges {
+            earliest_block: Option<u64>,
+            latest_block: Option<u64>,
+            earliest_timestamp: Option<u64>,
+            latest_timestamp: Option<u64>,
+        }
+        let ranges = self
+            .select::<Ranges>(&format!(
+                "SELECT
+                    minOrNull(event_block) AS earliest_block,
+                    maxOrNull(event_block) AS latest_block,
+                    minOrNull(toUInt64(event_timestamp)) AS earliest_timestamp,
+                    maxOrNull(toUInt64(event_timestamp)) AS latest_timestamp
+                FROM {}",
+                ARBS_TABLE
+            ))
+            .await?
+            .pop()
+            .ok_or(anyhow::format_err!("no ranges returned by clickhouse"))?;
+        // same defaults as mongo when there are no arbs yet
+        Ok(StoredArbsRanges {
+            earliest_block: ranges.earliest_block.unwrap_or(1),
+            latest_block: ranges.latest_block.unwrap_or(2),
+            earliest_timestamp: ranges.earliest_timestamp.unwrap_or(1),
+            latest_timestamp: ranges.latest_timestamp.unwrap_or(2),
+        })
+    }
+}
+
+#[async_trait]
+impl TokenDb for ClickhouseConnect {
+    async fn write_tokens(&self, tokens: &[TokenMetadata]) -> Result<()> {
+        self.ensure_writable()?;
+        let rows = tokens
+            .iter()
+            .map(|token| {
+                serde_json::json!({
+                    "address": format!("{:?}", token.address),
+                    "symbol": token.symbol,
+                    "name": token.name,
+                    "decimals": token.decimals,
+                })
+            })
+            .collect::<Vec<_>>();
+        self.insert(TOKENS_TABLE, &rows).await
+    }
+
+    async fn read_tokens(&self) -> Result<Vec<TokenMetadata>> {
+        #[derive(Deserialize)]
+        struct TokenRow {
+            address: String,
+            symbol: Option<String>,
+            name: Option<String>,
+            decimals: Option<u8>,
+        }
+        self.select::<TokenRow>(&format!(
+            "SELECT address, symbol, name, decimals FROM {} FINAL",
+            TOKENS_TABLE
+        ))
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(TokenMetadata {
+                address: Address::from_str(&row.address)?,
+                symbol: row.symbol,
+                name: row.name,
+                decimals: row.decimals,
+            })
+        })
+        .collect()
+    }
+}
+
+#[async_trait]
+impl CheckpointDb for ClickhouseConnect {
+    async fn save_checkpoint(&self, checkpoint: &ScanCheckpoint) -> Result<()> {
+        self.ensure_writable()?;
+        self.insert(CHECKPOINTS_TABLE, &[CheckpointRow::from(checkpoint)])
+            .await
+    }
+
+    async fn get_checkpoint(&self, run_id: &str) -> Result<Option<ScanCheckpoint>> {
+        Ok(self
+            .select::<CheckpointRow>(&format!(
+                "SELECT * FROM {} FINAL WHERE run_id = {}",
+                CHECKPOINTS_TABLE,
+                quote(run_id)
+            ))
+            .await?
+            .pop()
+            .map(ScanCheckpoint::from))
+    }
+
+    async fn get_latest_unfinished_checkpoint(&self) -> Result<Option<ScanCheckpoint>> {
+        Ok(self
+            .select::<CheckpointRow>(&format!(
+                "SELECT * FROM {} FINAL WHERE NOT done ORDER BY updated_at DESC LIMIT 1",
+                CHECKPOINTS_TABLE
+            ))
+            .await?
+            .pop()
+            .map(ScanCheckpoint::from))
+    }
+
+    async fn mark_events_processed(&self, events: &[ProcessedEvent]) -> Result<()> {
+        self.ensure_writable()?;
+        let rows = events
+            .iter()
+            .map(|event| {
+                serde_json::json!({
+                    "run_id": event.run_id,
+                    "tx_hash": format!("{:?}", event.hash),
+                    "event_block": event.block,
+                    "event_timestamp": event.timestamp,
+                })
+            })
+            .collect::<Vec<_>>();
+        self.insert(PROCESSED_EVENTS_TABLE, &rows).await
+    }
+
+    async fn filter_processed(&self, run_id: &str, hashes: &[H256]) -> Result<Vec<H256>> {
+        #[derive(Deserialize)]
+        struct Hash {
+            tx_hash: String,
+        }
+        if hashes.is_empty() {
+            return Ok(vec![]);
+        }
+        let hashes = hashes
+            .iter()
+            .map(|hash| format!("'{:?}'", hash))
+            .collect::<Vec<_>>();
+        self.select::<Hash>(&format!(
+            "SELECT DISTINCT tx_hash FROM {} WHERE run_id = {} AND tx_hash IN ({})",
+            PROCESSED_EVENTS_TABLE,
+            quote(run_id),
+            hashes.join(", ")
+        ))
+        .await?
+        .into_iter()
+        .map(|row| Ok(H256::from_str(&row.tx_hash)?))
+        .collect()
+    }
+}
+
+#[async_trait]
+impl RunDb for ClickhouseConnect {
+    async fn save_run(&self, run: &ScanRun) -> Result<()> {
+        self.ensure_writable()?;
+        self.insert(
+            RUNS_TABLE,
+            &[serde_json::json!({
+                "run_id": run.run_id,
+                "started_at": run.started_at,
+                "run": serde_json::to_string(run)?,
+            })],
+        )
+        .await
+    }
+
+    async fn get_run(&self, run_id: &str) -> Result<Option<ScanRun>> {
+        Ok(self
+            .list_runs_where(&format!("run_id = {}", quote(run_id)))
+            .await?
+            .pop())
+    }
+
+    async fn list_runs(&self) -> Result<Vec<ScanRun>> {
+        self.list_runs_where("1").await
+    }
+}
+
+impl ClickhouseConnect {
+    /// Runs matching `filter`, newest first.
+    async fn list_runs_where(&self, filter: &str) -> Result<Vec<ScanRun>> {
+        #[derive(Deserialize)]
+        struct RunRow {
+            run: String,
+        }
+        self.select::<RunRow>(&format!(
+            "SELECT run FROM {} FINAL WHERE {} ORDER BY started_at DESC",
+            RUNS_TABLE, filter
+        ))
+        .await?
+        .into_iter()
+        .map(|row| Ok(serde_json::from_str(&row.run)?))
+        .collect()
+    }
+}
+
+#[async_trait]
+impl FailureDb for ClickhouseConnect {
+    async fn write_failures(&self, failures: &[SimFailure]) -> Result<()> {
+        self.ensure_writable()?;
+        let rows = failures
+            .iter()
+            .map(|failure| FailureRow {
+                run_id: failure.run_id.to_owned(),
+                tx_hash: format!("{:?}", failure.hash),
+                event_block: failure.block,
+                event_timestamp: failure.timestamp,
+                reason: failure.reason.to_string(),
+                message: failure.message.to_owned(),
+            })
+            .collect::<Vec<_>>();
+        self.insert(FAILURES_TABLE, &rows).await
+    }
+
+    async fn read_failures(&self, filter_params: &ArbFilterParams) -> Result<Vec<SimFailure>> {
+        self.select::<FailureRow>(&format!(
+            "SELECT run_id, tx_hash, event_block, toUInt64(event_timestamp) AS event_timestamp, reason, message
+            FROM {} WHERE {}",
+            FAILURES_TABLE,
+            event_filter(filter_params)
+        ))
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(SimFailure {
+                run_id: row.run_id,
+                hash: H256::from_str(&row.tx_hash)?,
+                block: row.event_block,
+                timestamp: row.event_timestamp,
+                reason: row.reason.parse()?,
+                message: row.message,
+            })
+        })
+        .collect()
+    }
+}
+
+#[async_trait]
+impl SchemaDb for ClickhouseConnect {
+    async fn schema_version(&self) -> Result<i32> {
+        self.read_schema_version().await
+    }
+
+    fn latest_schema_version(&self) -> i32 {
+        latest_version(CLICKHOUSE_MIGRATIONS)
+    }
+
+    async fn migrate(&self) -> Result<Vec<i32>> {
+        self.apply_migrations(CLICKHOUSE_MIGRATIONS).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        data::conformance,
+        interfaces::{BackrunResult, PoolVariant, SimArbResult, TokenPair, UserTradeParams},
+    };
+    use ethers::types::I256;
+
+    async fn connect_with(conflict_policy: ConflictPolicy) -> Result<ClickhouseConnect> {
+        let connect = ClickhouseConnect::new(ClickhouseConfig {
+            conflict_policy,
+            ..ClickhouseConfig::default()
+        })
+        .await?;
+        // connecting doesn't migrate; `hindsight db migrate` does
+        connect.migrate().await?;
+        Ok(connect)
+    }
+
+    #[tokio::test]
+    async fn it_conforms_clickhouse() -> Result<()> {
+        if Config::default().clickhouse_url.is_none() {
+            println!("no clickhouse url, skipping test");
+            return Ok(());
+        }
+        conformance::check_backend(connect_with).await
+    }
+
+    #[tokio::test]
+    async fn it_migrates_clickhouse() -> Result<()> {
+        if Config::default().clickhouse_url.is_none() {
+            println!("no clickhouse url, skipping test");
+            return Ok(());
+        }
+        let connect = connect_with(ConflictPolicy::default()).await?;
+        // `connect_with` already migrated, so there's nothing left to apply
+        assert!(connect.migrate().await?.is_empty());
+        assert_eq!(
+            connect.schema_version().await?,
+            latest_version(CLICKHOUSE_MIGRATIONS)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_aggregates_trades_clickhouse() -> Result<()> {
+        if Config::default().clickhouse_url.is_none() {
+            println!("no clickhouse url, skipping test");
+            return Ok(());
+        }
+        #[derive(Deserialize)]
+        struct TokenProfit {
+            profit: String,
+            trades: String,
+        }
+        let connect = connect_with(ConflictPolicy::default()).await?;
+        let mut arb = SimArbResultBatch::test_example();
+        arb.event.hint.hash = H256::random();
+        arb.run_id = Some(format!("test-{:?}", H256::random()));
+        let result = SimArbResult {
+            user_trade: UserTradeParams {
+                pool_variant: PoolVariant::UniswapV3,
+                token_in: Address::random(),
+                token_out: Address::random(),
+                amount0_sent: I256::one(),
+                amount1_sent: I256::one(),
+                token0_is_weth: true,
+                pool: Address::random(),
+                price: U256::one(),
+                tokens: TokenPair {
+                    weth: Address::random(),
+                    token: Address::random(),
+                    weth_metadata: None,
+                    token_metadata: None,
+                },
+                arb_pools: vec![],
+            },
+            backrun_trade: BackrunResult {
+                amount_in: 0x100.into(),
+                balance_end: 0x110.into(),
+                profit: 0x10.into(),
+                start_pool: Address::random(),
+                end_pool: Address::random(),
+                start_variant: PoolVariant::UniswapV3,
+                end_variant: PoolVariant::UniswapV2,
+            },
+        };
+        arb.results = vec![result.clone(), result];
+        connect.write_arbs(&[arb.clone()]).await?;
+
+        let profits = connect
+            .select::<TokenProfit>(&format!(
+                "SELECT toString(sum(profit)) AS profit, toString(count()) AS trades
+                FROM {} WHERE run_id = {} GROUP BY token, user_dex",
+                ARB_TRADES_TABLE,
+                quote(arb.run_id.as_ref().unwrap())
+            ))
+            .await?;
+        assert_eq!(profits.len(), 1);
+        assert_eq!(profits[0].profit, "32");
+        assert_eq!(profits[0].trades, "2");
+        Ok(())
+    }
+
+    #[test]
+    fn it_splits_migrations_into_statements() {
+        assert_eq!(
+            split_statements(
+                "-- a; b\nCREATE TABLE a (x UInt8); -- c\n\n-- d;\nCREATE TABLE b (y UInt8);"
+            ),
+            vec!["CREATE TABLE a (x UInt8)", "CREATE TABLE b (y UInt8)"]
+        );
+        for migration in CLICKHOUSE_MIGRATIONS {
+            let statements = split_statements(migration.up);
+            assert!(!statements.is_empty(), "{}", migration.name);
+            for statement in statements {
+                assert!(
+                    statement.starts_with("CREATE ") || statement.starts_with("ALTER "),
+                    "{}",
+                    statement
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_filters_arbs_by_event_columns() {
+        assert_eq!(where_filter(&ArbFilterParams::default()), "1");
+        let filter = where_filter(&ArbFilterParams {
+            block_start: Some(1),
+            timestamp_end: Some(2),
+            min_profit: Some(3.into()),
+            run_id: Some("it's".to_owned()),
+            ..ArbFilterParams::default()
+        });
+        assert_eq!(
+            filter,
+            "1 AND event_block >= 1 AND event_timestamp <= toDateTime(2) AND run_id = 'it\\'s' AND max_profit >= toUInt256('3')"
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! Checks that every DB backend behaves the same. Each backend's tests run these against it.
+//!
+//! Backends under test may share a DB w/ other tests (& earlier runs), so every check
+//! writes under a new run id and only reads back arbs from that run.
+use super::arbs::{ArbFilterParams, ArbReader, ArbWriter, ConflictPolicy};
+use super::checkpoints::CheckpointDb;
+use super::failures::FailureDb;
+use super::runs::RunDb;
+use super::tokens::TokenDb;
+use crate::{
+    error::FailureReason,
+    interfaces::{ProcessedEvent, ScanRun, SimArbResultBatch, SimFailure, TokenMetadata},
+    Result,
+};
+use ethers::{
+    types::{Address, H256},
+    utils::parse_ether,
+};
+use std::future::Future;
+
+/// A backend that can be checked for conformance.
+pub trait ConformingDb: ArbReader + ArbWriter + CheckpointDb + FailureDb + TokenDb + RunDb {}
+
+impl<T: ArbReader + ArbWriter + CheckpointDb + FailureDb + TokenDb + RunDb> ConformingDb for T {}
+
+/// Runs every conformance check against the backend opened by `connect`.
+pub async fn check_backend<F, Fut, D>(connect: F) -> Result<()>
+where
+    F: Fn(ConflictPolicy) -> Fut,
+    Fut: Future<Output = Result<D>>,
+    D: ConformingDb,
+{
+    let db = connect(ConflictPolicy::default()).await?;
+    it_round_trips_arbs(&db).await?;
+    it_filters_arbs(&db).await?;
+    it_reports_saved_ranges(&db).await?;
+    it_filters_failures(&db).await?;
+    it_filters_processed_events(&db).await?;
+    it_upserts_tokens(&db).await?;
+    it_saves_runs(&db).await?;
+    it_pages_tied_arbs(&db).await?;
+    it_pages_arbs(&connect(ConflictPolicy::KeepBoth).await?).await?;
+    for policy in [
+        ConflictPolicy::Replace,
+        ConflictPolicy::KeepBestProfit,
+        ConflictPolicy::KeepBoth,
+    ] {
+        it_resolves_conflicts(&connect(policy).await?, policy).await?;
+    }
+    Ok(())
+}
+
+fn new_run_id() -> String {
+    format!("test-{:?}", H256::random())
+}
+
+/// Arb for a new event at `block`, w/ `block` as its timestamp too so they sort the same way.
+fn test_arb(run_id: &str, block: u64, profit_eth: u64) -> Result<SimArbResultBatch> {
+    let mut arb = SimArbResultBatch::test_example();
+    arb.event.hint.hash = H256::random();
+    arb.event.block = block;
+    arb.event.timestamp = block;
+    arb.max_profit = parse_ether(profit_eth)?;
+    arb.run_id = Some(run_id.to_owned());
+    Ok(arb)
+}
+
+fn run_filter(run_id: &str) -> ArbFilterParams {
+    ArbFilterParams {
+        run_id: Some(run_id.to_owned()),
+        ..ArbFilterParams::default()
+    }
+}
+
+async fn it_round_trips_arbs(db: &dyn ConformingDb) -> Result<()> {
+    let run_id = new_run_id();
+    let arb = test_arb(&run_id, 10_000, 1)?;
+    db.write_arbs(&[arb.clone()]).await?;
+    let saved = db.read_arbs(&run_filter(&run_id), None, None).await?;
+    assert_eq!(saved.len(), 1);
+    assert_eq!(serde_json::to_value(&saved[0])?, serde_json::to_value(arb)?);
+    Ok(())
+}
+
+async fn it_filters_arbs(db: &dyn ConformingDb) -> Result<()> {
+    let run_id = new_run_id();
+    // written out of order to make sure reads are sorted
+    let arbs = vec![
+        test_arb(&run_id, 10_003, 3)?,
+        test_arb(&run_id, 10_001, 1)?,
+        test_arb(&run_id, 10_002, 2)?,
+    ];
+    db.write_arbs(&arbs).await?;
+
+    let blocks = |arbs: Vec<SimArbResultBatch>| {
+        arbs.into_iter()
+            .map(|arb| arb.event.block)
+            .collect::<Vec<_>>()
+    };
+    let all = db.read_arbs(&run_filter(&run_id), None, None).await?;
+    assert_eq!(blocks(all), vec![10_001, 10_002, 10_003]);
+    let offset = db.read_arbs(&run_filter(&run_id), Some(1), Some(1)).await?;
+    assert_eq!(blocks(offset), vec![10_002]);
+
+    for (filter, expected) in [
+        (
+            ArbFilterParams {
+                block_start: Some(10_002),
+                ..run_filter(&run_id)
+            },
+            vec![10_002, 10_003],
+        ),
+        (
+            ArbFilterParams {
+                block_end: Some(10_002),
+                ..run_filter(&run_id)
+            },
+            vec![10_001, 10_002],
+        ),
+        (
+            ArbFilterParams {
+                timestamp_start: Some(10_002),
+                timestamp_end: Some(10_002),
+                ..run_filter(&run_id)
+            },
+            vec![10_002],
+        ),
+        (
+            ArbFilterParams {
+                min_profit: Some(parse_ether(2)?),
+                ..run_filter(&run_id)
+            },
+            vec![10_002, 10_003],
+        ),
+        (
+            // run ids must match exactly
+            ArbFilterParams {
+                run_id: Some(format!("{}-other", run_id)),
+                ..ArbFilterParams::default()
+            },
+            vec![],
+        ),
+    ] {
+        assert_eq!(
+            db.get_num_arbs(&filter).await?,
+            expected.len() as u64,
+            "{:?}",
+            filter
+        );
+        let read = db.read_arbs(&filter, None, None).await?;
+        assert_eq!(blocks(read), expected, "{:?}", filter);
+    }
+    Ok(())
+}
+
+async fn it_reports_saved_ranges(db: &dyn ConformingDb) -> Result<()> {
+    let run_id = new_run_id();
+    db.write_arbs(&[test_arb(&run_id, 10_001, 1)?, test_arb(&run_id, 10_005, 1)?])
+        .await?;
+    let ranges = db.get_previously_saved_ranges().await?;
+    // other arbs may be saved too, so only check that ours are covered
+    assert!(ranges.earliest_block <= 10_001, "{:?}", ranges);
+    assert!(ranges.latest_block >= 10_005, "{:?}", ranges);
+    assert!(ranges.earliest_timestamp <= 10_001, "{:?}", ranges);
+    assert!(ranges.latest_timestamp >= 10_005, "{:?}", ranges);
+    Ok(())
+}
+
+/// Expects `db` to keep both arbs when an event's arb is saved twice.
+async fn it_pages_arbs(db: &dyn ConformingDb) -> Result<()> {
+    let run_id = new_run_id();
+    let tied = test_arb(&run_id, 10_001, 1)?;
+    let arbs = vec![
+        tied.clone(),
+        tied.clone(),
+        test_arb(&run_id, 10_002, 1)?,
+        test_arb(&run_id, 10_003, 1)?,
+        test_arb(&run_id, 10_004, 1)?,
+    ];
+    db.write_arbs(&arbs).await?;
+
+    let filter = run_filter(&run_id);
+    let mut pages = vec![];
+    let mut after = None;
+    loop {
+        let page = db.read_arbs_page(&filter, after, 1).await?;
+        pages.push(
+            page.arbs
+                .iter()
+                .map(|arb| arb.event.block)
+                .collect::<Vec<_>>(),
+        );
+        after = page.next;
+        if after.is_none() {
+            break;
+        }
+    }
+    // the first page is finished w/ the other arb of its event; the last one comes up empty
+    assert_eq!(
+        pages,
+        vec![
+            vec![10_001, 10_001],
+            vec![10_002],
+            vec![10_003],
+            vec![10_004],
+            vec![]
+        ]
+    );
+    Ok(())
+}
+
+/// Arbs of different events at the same time are paged in order of their tx hashes.
+async fn it_pages_tied_arbs(db: &dyn ConformingDb) -> Result<()> {
+    let run_id = new_run_id();
+    let mut arbs = (0..5)
+        .map(|_| test_arb(&run_id, 10_001, 1))
+        .collect::<Result<Vec<_>>>()?;
+    db.write_arbs(&arbs).await?;
+    arbs.sort_by_key(|arb| arb.event.hint.hash);
+
+    let filter = run_filter(&run_id);
+    let mut hashes = vec![];
+    let mut after = None;
+    loop {
+        let page = db.read_arbs_page(&filter, after, 2).await?;
+        hashes.extend(page.arbs.iter().map(|arb| arb.event.hint.hash));
+        after = page.next;
+        if after.is_none() {
+            break;
+        }
+    }
+    assert_eq!(
+        hashes,
+        arbs.iter()
+            .map(|arb| arb.event.hint.hash)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+/// Saves an arb, then another for the same event w/ less profit.
+async fn it_resolves_conflicts(db: &dyn ConformingDb, policy: ConflictPolicy) -> Result<()> {
+    let run_id = new_run_id();
+    let mut arb = test_arb(&run_id, 10_001, 2)?;
+    db.write_arbs(&[arb.clone()]).await?;
+    arb.max_profit = parse_ether(1)?;
+    db.write_arbs(&[arb.clone()]).await?;
+
+    let mut profits = db
+        .read_arbs(&run_filter(&run_id), None, None)
+        .await?
+        .into_iter()
+        .map(|arb| arb.max_profit)
+        .collect::<Vec<_>>();
+    profits.sort();
+    let expected_profits = match policy {
+        ConflictPolicy::Replace => vec![parse_ether(1)?],
+        ConflictPolicy::KeepBestProfit => vec![parse_ether(2)?],
+        ConflictPolicy::KeepBoth => vec![parse_ether(1)?, parse_ether(2)?],
+    };
+    assert_eq!(profits, expected_profits, "{}", policy);
+    Ok(())
+}
+
+async fn it_filters_failures(db: &dyn ConformingDb) -> Result<()> {
+    let run_id = new_run_id();
+    let failure = |block: u64| SimFailure {
+        run_id: Some(run_id.to_owned()),
+        hash: H256::random(),
+        block,
+        timestamp: block,
+        reason: FailureReason::PoolNotFound,
+        message: "pool not found".to_owned(),
+    };
+    let failures = vec![failure(10_001), failure(10_002)];
+    db.write_failures(&failures).await?;
+
+    let saved = db.read_failures(&run_filter(&run_id)).await?;
+    assert_eq!(saved.len(), 2);
+    let saved = db
+        .read_failures(&ArbFilterParams {
+            block_start: Some(10_002),
+            ..run_filter(&run_id)
+        })
+        .await?;
+    assert_eq!(saved, vec![failures[1].clone()]);
+    Ok(())
+}
+
+async fn it_filters_processed_events(db: &dyn ConformingDb) -> Result<()> {
+    let run_id = new_run_id();
+    let (processed, unprocessed) = (H256::random(), H256::random());
+    let event = ProcessedEvent {
+        run_id: run_id.to_owned(),
+        hash: processed,
+        block: 10_001,
+        timestamp: 10_001,
+    };
+    // marking an event twice is fine
+    db.mark_events_processed(&[event.clone()]).await?;
+    db.mark_events_processed(&[event]).await?;
+    assert_eq!(
+        db.filter_processed(&run_id, &[processed, unprocessed])
+            .await?,
+        vec![processed]
+    );
+    assert!(db
+        .filter_processed(&new_run_id(), &[processed])
+        .await?
+        .is_empty());
+    Ok(())
+}
+
+async fn it_upserts_tokens(db: &dyn ConformingDb) -> Result<()> {
+    let mut token = TokenMetadata {
+        address: Address::random(),
+        symbol: Some("TEST".to_owned()),
+        name: None,
+        decimals: Some(18),
+    };
+    db.write_tokens(&[token.clone()]).await?;
+    token.name = Some("Test Token".to_owned());
+    db.write_tokens(&[token.clone()]).await?;
+    let saved = db
+        .read_tokens()
+        .await?
+        .into_iter()
+        .filter(|saved| saved.address == token.address)
+        .collect::<Vec<_>>();
+    assert_eq!(saved, vec![token]);
+    Ok(())
+}
+
+async fn it_saves_runs(db: &dyn ConformingDb) -> Result<()> {
+    let mut run = ScanRun {
+        run_id: new_run_id(),
+        started_at: 1,
+        git_version: "test".to_owned(),
+        hindsight_version: "0.1.0".to_owned(),
+        cli_args: vec!["hindsight".to_owned(), "scan".to_owned()],
+        chain_id: 1,
+        rpc_host: "localhost:8545".to_owned(),
+        eth_price_source: "chainlink".to_owned(),
+        sim_backend: Some("revm".to_owned()),
+        max_depth: 7,
+        step_intervals: 15,
+        pool_registry: crate::util::pool_registry(),
+    };
+    db.save_run(&run).await?;
+    // saving a run again updates it
+    run.max_depth = 8;
+    db.save_run(&run).await?;
+    assert_eq!(db.get_run(&run.run_id).await?, Some(run.clone()));
+    assert!(db.get_run(&new_run_id()).await?.is_none());
+    let saved = db
+        .list_runs()
+        .await?
+        .into_iter()
+        .filter(|saved| saved.run_id == run.run_id)
+        .collect::<Vec<_>>();
+    assert_eq!(saved, vec![run]);
+    Ok(())
+}
@@ -1,8 +1,17 @@
+#[cfg(feature = "clickhouse")]
+use crate::data::clickhouse::{ClickhouseConfig, ClickhouseConnect};
 use crate::{
     data::{
-        arbs::ArbDatabase,
+        arbs::{ArbDatabase, ConflictPolicy},
+        checkpoints::CheckpointDatabase,
+        failures::FailureDatabase,
+        memory::{MemoryConfig, MemoryConnect},
+        migrations::SchemaDatabase,
         mongo::{MongoConfig, MongoConnect},
         postgres::{PostgresConfig, PostgresConnect},
+        runs::RunDatabase,
+        sqlite::{SqliteConfig, SqliteConnect},
+        tokens::TokenDatabase,
     },
     Result,
 };
@@ -11,12 +20,23 @@ use strum::{EnumIter, IntoEnumIterator};
 
 pub struct Db {
     pub connect: ArbDatabase,
+    pub tokens: TokenDatabase,
+    pub checkpoints: CheckpointDatabase,
+    pub failures: FailureDatabase,
+    pub runs: RunDatabase,
+    pub schema: SchemaDatabase,
 }
 
 #[derive(Clone, Debug, EnumIter)]
 pub enum DbEngine {
     Mongo(MongoConfig),
     Postgres(PostgresConfig),
+    Sqlite(SqliteConfig),
+    /// Nothing is persisted; each `Db` gets its own empty store.
+    Memory(MemoryConfig),
+    /// Only available when built w/ `--features clickhouse`.
+    #[cfg(feature = "clickhouse")]
+    Clickhouse(ClickhouseConfig),
 }
 
 impl DbEngine {
@@ -25,7 +45,32 @@ impl DbEngine {
             .map(|engine| engine.to_string())
             .reduce(|a, b| format!("{} | {}", a, b))
             .expect("failed to reduce db engines to string")
-            
+    }
+}
+
+impl DbEngine {
+    /// Sets how arbs that are already saved (by event & run id) are handled on write.
+    pub fn with_conflict_policy(self, conflict_policy: ConflictPolicy) -> Self {
+        match self {
+            DbEngine::Mongo(config) => DbEngine::Mongo(MongoConfig {
+                conflict_policy,
+                ..config
+            }),
+            DbEngine::Postgres(config) => DbEngine::Postgres(PostgresConfig {
+                conflict_policy,
+                ..config
+            }),
+            DbEngine::Sqlite(config) => DbEngine::Sqlite(SqliteConfig {
+                conflict_policy,
+                ..config
+            }),
+            DbEngine::Memory(_) => DbEngine::Memory(MemoryConfig { conflict_policy }),
+            #[cfg(feature = "clickhouse")]
+            DbEngine::Clickhouse(config) => DbEngine::Clickhouse(ClickhouseConfig {
+                conflict_policy,
+                ..config
+            }),
+        }
     }
 }
 
@@ -40,6 +85,10 @@ impl std::fmt::Display for DbEngine {
         match self {
             DbEngine::Mongo(_) => write!(f, "mongo"),
             DbEngine::Postgres(_) => write!(f, "postgres"),
+            DbEngine::Sqlite(_) => write!(f, "sqlite"),
+            DbEngine::Memory(_) => write!(f, "memory"),
+            #[cfg(feature = "clickhouse")]
+            DbEngine::Clickhouse(_) => write!(f, "clickhouse"),
         }
     }
 }
@@ -51,6 +100,15 @@ impl std::str::FromStr for DbEngine {
         match s {
             "mongo" => Ok(DbEngine::Mongo(MongoConfig::default())),
             "postgres" => Ok(DbEngine::Postgres(PostgresConfig::default())),
+            "sqlite" => Ok(DbEngine::Sqlite(SqliteConfig::default())),
+            "memory" => Ok(DbEngine::Memory(MemoryConfig::default())),
+            #[cfg(feature = "clickhouse")]
+            "clickhouse" => Ok(DbEngine::Clickhouse(ClickhouseConfig::default())),
+            #[cfg(not(feature = "clickhouse"))]
+            "clickhouse" => Err(
+                "clickhouse support is not built in; rebuild with `--features clickhouse`"
+                    .to_owned(),
+            ),
             _ => Err(format!("invalid db engine: {}", s)),
         }
     }
@@ -60,21 +118,80 @@ impl Db {
     pub async fn new(engine: DbEngine) -> Self {
         match engine {
             DbEngine::Mongo(config) => {
+                let connect = Arc::new(MongoConnect::new(config.to_owned()).await.unwrap_or_else(
+                    |err| panic!("failed to connect to mongo db at {}: {}", config.url, err),
+                ));
                 Db {
-                    connect: Arc::new(MongoConnect::new(config.to_owned()).await.unwrap_or_else(
-                        |_| panic!("failed to connect to mongo db at {}", config.url),
-                    )),
+                    connect: connect.clone(),
+                    tokens: connect.clone(),
+                    checkpoints: connect.clone(),
+                    failures: connect.clone(),
+                    runs: connect.clone(),
+                    schema: connect,
                 }
             }
-            DbEngine::Postgres(config) => Db {
-                connect: Arc::new(
+            DbEngine::Postgres(config) => {
+                let connect = Arc::new(
                     PostgresConnect::new(config.to_owned())
                         .await
-                        .unwrap_or_else(|_| {
-                            panic!("failed to connect to postgres db at {:?}", config.url)
+                        .unwrap_or_else(|err| {
+                            panic!(
+                                "failed to connect to postgres db at {:?}: {}",
+                                config.url, err
+                            )
+                        }),
+                );
+                Db {
+                    connect: connect.clone(),
+                    tokens: connect.clone(),
+                    checkpoints: connect.clone(),
+                    failures: connect.clone(),
+                    runs: connect.clone(),
+                    schema: connect,
+                }
+            }
+            DbEngine::Sqlite(config) => {
+                let connect = Arc::new(SqliteConnect::new(config.to_owned()).await.unwrap_or_else(
+                    |err| panic!("failed to open sqlite db at {:?}: {}", config.path, err),
+                ));
+                Db {
+                    connect: connect.clone(),
+                    tokens: connect.clone(),
+                    checkpoints: connect.clone(),
+                    failures: connect.clone(),
+                    runs: connect.clone(),
+                    schema: connect,
+                }
+            }
+            DbEngine::Memory(config) => {
+                let connect = Arc::new(MemoryConnect::new(config));
+                Db {
+                    connect: connect.clone(),
+                    tokens: connect.clone(),
+                    checkpoints: connect.clone(),
+                    failures: connect.clone(),
+                    runs: connect.clone(),
+                    schema: connect,
+                }
+            }
+            #[cfg(feature = "clickhouse")]
+            DbEngine::Clickhouse(config) => {
+                let connect = Arc::new(
+                    ClickhouseConnect::new(config.to_owned())
+                        .await
+                        .unwrap_or_else(|err| {
+                            panic!("failed to connect to clickhouse at {}: {}", config.url, err)
                         }),
-                ),
-            },
+                );
+                Db {
+                    connect: connect.clone(),
+                    tokens: connect.clone(),
+                    checkpoints: connect.clone(),
+                    failures: connect.clone(),
+                    runs: connect.clone(),
+                    schema: connect,
+                }
+            }
         }
     }
 }
@@ -0,0 +1,13 @@
+use crate::{data::arbs::ArbFilterParams, interfaces::SimFailure, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Stores events that failed to simulate, so that failures can be told apart from a lack of MEV.
+#[async_trait]
+pub trait FailureDb: Sync + Send {
+    async fn write_failures(&self, failures: &[SimFailure]) -> Result<()>;
+    /// Load failures matching the block, timestamp & run id of `filter_params` (`min_profit` is ignored).
+    async fn read_failures(&self, filter_params: &ArbFilterParams) -> Result<Vec<SimFailure>>;
+}
+
+pub type FailureDatabase = Arc<dyn FailureDb>;
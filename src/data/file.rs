@@ -1,9 +1,4 @@
-use crate::{
-    data::arbs::{ArbDb, ArbFilterParams, WriteEngine},
-    info,
-    interfaces::{SimArbResultBatch, StoredArbsRanges},
-    Result,
-};
+use crate::{data::arbs::ArbWriter, info, interfaces::SimArbResultBatch, Result};
 use async_trait::async_trait;
 use std::{
     fs::File,
@@ -56,34 +51,9 @@ impl FileWriter {
 }
 
 #[async_trait]
-impl ArbDb for FileWriter {
+impl ArbWriter for FileWriter {
     /// Write arbs to a file.
     async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()> {
         self.save_arbs_to_file(arbs).await
     }
-
-    /* The following aren't really needed, but the trait requires them. Maybe I should break up the trait a bit.
-    (TODO: try breaking ArbDb trait into ArbReader and ArbWriter)
-    */
-    async fn read_arbs(
-        &self,
-        _filter_params: &ArbFilterParams,
-        _offset: Option<u64>,
-        _limit: Option<i64>,
-    ) -> Result<Vec<SimArbResultBatch>> {
-        unimplemented!()
-    }
-    async fn get_num_arbs(&self, _filter_params: &ArbFilterParams) -> Result<u64> {
-        unimplemented!()
-    }
-    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges> {
-        unimplemented!()
-    }
-    async fn export_arbs(
-        &self,
-        _write_dest: WriteEngine,
-        _filter_params: &ArbFilterParams,
-    ) -> Result<()> {
-        unimplemented!()
-    }
 }
@@ -0,0 +1,334 @@
+use super::arbs::{
+    ArbCursor, ArbFilterParams, ArbPage, ArbReader, ArbWriter, ConflictAction, ConflictPolicy,
+};
+use super::checkpoints::CheckpointDb;
+use super::failures::FailureDb;
+use super::migrations::SchemaDb;
+use super::runs::RunDb;
+use super::tokens::TokenDb;
+use crate::{
+    interfaces::{
+        ProcessedEvent, ScanCheckpoint, ScanRun, SimArbResultBatch, SimFailure, StoredArbsRanges,
+        TokenMetadata,
+    },
+    Result,
+};
+use async_trait::async_trait;
+use ethers::types::{Address, H256};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Keeps everything in memory, for tests & embedding hindsight as a library.
+/// Data is lost when the last clone is dropped.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryConnect {
+    state: Arc<Mutex<MemoryState>>,
+    conflict_policy: ConflictPolicy,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryConfig {
+    pub conflict_policy: ConflictPolicy,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    /// In the order they were saved, which breaks ties between arbs of the same event.
+    arbs: Vec<SimArbResultBatch>,
+    tokens: HashMap<Address, TokenMetadata>,
+    checkpoints: HashMap<String, ScanCheckpoint>,
+    /// (run id, event hash)
+    processed_events: HashSet<(String, H256)>,
+    runs: HashMap<String, ScanRun>,
+    failures: Vec<SimFailure>,
+}
+
+/// Whether `filter` selects an event at `block` & `timestamp` from `run_id`.
+fn matches_event(
+    filter: &ArbFilterParams,
+    block: u64,
+    timestamp: u64,
+    run_id: &Option<String>,
+) -> bool {
+    (filter.block_start.unwrap_or(0) as u64..=filter.block_end.unwrap_or(u32::MAX) as u64)
+        .contains(&block)
+        && (filter.timestamp_start.unwrap_or(0) as u64
+            ..=filter.timestamp_end.unwrap_or(u32::MAX) as u64)
+            .contains(&timestamp)
+        && (filter.run_id.is_none() || &filter.run_id == run_id)
+}
+
+fn matches_arb(filter: &ArbFilterParams, arb: &SimArbResultBatch) -> bool {
+    matches_event(filter, arb.event.block, arb.event.timestamp, &arb.run_id)
+        && arb.max_profit >= filter.min_profit.unwrap_or_default()
+}
+
+impl MemoryConnect {
+    pub fn new(config: MemoryConfig) -> Self {
+        Self {
+            state: Arc::default(),
+            conflict_policy: config.conflict_policy,
+        }
+    }
+
+    fn state(&self) -> Result<MutexGuard<'_, MemoryState>> {
+        self.state
+            .lock()
+            .map_err(|_| anyhow::format_err!("memory db poisoned"))
+    }
+
+    /// Arbs matching `filter`, in (timestamp, hash) order.
+    fn sorted_arbs(&self, filter: &ArbFilterParams) -> Result<Vec<SimArbResultBatch>> {
+        let mut arbs = self
+            .state()?
+            .arbs
+            .iter()
+            .filter(|arb| matches_arb(filter, arb))
+            .cloned()
+            .collect::<Vec<_>>();
+        // stable, so arbs of the same event stay in the order they were saved
+        arbs.sort_by_key(ArbCursor::from);
+        Ok(arbs)
+    }
+}
+
+#[async_trait]
+impl ArbWriter for MemoryConnect {
+    async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()> {
+        let mut state = self.state()?;
+        for arb in arbs {
+            let is_saved = |saved: &SimArbResultBatch| {
+                saved.event.hint.hash == arb.event.hint.hash && saved.run_id == arb.run_id
+            };
+            let saved_profits = state
+                .arbs
+                .iter()
+                .filter(|saved| is_saved(saved))
+                .map(|saved| saved.max_profit)
+                .collect::<Vec<_>>();
+            match self.conflict_policy.resolve(&saved_profits, arb.max_profit) {
+                ConflictAction::Insert => {}
+                ConflictAction::Replace => state.arbs.retain(|saved| !is_saved(saved)),
+                ConflictAction::Skip => continue,
+            }
+            state.arbs.push(arb.to_owned());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ArbReader for MemoryConnect {
+    async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64> {
+        Ok(self
+            .state()?
+            .arbs
+            .iter()
+            .filter(|arb| matches_arb(filter_params, arb))
+            .count() as u64)
+    }
+
+    async fn read_arbs(
+        &self,
+        filter_params: &ArbFilterParams,
+        offset: Option<u64>,
+        limit: Option<i64>,
+    ) -> Result<Vec<SimArbResultBatch>> {
+        Ok(self
+            .sorted_arbs(filter_params)?
+            .into_iter()
+            .skip(offset.unwrap_or(0) as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+            .collect())
+    }
+
+    async fn read_arbs_page(
+        &self,
+        filter_params: &ArbFilterParams,
+        after: Option<ArbCursor>,
+        limit: i64,
+    ) -> Result<ArbPage> {
+        let mut arbs = self
+            .sorted_arbs(filter_params)?
+            .into_iter()
+            // `None` sorts before every cursor
+            .filter(|arb| Some(ArbCursor::from(arb)) > after)
+            .peekable();
+        let mut page = arbs
+            .by_ref()
+            .take(limit.max(0) as usize)
+            .collect::<Vec<_>>();
+        let next = ArbCursor::next(&page, limit);
+        if let Some(last) = next {
+            // finish the last event, so the next page can start after it
+            while let Some(arb) = arbs.next_if(|arb| ArbCursor::from(arb) == last) {
+                page.push(arb);
+            }
+        }
+        Ok(ArbPage { arbs: page, next })
+    }
+
+    /// Gets the extrema of the blocks and timestamps of the arbs in the DB.
+    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges> {
+        let state = self.state()?;
+        let blocks = state.arbs.iter().map(|arb| arb.event.block);
+        let timestamps = state.arbs.iter().map(|arb| arb.event.timestamp);
+        // same defaults as mongo when there are no arbs yet
+        Ok(StoredArbsRanges {
+            earliest_block: blocks.clone().min().unwrap_or(1),
+            latest_block: blocks.max().unwrap_or(2),
+            earliest_timestamp: timestamps.clone().min().unwrap_or(1),
+            latest_timestamp: timestamps.max().unwrap_or(2),
+        })
+    }
+}
+
+#[async_trait]
+impl TokenDb for MemoryConnect {
+    async fn write_tokens(&self, tokens: &[TokenMetadata]) -> Result<()> {
+        let mut state = self.state()?;
+        for token in tokens {
+            state.tokens.insert(token.address, token.to_owned());
+        }
+        Ok(())
+    }
+
+    async fn read_tokens(&self) -> Result<Vec<TokenMetadata>> {
+        Ok(self.state()?.tokens.values().cloned().collect())
+    }
+}
+
+#[async_trait]
+impl CheckpointDb for MemoryConnect {
+    async fn save_checkpoint(&self, checkpoint: &ScanCheckpoint) -> Result<()> {
+        self.state()?
+            .checkpoints
+            .insert(checkpoint.run_id.to_owned(), checkpoint.to_owned());
+        Ok(())
+    }
+
+    async fn get_checkpoint(&self, run_id: &str) -> Result<Option<ScanCheckpoint>> {
+        Ok(self.state()?.checkpoints.get(run_id).cloned())
+    }
+
+    async fn get_latest_unfinished_checkpoint(&self) -> Result<Option<ScanCheckpoint>> {
+        Ok(self
+            .state()?
+            .checkpoints
+            .values()
+            .filter(|checkpoint| !checkpoint.done)
+            .max_by_key(|checkpoint| checkpoint.updated_at)
+            .cloned())
+    }
+
+    async fn mark_events_processed(&self, events: &[ProcessedEvent]) -> Result<()> {
+        let mut state = self.state()?;
+        for event in events {
+            state
+                .processed_events
+                .insert((event.run_id.to_owned(), event.hash));
+        }
+        Ok(())
+    }
+
+    async fn filter_processed(&self, run_id: &str, hashes: &[H256]) -> Result<Vec<H256>> {
+        let state = self.state()?;
+        Ok(hashes
+            .iter()
+            .filter(|hash| {
+                state
+                    .processed_events
+                    .contains(&(run_id.to_owned(), **hash))
+            })
+            .copied()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl RunDb for MemoryConnect {
+    async fn save_run(&self, run: &ScanRun) -> Result<()> {
+        self.state()?
+            .runs
+            .insert(run.run_id.to_owned(), run.to_owned());
+        Ok(())
+    }
+
+    async fn get_run(&self, run_id: &str) -> Result<Option<ScanRun>> {
+        Ok(self.state()?.runs.get(run_id).cloned())
+    }
+
+    async fn list_runs(&self) -> Result<Vec<ScanRun>> {
+        let mut runs = self.state()?.runs.values().cloned().collect::<Vec<_>>();
+        runs.sort_by_key(|run| std::cmp::Reverse(run.started_at));
+        Ok(runs)
+    }
+}
+
+#[async_trait]
+impl FailureDb for MemoryConnect {
+    async fn write_failures(&self, failures: &[SimFailure]) -> Result<()> {
+        self.state()?.failures.extend_from_slice(failures);
+        Ok(())
+    }
+
+    async fn read_failures(&self, filter_params: &ArbFilterParams) -> Result<Vec<SimFailure>> {
+        Ok(self
+            .state()?
+            .failures
+            .iter()
+            .filter(|failure| {
+                matches_event(
+                    filter_params,
+                    failure.block,
+                    failure.timestamp,
+                    &failure.run_id,
+                )
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+/// Nothing is persisted, so there's never anything to migrate.
+#[async_trait]
+impl SchemaDb for MemoryConnect {
+    async fn schema_version(&self) -> Result<i32> {
+        Ok(0)
+    }
+
+    fn latest_schema_version(&self) -> i32 {
+        0
+    }
+
+    async fn migrate(&self) -> Result<Vec<i32>> {
+        Ok(vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::conformance;
+
+    #[tokio::test]
+    async fn it_conforms_memory() -> Result<()> {
+        conformance::check_backend(|conflict_policy| async move {
+            Ok(MemoryConnect::new(MemoryConfig { conflict_policy }))
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_shares_state_between_clones() -> Result<()> {
+        let connect = MemoryConnect::default();
+        connect
+            .write_arbs(&[SimArbResultBatch::test_example()])
+            .await?;
+        let clone = connect.clone();
+        assert_eq!(clone.get_num_arbs(&ArbFilterParams::default()).await?, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{warn, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// A versioned schema change. Migrations are applied in order of `version`, each at most once.
+#[derive(Clone, Copy, Debug)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    /// Postgres & SQLite: a SQL script. ClickHouse: SQL statements, each ending w/ `;` at the end of a line. Mongo: a JSON array of database commands.
+    pub up: &'static str,
+}
+
+/// Postgres schema history. Never edit a migration that's been released; add a new one.
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        up: include_str!("migrations/postgres/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "normalized_arbs",
+        up: include_str!("migrations/postgres/0002_normalized_arbs.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "arb_keyset_index",
+        up: include_str!("migrations/postgres/0003_arb_keyset_index.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "arb_ids",
+        up: include_str!("migrations/postgres/0004_arb_ids.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "unique_sim_failures",
+        up: include_str!("migrations/postgres/0005_unique_sim_failures.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "exact_arbs",
+        up: include_str!("migrations/postgres/0006_exact_arbs.sql"),
+    },
+];
+
+/// Mongo schema history. Never edit a migration that's been released; add a new one.
+pub const MONGO_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "indexes",
+        up: include_str!("migrations/mongo/0001_indexes.json"),
+    },
+    Migration {
+        version: 2,
+        name: "arb_indexes",
+        up: include_str!("migrations/mongo/0002_arb_indexes.json"),
+    },
+    Migration {
+        version: 3,
+        name: "pad_max_profit",
+        up: include_str!("migrations/mongo/0003_pad_max_profit.json"),
+    },
+    Migration {
+        version: 4,
+        name: "unique_processed_events",
+        up: include_str!("migrations/mongo/0004_unique_processed_events.json"),
+    },
+    Migration {
+        version: 5,
+        name: "pad_backrun_profits",
+        up: include_str!("migrations/mongo/0005_pad_backrun_profits.json"),
+    },
+];
+
+/// SQLite schema history. Never edit a migration that's been released; add a new one.
+pub const SQLITE_MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    name: "initial",
+    up: include_str!("migrations/sqlite/0001_initial.sql"),
+}];
+
+/// ClickHouse schema history. Never edit a migration that's been released; add a new one.
+pub const CLICKHOUSE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        up: include_str!("migrations/clickhouse/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "tx_hash_index",
+        up: include_str!("migrations/clickhouse/0002_tx_hash_index.sql"),
+    },
+];
+
+/// Latest schema version in `migrations`.
+pub fn latest_version(migrations: &[Migration]) -> i32 {
+    migrations.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Checks the schema version of a DB we just connected to. Connecting only migrates new DBs
+/// (upgrading existing ones is `hindsight db migrate`'s job), so this only warns: a DB that's
+/// behind `migrations` may be missing tables or columns, & one that's ahead is read-only.
+pub fn check_version(db_name: &str, db_version: i32, migrations: &[Migration]) {
+    let latest = latest_version(migrations);
+    if db_version > latest {
+        warn!(
+            "{} schema version {} is newer than this build supports ({}); opening read-only",
+            db_name, db_version, latest
+        );
+    } else if db_version < latest {
+        warn!(
+            "{} schema version {} is older than this build's ({}); run `hindsight db migrate --db {}` to update it",
+            db_name, db_version, latest, db_name
+        );
+    }
+}
+
+/// Errors if a DB at `db_version` has migrations newer than `migrations`; this build
+/// doesn't know what they changed, so writing to it could corrupt data.
+pub fn ensure_writable(db_version: i32, migrations: &[Migration]) -> Result<()> {
+    let latest = latest_version(migrations);
+    if db_version > latest {
+        return Err(anyhow::format_err!(
+            "DB schema version {} is newer than this build of hindsight supports ({}); upgrade hindsight to write to it",
+            db_version,
+            latest
+        ));
+    }
+    Ok(())
+}
+
+/// Tracks & applies schema migrations.
+#[async_trait]
+pub trait SchemaDb: Sync + Send {
+    /// Version of the newest migration applied to the DB; 0 if it has none.
+    async fn schema_version(&self) -> Result<i32>;
+    /// Latest schema version this build knows about.
+    fn latest_schema_version(&self) -> i32;
+    /// Applies every migration the DB hasn't seen yet, in order. Returns the versions applied.
+    /// Fails if the DB's schema is newer than this build's.
+    async fn migrate(&self) -> Result<Vec<i32>>;
+}
+
+pub type SchemaDatabase = Arc<dyn SchemaDb>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_orders_migrations() {
+        for migrations in [
+            POSTGRES_MIGRATIONS,
+            MONGO_MIGRATIONS,
+            SQLITE_MIGRATIONS,
+            CLICKHOUSE_MIGRATIONS,
+        ] {
+            for (i, migration) in migrations.iter().enumerate() {
+                assert_eq!(migration.version, i as i32 + 1, "{}", migration.name);
+            }
+            assert_eq!(latest_version(migrations), migrations.len() as i32);
+        }
+    }
+
+    #[test]
+    fn it_parses_mongo_migrations() {
+        for migration in MONGO_MIGRATIONS {
+            let commands: Vec<mongodb::bson::Document> = serde_json::from_str(migration.up)
+                .unwrap_or_else(|err| panic!("bad migration {}: {}", migration.name, err));
+            assert!(!commands.is_empty());
+        }
+    }
+
+    #[test]
+    fn it_refuses_newer_schemas() {
+        let latest = latest_version(POSTGRES_MIGRATIONS);
+        assert!(ensure_writable(0, POSTGRES_MIGRATIONS).is_ok());
+        assert!(ensure_writable(latest, POSTGRES_MIGRATIONS).is_ok());
+        assert!(ensure_writable(latest + 1, POSTGRES_MIGRATIONS).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+-- One row per simulated event. `arb` holds the whole result as JSON, so it can be read back as-is.
+-- The other columns are for filtering & aggregating w/o parsing it.
+CREATE TABLE IF NOT EXISTS arbs (
+    arb_id UUID,
+    tx_hash String,
+    run_id Nullable(String),
+    event_block UInt64,
+    event_timestamp DateTime('UTC'),
+    -- wei
+    max_profit UInt256,
+    max_profit_eth Float64,
+    max_profit_usd Nullable(Float64),
+    arb String CODEC(ZSTD(3))
+) ENGINE = MergeTree
+PARTITION BY toYYYYMM(event_timestamp)
+ORDER BY (event_timestamp, tx_hash, arb_id);
+
+-- One row per (user trade, backrun) of an arb, for aggregating profit by block, token, pool & DEX.
+CREATE TABLE IF NOT EXISTS arb_trades (
+    arb_id UUID,
+    result_index UInt16,
+    tx_hash String,
+    run_id Nullable(String),
+    event_block UInt64,
+    event_timestamp DateTime('UTC'),
+    -- the non-WETH token of the user's trade
+    token String,
+    token_symbol LowCardinality(Nullable(String)),
+    user_pool String,
+    user_dex LowCardinality(String),
+    start_pool String,
+    start_dex LowCardinality(String),
+    end_pool String,
+    end_dex LowCardinality(String),
+    -- wei
+    amount_in UInt256,
+    profit UInt256,
+    profit_eth Float64
+) ENGINE = MergeTree
+PARTITION BY toYYYYMM(event_timestamp)
+ORDER BY (token, event_timestamp, tx_hash);
+
+-- Tables below are updated by inserting new rows. Read them w/ FINAL to get the latest.
+CREATE TABLE IF NOT EXISTS tokens (
+    address String,
+    symbol Nullable(String),
+    name Nullable(String),
+    decimals Nullable(UInt8)
+) ENGINE = ReplacingMergeTree
+ORDER BY address;
+
+CREATE TABLE IF NOT EXISTS scan_checkpoints (
+    run_id String,
+    block_start UInt64,
+    block_end Nullable(UInt64),
+    timestamp_start UInt64,
+    timestamp_end Nullable(UInt64),
+    event_offset UInt64,
+    done Bool,
+    updated_at UInt64
+) ENGINE = ReplacingMergeTree
+ORDER BY run_id;
+
+CREATE TABLE IF NOT EXISTS processed_events (
+    run_id String,
+    tx_hash String,
+    event_block UInt64,
+    event_timestamp UInt64
+) ENGINE = ReplacingMergeTree
+ORDER BY (run_id, tx_hash);
+
+CREATE TABLE IF NOT EXISTS scan_runs (
+    run_id String,
+    started_at UInt64,
+    run String
+) ENGINE = ReplacingMergeTree
+ORDER BY run_id;
+
+CREATE TABLE IF NOT EXISTS sim_failures (
+    run_id Nullable(String),
+    tx_hash String,
+    event_block UInt64,
+    event_timestamp DateTime('UTC'),
+    reason LowCardinality(String),
+    message String
+) ENGINE = MergeTree
+PARTITION BY toYYYYMM(event_timestamp)
+ORDER BY (event_timestamp, tx_hash);
@@ -0,0 +1,6 @@
+-- Arbs are looked up by tx hash before they're written, to resolve conflicts w/ saved ones.
+-- Neither table is ordered by it, so skip indexes keep those lookups from reading every granule.
+ALTER TABLE arbs ADD INDEX IF NOT EXISTS tx_hash_idx tx_hash TYPE bloom_filter GRANULARITY 4;
+ALTER TABLE arbs MATERIALIZE INDEX tx_hash_idx;
+ALTER TABLE arb_trades ADD INDEX IF NOT EXISTS tx_hash_idx tx_hash TYPE bloom_filter GRANULARITY 4;
+ALTER TABLE arb_trades MATERIALIZE INDEX tx_hash_idx;
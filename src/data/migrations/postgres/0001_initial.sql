@@ -0,0 +1,53 @@
+-- Tables created by hindsight before migrations were versioned.
+-- Everything is `IF NOT EXISTS`, so existing databases pick up where they are.
+
+CREATE TABLE IF NOT EXISTS hindsight (
+    tx_hash VARCHAR(66) NOT NULL PRIMARY KEY,
+    profit__eth__ NUMERIC,
+    event_block INTEGER NOT NULL,
+    event_timestamp TIMESTAMP NOT NULL
+);
+ALTER TABLE hindsight ADD COLUMN IF NOT EXISTS profit__usd__ NUMERIC;
+ALTER TABLE hindsight ADD COLUMN IF NOT EXISTS run_id TEXT;
+
+CREATE TABLE IF NOT EXISTS tokens (
+    address VARCHAR(42) NOT NULL PRIMARY KEY,
+    symbol TEXT,
+    name TEXT,
+    decimals SMALLINT
+);
+
+CREATE TABLE IF NOT EXISTS scan_checkpoints (
+    run_id TEXT NOT NULL PRIMARY KEY,
+    block_start BIGINT NOT NULL,
+    block_end BIGINT,
+    timestamp_start BIGINT NOT NULL,
+    timestamp_end BIGINT,
+    event_offset BIGINT NOT NULL,
+    done BOOLEAN NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS processed_events (
+    run_id TEXT NOT NULL,
+    tx_hash VARCHAR(66) NOT NULL,
+    event_block BIGINT NOT NULL,
+    event_timestamp BIGINT NOT NULL,
+    PRIMARY KEY (run_id, tx_hash)
+);
+
+CREATE TABLE IF NOT EXISTS scan_runs (
+    run_id TEXT NOT NULL PRIMARY KEY,
+    started_at BIGINT NOT NULL,
+    run JSONB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sim_failures (
+    id BIGSERIAL PRIMARY KEY,
+    run_id TEXT,
+    tx_hash VARCHAR(66) NOT NULL,
+    event_block BIGINT NOT NULL,
+    event_timestamp BIGINT NOT NULL,
+    reason TEXT NOT NULL,
+    message TEXT NOT NULL
+);
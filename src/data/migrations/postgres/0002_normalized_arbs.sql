@@ -0,0 +1,75 @@
+-- Everything else in a `SimArbResultBatch`, so arbs round-trip without losing data.
+-- `hindsight` keeps one summary row per arb; the rest hangs off its tx hash.
+-- Token amounts are NUMERIC(78, 0) (wei; fits any uint256/int256).
+
+-- MEV-Share events. Arbs saved before this migration have no row here.
+CREATE TABLE events (
+    tx_hash VARCHAR(66) NOT NULL PRIMARY KEY,
+    block BIGINT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    -- hinted txs, as returned by the MEV-Share API
+    hint_txs JSONB NOT NULL,
+    gas_used NUMERIC(78, 0),
+    mev_gas_price NUMERIC(78, 0)
+);
+CREATE INDEX events_block_idx ON events (block);
+
+-- Hinted logs of each event, in their original order.
+CREATE TABLE hint_logs (
+    tx_hash VARCHAR(66) NOT NULL REFERENCES events (tx_hash) ON DELETE CASCADE,
+    log_index INTEGER NOT NULL,
+    address VARCHAR(42) NOT NULL,
+    topics VARCHAR(66)[] NOT NULL,
+    PRIMARY KEY (tx_hash, log_index)
+);
+
+-- Every pool that a user trade or backrun touched.
+CREATE TABLE pools (
+    address VARCHAR(42) NOT NULL PRIMARY KEY,
+    -- UniswapV2 | UniswapV3
+    variant TEXT NOT NULL
+);
+
+-- The user's trade that each backrun result responds to; one per result, in result order.
+CREATE TABLE user_trades (
+    tx_hash VARCHAR(66) NOT NULL REFERENCES hindsight (tx_hash) ON DELETE CASCADE,
+    result_index INTEGER NOT NULL,
+    pool VARCHAR(42) NOT NULL REFERENCES pools (address),
+    token_in VARCHAR(42) NOT NULL,
+    token_out VARCHAR(42) NOT NULL,
+    amount0_sent NUMERIC(78, 0) NOT NULL,
+    amount1_sent NUMERIC(78, 0) NOT NULL,
+    token0_is_weth BOOLEAN NOT NULL,
+    price NUMERIC(78, 0) NOT NULL,
+    weth VARCHAR(42) NOT NULL,
+    token VARCHAR(42) NOT NULL,
+    -- set to the token's address if the trade was saved with its metadata
+    weth_metadata VARCHAR(42) REFERENCES tokens (address),
+    token_metadata VARCHAR(42) REFERENCES tokens (address),
+    PRIMARY KEY (tx_hash, result_index)
+);
+CREATE INDEX user_trades_pool_idx ON user_trades (pool);
+CREATE INDEX user_trades_token_idx ON user_trades (token);
+
+-- Other pools that trade the same tokens as each user trade, in their original order.
+CREATE TABLE user_trade_arb_pools (
+    tx_hash VARCHAR(66) NOT NULL,
+    result_index INTEGER NOT NULL,
+    pool_index INTEGER NOT NULL,
+    pool VARCHAR(42) NOT NULL REFERENCES pools (address),
+    PRIMARY KEY (tx_hash, result_index, pool_index),
+    FOREIGN KEY (tx_hash, result_index) REFERENCES user_trades (tx_hash, result_index) ON DELETE CASCADE
+);
+
+-- The optimal backrun found for each user trade.
+CREATE TABLE backrun_results (
+    tx_hash VARCHAR(66) NOT NULL,
+    result_index INTEGER NOT NULL,
+    amount_in NUMERIC(78, 0) NOT NULL,
+    balance_end NUMERIC(78, 0) NOT NULL,
+    profit NUMERIC(78, 0) NOT NULL,
+    start_pool VARCHAR(42) NOT NULL REFERENCES pools (address),
+    end_pool VARCHAR(42) NOT NULL REFERENCES pools (address),
+    PRIMARY KEY (tx_hash, result_index),
+    FOREIGN KEY (tx_hash, result_index) REFERENCES user_trades (tx_hash, result_index) ON DELETE CASCADE
+);
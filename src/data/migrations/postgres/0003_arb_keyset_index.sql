@@ -0,0 +1,2 @@
+-- arbs are paged through in (timestamp, hash) order
+CREATE INDEX IF NOT EXISTS hindsight_timestamp_hash_idx ON hindsight (event_timestamp, tx_hash);
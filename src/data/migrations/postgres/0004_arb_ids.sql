@@ -0,0 +1,41 @@
+-- Arbs are identified by (tx hash, run id) instead of tx hash, so that runs don't overwrite
+-- each other's results. With the keep-both conflict policy, there can be more than one arb
+-- per (tx hash, run id), so each arb gets a surrogate `id` for its results to hang off of.
+
+ALTER TABLE hindsight ADD COLUMN id BIGSERIAL;
+
+ALTER TABLE user_trades ADD COLUMN arb_id BIGINT;
+UPDATE user_trades t SET arb_id = a.id FROM hindsight a WHERE a.tx_hash = t.tx_hash;
+ALTER TABLE user_trade_arb_pools ADD COLUMN arb_id BIGINT;
+UPDATE user_trade_arb_pools p SET arb_id = a.id FROM hindsight a WHERE a.tx_hash = p.tx_hash;
+ALTER TABLE backrun_results ADD COLUMN arb_id BIGINT;
+UPDATE backrun_results b SET arb_id = a.id FROM hindsight a WHERE a.tx_hash = b.tx_hash;
+
+ALTER TABLE backrun_results
+    DROP CONSTRAINT backrun_results_tx_hash_result_index_fkey,
+    DROP CONSTRAINT backrun_results_pkey,
+    DROP COLUMN tx_hash;
+ALTER TABLE user_trade_arb_pools
+    DROP CONSTRAINT user_trade_arb_pools_tx_hash_result_index_fkey,
+    DROP CONSTRAINT user_trade_arb_pools_pkey,
+    DROP COLUMN tx_hash;
+ALTER TABLE user_trades
+    DROP CONSTRAINT user_trades_tx_hash_fkey,
+    DROP CONSTRAINT user_trades_pkey,
+    DROP COLUMN tx_hash;
+
+ALTER TABLE hindsight DROP CONSTRAINT hindsight_pkey, ADD PRIMARY KEY (id);
+CREATE INDEX hindsight_tx_hash_run_id_idx ON hindsight (tx_hash, run_id);
+
+ALTER TABLE user_trades
+    ALTER COLUMN arb_id SET NOT NULL,
+    ADD PRIMARY KEY (arb_id, result_index),
+    ADD FOREIGN KEY (arb_id) REFERENCES hindsight (id) ON DELETE CASCADE;
+ALTER TABLE user_trade_arb_pools
+    ALTER COLUMN arb_id SET NOT NULL,
+    ADD PRIMARY KEY (arb_id, result_index, pool_index),
+    ADD FOREIGN KEY (arb_id, result_index) REFERENCES user_trades (arb_id, result_index) ON DELETE CASCADE;
+ALTER TABLE backrun_results
+    ALTER COLUMN arb_id SET NOT NULL,
+    ADD PRIMARY KEY (arb_id, result_index),
+    ADD FOREIGN KEY (arb_id, result_index) REFERENCES user_trades (arb_id, result_index) ON DELETE CASCADE;
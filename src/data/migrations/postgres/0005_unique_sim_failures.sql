@@ -0,0 +1,11 @@
+-- Failures are keyed by (run id, tx hash), so that re-running a range replaces an event's
+-- failure instead of adding another. Only the latest failure of each event is kept.
+-- `run_id` can be NULL, which a plain unique index wouldn't treat as equal.
+
+DELETE FROM sim_failures a USING sim_failures b
+WHERE COALESCE(a.run_id, '') = COALESCE(b.run_id, '')
+    AND a.tx_hash = b.tx_hash
+    AND a.id < b.id;
+
+CREATE UNIQUE INDEX IF NOT EXISTS sim_failures_run_id_tx_hash_idx
+    ON sim_failures ((COALESCE(run_id, '')), tx_hash);
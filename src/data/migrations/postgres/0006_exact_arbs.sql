@@ -0,0 +1,20 @@
+-- Arbs are read back exactly as they were saved.
+
+-- Max profit in wei. `profit__eth__` stays for SQL analysis, but it was read back through a
+-- 28-digit decimal. Profits saved before this migration are only as precise as `profit__eth__`.
+ALTER TABLE hindsight ADD COLUMN profit__wei__ NUMERIC(78, 0);
+UPDATE hindsight SET profit__wei__ = ROUND(profit__eth__ * 1000000000000000000)
+WHERE profit__eth__ IS NOT NULL;
+
+-- Each trade keeps the token metadata it was saved with, rather than whatever `tokens` holds now.
+ALTER TABLE user_trades
+    ADD COLUMN weth_symbol TEXT,
+    ADD COLUMN weth_name TEXT,
+    ADD COLUMN weth_decimals SMALLINT,
+    ADD COLUMN token_symbol TEXT,
+    ADD COLUMN token_name TEXT,
+    ADD COLUMN token_decimals SMALLINT;
+UPDATE user_trades t SET weth_symbol = tk.symbol, weth_name = tk.name, weth_decimals = tk.decimals
+FROM tokens tk WHERE tk.address = t.weth_metadata;
+UPDATE user_trades t SET token_symbol = tk.symbol, token_name = tk.name, token_decimals = tk.decimals
+FROM tokens tk WHERE tk.address = t.token_metadata;
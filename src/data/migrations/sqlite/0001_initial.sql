@@ -0,0 +1,60 @@
+-- Arbs are stored whole as JSON, w/ the fields they're filtered & sorted by pulled out into columns.
+CREATE TABLE IF NOT EXISTS arbs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tx_hash TEXT NOT NULL,
+    run_id TEXT,
+    event_block INTEGER NOT NULL,
+    event_timestamp INTEGER NOT NULL,
+    -- wei as zero-padded hex, so it sorts & compares numerically
+    max_profit TEXT NOT NULL,
+    max_profit_usd REAL,
+    arb TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS arbs_tx_hash_run_id ON arbs (tx_hash, run_id);
+CREATE INDEX IF NOT EXISTS arbs_event_block ON arbs (event_block);
+CREATE INDEX IF NOT EXISTS arbs_event_timestamp_tx_hash ON arbs (event_timestamp, tx_hash, id);
+CREATE INDEX IF NOT EXISTS arbs_max_profit ON arbs (max_profit);
+
+CREATE TABLE IF NOT EXISTS tokens (
+    address TEXT NOT NULL PRIMARY KEY,
+    symbol TEXT,
+    name TEXT,
+    decimals INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS scan_checkpoints (
+    run_id TEXT NOT NULL PRIMARY KEY,
+    block_start INTEGER NOT NULL,
+    block_end INTEGER,
+    timestamp_start INTEGER NOT NULL,
+    timestamp_end INTEGER,
+    event_offset INTEGER NOT NULL,
+    done INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS processed_events (
+    run_id TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    event_block INTEGER NOT NULL,
+    event_timestamp INTEGER NOT NULL,
+    PRIMARY KEY (run_id, tx_hash)
+);
+
+CREATE TABLE IF NOT EXISTS scan_runs (
+    run_id TEXT NOT NULL PRIMARY KEY,
+    started_at INTEGER NOT NULL,
+    run TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sim_failures (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id TEXT,
+    tx_hash TEXT NOT NULL,
+    event_block INTEGER NOT NULL,
+    event_timestamp INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    message TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sim_failures_event_block ON sim_failures (event_block);
+CREATE INDEX IF NOT EXISTS sim_failures_run_id ON sim_failures (run_id);
@@ -1,8 +1,24 @@
 pub mod arbs;
+pub mod checkpoints;
+#[cfg(feature = "clickhouse")]
+mod clickhouse;
+#[cfg(test)]
+mod conformance;
 pub mod db;
+pub mod failures;
 mod file;
+mod memory;
+pub mod migrations;
 mod mongo;
 mod postgres;
+pub mod runs;
+mod sqlite;
+pub mod tokens;
 
+#[cfg(feature = "clickhouse")]
+pub use clickhouse::ClickhouseConfig;
+pub use file::FileWriter;
+pub use memory::MemoryConfig;
 pub use mongo::MongoConfig;
 pub use postgres::PostgresConfig;
+pub use sqlite::SqliteConfig;
@@ -1,15 +1,29 @@
-use super::arbs::{export_arbs_core, ArbDb, ArbFilterParams, WriteEngine};
-use crate::interfaces::SimArbResultBatch;
-use crate::interfaces::StoredArbsRanges;
-use crate::Result;
+use super::arbs::{ArbCursor, ArbFilterParams, ArbPage, ArbReader, ArbWriter, ConflictPolicy};
+use super::checkpoints::CheckpointDb;
+use super::failures::FailureDb;
+use super::migrations::{
+    check_version, ensure_writable, latest_version, Migration, SchemaDb, MONGO_MIGRATIONS,
+};
+use super::runs::RunDb;
+use super::tokens::TokenDb;
+use crate::interfaces::{to_padded_hex, SimArbResultBatch};
+use crate::interfaces::{
+    ProcessedEvent, ScanCheckpoint, ScanRun, SimFailure, StoredArbsRanges, TokenMetadata,
+};
+use crate::{info, Result};
 use async_trait::async_trait;
+use ethers::types::H256;
 use futures::stream::TryStreamExt;
-use mongodb::bson::Document;
+use mongodb::bson::{self, Document};
+use mongodb::error::ErrorKind;
 use mongodb::options::Tls;
 use mongodb::options::TlsOptions;
 use mongodb::{
     bson::doc,
-    options::{FindOneOptions, FindOptions},
+    options::{
+        FindOneAndReplaceOptions, FindOneOptions, FindOptions, InsertManyOptions, ReplaceOptions,
+        ReturnDocument, UpdateOptions,
+    },
     Collection,
 };
 use mongodb::{options::ClientOptions, Client as DbClient, Database};
@@ -19,24 +33,45 @@ use std::sync::Arc;
 pub const DB_NAME: &str = "hindsight";
 const PROJECT_NAME: &str = "simulator";
 const ARB_COLLECTION: &str = "arbs";
+const TOKEN_COLLECTION: &str = "tokens";
+const CHECKPOINT_COLLECTION: &str = "scanCheckpoints";
+const PROCESSED_EVENT_COLLECTION: &str = "processedEvents";
+const RUN_COLLECTION: &str = "scanRuns";
+const FAILURE_COLLECTION: &str = "simFailures";
+const MIGRATION_COLLECTION: &str = "schemaMigrations";
+/// Mongo's error code for a write that breaks a unique index.
+const DUPLICATE_KEY: i32 = 11000;
+/// Mongo's error code for dropping an index that doesn't exist.
+const INDEX_NOT_FOUND: i32 = 27;
 
 #[derive(Debug, Clone)]
 pub struct MongoConnect {
     arb_collection: Arc<Collection<SimArbResultBatch>>,
+    token_collection: Arc<Collection<TokenMetadata>>,
+    checkpoint_collection: Arc<Collection<ScanCheckpoint>>,
+    processed_event_collection: Arc<Collection<ProcessedEvent>>,
+    run_collection: Arc<Collection<ScanRun>>,
+    failure_collection: Arc<Collection<SimFailure>>,
+    db: Arc<Database>,
+    /// Schema version of the DB when we connected. Writes are refused if it's newer than ours.
+    schema_version: i32,
+    conflict_policy: ConflictPolicy,
 }
 
 #[derive(Clone, Debug)]
 pub struct MongoConfig {
     pub url: String,
     pub tls_ca_file_path: Option<PathBuf>,
+    pub conflict_policy: ConflictPolicy,
 }
 
 impl Default for MongoConfig {
     fn default() -> Self {
         let config = crate::config::Config::default();
         Self {
-            url: config.mongo_url,
+            url: config.mongo_url.unwrap_or_default(),
             tls_ca_file_path: config.tls_ca_file_mongo,
+            conflict_policy: ConflictPolicy::default(),
         }
     }
 }
@@ -47,19 +82,10 @@ impl From<ArbFilterParams> for Document {
         let block_end = val.block_end.unwrap_or(u32::MAX);
         let timestamp_start = val.timestamp_start.unwrap_or(1);
         let timestamp_end = val.timestamp_end.unwrap_or(u32::MAX);
-        let min_profit = val.min_profit.unwrap_or(0.into());
-        let max_profit = if min_profit > 0.into() {
-            doc! {
-                "$ne": "0x0",
-            }
-        } else {
-            // basically a noop; matches any doc w/ this field, which is all of them
-            doc! {
-                "$exists": true
-            }
-        };
+        // profits are saved as zero-padded hex, which compares like the number it represents
+        let min_profit = to_padded_hex(val.min_profit.unwrap_or(0.into()));
 
-        doc! {
+        let mut filter = doc! {
                 "event.block": {
                     "$gte": block_start,
                     "$lte": block_end,
@@ -68,23 +94,185 @@ impl From<ArbFilterParams> for Document {
                     "$gte": timestamp_start,
                     "$lte": timestamp_end,
                 },
-                "maxProfit": max_profit,
+                "maxProfit": {
+                    "$gte": min_profit,
+                },
+        };
+        if let Some(run_id) = val.run_id {
+            filter.insert("runId", run_id);
+        }
+        filter
+    }
+}
+
+/// `arb` as a mongo document. Profits are saved as zero-padded hex, so that they can be
+/// range-compared as strings.
+fn arb_document(arb: &SimArbResultBatch) -> Result<Document> {
+    let mut doc = bson::to_document(arb)?;
+    doc.insert("maxProfit", to_padded_hex(arb.max_profit));
+    let results = doc.get_array_mut("results")?;
+    for (result, arb_result) in results.iter_mut().zip(&arb.results) {
+        if let bson::Bson::Document(result) = result {
+            result
+                .get_document_mut("backrunTrade")?
+                .insert("profit", to_padded_hex(arb_result.backrun_trade.profit));
         }
     }
+    Ok(doc)
+}
+
+/// Matches the arbs saved for `arb`'s event & run.
+fn arb_key_filter(arb: &SimArbResultBatch) -> Document {
+    doc! {
+        "event.hint.hash": format!("{:?}", arb.event.hint.hash),
+        "runId": arb.run_id.to_owned(),
+    }
+}
+
+/// Matches arbs that come after `cursor` in (timestamp, hash) order.
+fn after_cursor_filter(cursor: &ArbCursor) -> Document {
+    let hash = format!("{:?}", cursor.hash);
+    doc! {
+        "$or": [
+            { "event.timestamp": { "$gt": cursor.timestamp as i64 } },
+            { "event.timestamp": cursor.timestamp as i64, "event.hint.hash": { "$gt": hash } },
+        ]
+    }
+}
+
+/// Filters failures by the block, timestamp & run id of `val`.
+fn failure_filter(val: &ArbFilterParams) -> Document {
+    let mut filter = doc! {
+        "block": {
+            "$gte": val.block_start.unwrap_or(1),
+            "$lte": val.block_end.unwrap_or(u32::MAX),
+        },
+        "timestamp": {
+            "$gte": val.timestamp_start.unwrap_or(1),
+            "$lte": val.timestamp_end.unwrap_or(u32::MAX),
+        },
+    };
+    if let Some(run_id) = &val.run_id {
+        filter.insert("runId", run_id);
+    }
+    filter
+}
+
+/// True if `err` is a command error w/ `code`, or a bulk write whose errors all have it.
+fn has_code(err: &mongodb::error::Error, code: i32) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Command(err) => err.code == code,
+        ErrorKind::BulkWrite(failure) => {
+            failure.write_concern_error.is_none()
+                && failure
+                    .write_errors
+                    .as_ref()
+                    .map_or(false, |errors| errors.iter().all(|err| err.code == code))
+        }
+        _ => false,
+    }
+}
+
+/// Version of the newest migration applied to `db`; 0 if it's never been migrated.
+async fn schema_version(db: &Database) -> Result<i32> {
+    let latest = db
+        .collection::<Document>(MIGRATION_COLLECTION)
+        .find_one(
+            None,
+            FindOneOptions::builder()
+                .sort(doc! { "version": -1 })
+                .build(),
+        )
+        .await?;
+    Ok(latest
+        .map(|migration| migration.get_i32("version"))
+        .transpose()?
+        .unwrap_or(0))
+}
+
+/// Runs the commands of each migration in `migrations` that `db` hasn't seen yet.
+/// Returns the versions applied.
+///
+/// Mongo can't run these in a transaction, so migrations must be safe to re-run
+/// (e.g. `createIndexes` is a no-op for indexes that already exist, & dropping an
+/// index that's already gone is ignored).
+async fn migrate(db: &Database, migrations: &[Migration]) -> Result<Vec<i32>> {
+    let version = schema_version(db).await?;
+    ensure_writable(version, migrations)?;
+    let mut applied_versions = vec![];
+    for migration in migrations.iter().filter(|m| m.version > version) {
+        info!(
+            "applying mongo migration {} ({})",
+            migration.version, migration.name
+        );
+        let commands: Vec<Document> = serde_json::from_str(migration.up)?;
+        for command in commands {
+            let drops_index = command.contains_key("dropIndexes");
+            if let Err(err) = db.run_command(command, None).await {
+                if !(drops_index && has_code(&err, INDEX_NOT_FOUND)) {
+                    return Err(err.into());
+                }
+            }
+        }
+        db.collection::<Document>(MIGRATION_COLLECTION)
+            .replace_one(
+                doc! { "_id": migration.version },
+                doc! {
+                    "_id": migration.version,
+                    "version": migration.version,
+                    "name": migration.name,
+                    "appliedAt": std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)?
+                        .as_secs() as i64,
+                },
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        applied_versions.push(migration.version);
+    }
+    Ok(applied_versions)
 }
 
 /// Talks to the database.
 impl MongoConnect {
-    /// Creates a new ArbDb instance, which connects to the arb collection.
+    /// Connects to the hindsight DB & its collections. The schema isn't migrated (see `check_version`);
+    /// a DB w/ a newer schema than this build's is opened read-only.
     pub async fn new(config: MongoConfig) -> Result<Self> {
+        let conflict_policy = config.conflict_policy;
         let db = MongoConnect::init_db(config).await?;
+        let schema_version = schema_version(&db).await?;
+        check_version("mongo", schema_version, MONGO_MIGRATIONS);
         let arb_collection = Arc::new(db.collection::<SimArbResultBatch>(ARB_COLLECTION));
-        // TODO: use indexes
-        Ok(Self { arb_collection })
+        let token_collection = Arc::new(db.collection::<TokenMetadata>(TOKEN_COLLECTION));
+        let checkpoint_collection =
+            Arc::new(db.collection::<ScanCheckpoint>(CHECKPOINT_COLLECTION));
+        let processed_event_collection =
+            Arc::new(db.collection::<ProcessedEvent>(PROCESSED_EVENT_COLLECTION));
+        let run_collection = Arc::new(db.collection::<ScanRun>(RUN_COLLECTION));
+        let failure_collection = Arc::new(db.collection::<SimFailure>(FAILURE_COLLECTION));
+        Ok(Self {
+            arb_collection,
+            token_collection,
+            checkpoint_collection,
+            processed_event_collection,
+            run_collection,
+            failure_collection,
+            db,
+            schema_version,
+            conflict_policy,
+        })
+    }
+
+    /// Errors if the DB's schema is newer than this build's.
+    fn ensure_writable(&self) -> Result<()> {
+        ensure_writable(self.schema_version, MONGO_MIGRATIONS)
     }
 
     /// Connects to Mongo db provided in `config`. If `config.tls_ca_file_path` is None, then TLS is disabled.
     async fn init_db(config: MongoConfig) -> Result<Arc<Database>> {
+        if config.url.is_empty() {
+            return Err(anyhow::format_err!("MONGO_URL must be set to use mongo"));
+        }
         let mut options = ClientOptions::parse(config.url).await?;
         options.app_name = Some(PROJECT_NAME.to_owned());
         options.tls = Some(config.tls_ca_file_path.map_or(Tls::Disabled, |ca_path| {
@@ -124,16 +312,129 @@ impl MongoConnect {
             .await?;
         Ok((first, last))
     }
+
+    /// Replaces the arb saved for `arb`'s (hash, run id), or inserts it if there isn't one.
+    ///
+    /// The replacement is a single atomic upsert, so a failed write never loses the saved arb.
+    /// Other arbs saved for the same (hash, run id) (w/ `keep-both`, or by a racing insert) are
+    /// deleted afterwards, but only those saved after the replaced one, so racing writers can't
+    /// delete each other's arbs & one always survives.
+    async fn replace_arb(&self, arb: &SimArbResultBatch) -> Result<()> {
+        let filter = arb_key_filter(arb);
+        let arbs = self.arb_collection.clone_with_type::<Document>();
+        let saved = arbs
+            .find_one_and_replace(
+                filter.to_owned(),
+                arb_document(arb)?,
+                FindOneAndReplaceOptions::builder()
+                    .upsert(true)
+                    .sort(doc! { "_id": 1 })
+                    .return_document(ReturnDocument::After)
+                    .projection(doc! { "_id": 1 })
+                    .build(),
+            )
+            .await?
+            .ok_or(anyhow::format_err!("upserted arb wasn't returned"))?;
+        let id = saved.get_object_id("_id")?;
+        arbs.delete_many(doc! { "$and": [filter, { "_id": { "$gt": id } }] }, None)
+            .await?;
+        Ok(())
+    }
+
+    /// Saves `arb` unless an arb w/ at least its `max_profit` is saved for its (hash, run id).
+    ///
+    /// The saved arb's profit is compared in the update itself, so it's only ever replaced by a
+    /// better one. Racing inserts for a new (hash, run id) (or arbs saved w/ `keep-both`) can
+    /// still leave several arbs, so all but the best (the earliest saved, on ties) are deleted
+    /// afterwards. Only arbs worse than one that's saved are deleted, so the best always survives.
+    async fn keep_best_arb(&self, arb: &SimArbResultBatch) -> Result<()> {
+        let filter = arb_key_filter(arb);
+        let arbs = self.arb_collection.clone_with_type::<Document>();
+        // profits are padded hex, so they compare as strings like they do as numbers;
+        // w/o a saved arb there's no `maxProfit`, which compares lower than any string
+        let keep_best = vec![doc! {
+            "$replaceWith": {
+                "$cond": [
+                    { "$lt": ["$maxProfit", to_padded_hex(arb.max_profit)] },
+                    { "$literal": arb_document(arb)? },
+                    "$$ROOT",
+                ]
+            }
+        }];
+        arbs.update_one(
+            filter.to_owned(),
+            keep_best,
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await?;
+        let best = arbs
+            .find_one(
+                filter.to_owned(),
+                FindOneOptions::builder()
+                    .sort(doc! { "maxProfit": -1, "_id": 1 })
+                    .projection(doc! { "_id": 1, "maxProfit": 1 })
+                    .build(),
+            )
+            .await?
+            .ok_or(anyhow::format_err!("upserted arb wasn't found"))?;
+        let (id, profit) = (best.get_object_id("_id")?, best.get_str("maxProfit")?);
+        arbs.delete_many(
+            doc! {
+                "$and": [
+                    filter,
+                    {
+                        "$or": [
+                            { "maxProfit": { "$lt": profit } },
+                            { "maxProfit": profit, "_id": { "$gt": id } },
+                        ]
+                    },
+                ]
+            },
+            None,
+        )
+        .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
-impl ArbDb for MongoConnect {
-    /// Write given arbs to the DB.
+impl ArbWriter for MongoConnect {
+    /// Write given arbs to the DB, resolving conflicts w/ saved arbs by `self.conflict_policy`.
+    ///
+    /// Conflicts are resolved by each write, not by reading the saved arbs first, so concurrent
+    /// writers (e.g. two scans w/ the same run id) can't both decide to save the same event.
+    /// Arbs are written in order, as if each came in its own batch.
     async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()> {
-        self.arb_collection.insert_many(arbs, None).await?;
+        self.ensure_writable()?;
+        if arbs.is_empty() {
+            return Ok(());
+        }
+        match self.conflict_policy {
+            ConflictPolicy::Replace => {
+                for arb in arbs {
+                    self.replace_arb(arb).await?;
+                }
+            }
+            ConflictPolicy::KeepBestProfit => {
+                for arb in arbs {
+                    self.keep_best_arb(arb).await?;
+                }
+            }
+            // saved arbs are never replaced, so there's nothing to compare
+            ConflictPolicy::KeepBoth => {
+                let arbs = arbs.iter().map(arb_document).collect::<Result<Vec<_>>>()?;
+                self.arb_collection
+                    .clone_with_type::<Document>()
+                    .insert_many(arbs, None)
+                    .await?;
+            }
+        }
         Ok(())
     }
+}
 
+#[async_trait]
+impl ArbReader for MongoConnect {
     async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64> {
         Ok(self
             .arb_collection
@@ -141,19 +442,24 @@ impl ArbDb for MongoConnect {
             .await?)
     }
 
-    /// Load all arbs from the DB.
+    /// Load all arbs from the DB, in (timestamp, hash) order like the other backends.
     async fn read_arbs(
         &self,
         filter_params: &ArbFilterParams,
         offset: Option<u64>,
         limit: Option<i64>,
     ) -> Result<Vec<SimArbResultBatch>> {
-        // small optimization: match non-zero profit if min_profit is set and > 0
         let mut cursor = self
             .arb_collection
             .find(
                 Some(filter_params.to_owned().into()),
-                Some(FindOptions::builder().skip(offset).limit(limit).build()),
+                Some(
+                    FindOptions::builder()
+                        .sort(doc! { "event.timestamp": 1, "event.hint.hash": 1, "_id": 1 })
+                        .skip(offset)
+                        .limit(limit)
+                        .build(),
+                ),
             )
             .await?;
 
@@ -161,14 +467,58 @@ impl ArbDb for MongoConnect {
         while let Some(res) = cursor.try_next().await? {
             results.push(res);
         }
-        // gotta filter profits in memory bc mongo doesn't support bigint comparisons
-        let results = results
-            .into_iter()
-            .filter(|arb| arb.max_profit >= filter_params.min_profit.unwrap_or(0.into()))
-            .collect::<Vec<_>>();
         Ok(results)
     }
 
+    async fn read_arbs_page(
+        &self,
+        filter_params: &ArbFilterParams,
+        after: Option<ArbCursor>,
+        limit: i64,
+    ) -> Result<ArbPage> {
+        let mut filter: Document = filter_params.to_owned().into();
+        if let Some(after) = after {
+            filter = doc! { "$and": [filter, after_cursor_filter(&after)] };
+        }
+        let cursor = self
+            .arb_collection
+            .find(
+                filter.to_owned(),
+                FindOptions::builder()
+                    .sort(doc! { "event.timestamp": 1, "event.hint.hash": 1, "_id": 1 })
+                    .limit(limit)
+                    .build(),
+            )
+            .await?;
+        let mut arbs: Vec<SimArbResultBatch> = cursor.try_collect().await?;
+        let next = ArbCursor::next(&arbs, limit);
+        if let Some(last) = next {
+            // finish the last event, so the next page can start after it
+            let num_read = arbs
+                .iter()
+                .filter(|arb| ArbCursor::from(*arb) == last)
+                .count();
+            let ties = self
+                .arb_collection
+                .find(
+                    doc! { "$and": [
+                        filter,
+                        {
+                            "event.timestamp": last.timestamp as i64,
+                            "event.hint.hash": format!("{:?}", last.hash),
+                        },
+                    ] },
+                    FindOptions::builder()
+                        .sort(doc! { "_id": 1 })
+                        .skip(num_read as u64)
+                        .build(),
+                )
+                .await?;
+            arbs.extend(ties.try_collect::<Vec<_>>().await?);
+        }
+        Ok(ArbPage { arbs, next })
+    }
+
     /// Gets the extrema of the blocks and timestamps of the arbs in the DB.
     ///
     /// It is assumed that the timestamps and blocks are both monotonically increasing,
@@ -192,22 +542,175 @@ impl ArbDb for MongoConnect {
             latest_timestamp,
         })
     }
+}
 
-    async fn export_arbs(
-        &self,
-        write_dest: WriteEngine,
-        filter_params: &ArbFilterParams,
-    ) -> Result<()> {
-        let src = Arc::new(self.clone());
-        export_arbs_core(src, write_dest, filter_params).await?;
+#[async_trait]
+impl TokenDb for MongoConnect {
+    /// Upsert token metadata, keyed by token address.
+    async fn write_tokens(&self, tokens: &[TokenMetadata]) -> Result<()> {
+        self.ensure_writable()?;
+        for token in tokens {
+            self.token_collection
+                .replace_one(
+                    doc! { "address": format!("{:?}", token.address) },
+                    token,
+                    ReplaceOptions::builder().upsert(true).build(),
+                )
+                .await?;
+        }
         Ok(())
     }
+
+    async fn read_tokens(&self) -> Result<Vec<TokenMetadata>> {
+        let cursor = self.token_collection.find(None, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
+
+#[async_trait]
+impl CheckpointDb for MongoConnect {
+    async fn save_checkpoint(&self, checkpoint: &ScanCheckpoint) -> Result<()> {
+        self.ensure_writable()?;
+        self.checkpoint_collection
+            .replace_one(
+                doc! { "runId": &checkpoint.run_id },
+                checkpoint,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_checkpoint(&self, run_id: &str) -> Result<Option<ScanCheckpoint>> {
+        Ok(self
+            .checkpoint_collection
+            .find_one(doc! { "runId": run_id }, None)
+            .await?)
+    }
+
+    async fn get_latest_unfinished_checkpoint(&self) -> Result<Option<ScanCheckpoint>> {
+        Ok(self
+            .checkpoint_collection
+            .find_one(
+                doc! { "done": false },
+                FindOneOptions::builder()
+                    .sort(doc! { "updatedAt": -1 })
+                    .build(),
+            )
+            .await?)
+    }
+
+    async fn mark_events_processed(&self, events: &[ProcessedEvent]) -> Result<()> {
+        self.ensure_writable()?;
+        if events.is_empty() {
+            return Ok(());
+        }
+        // unordered, so events that are already marked don't stop the rest from being inserted
+        match self
+            .processed_event_collection
+            .insert_many(events, InsertManyOptions::builder().ordered(false).build())
+            .await
+        {
+            Err(err) if !has_code(&err, DUPLICATE_KEY) => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn filter_processed(&self, run_id: &str, hashes: &[H256]) -> Result<Vec<H256>> {
+        let hashes = hashes
+            .iter()
+            .map(|hash| format!("{:?}", hash))
+            .collect::<Vec<_>>();
+        let cursor = self
+            .processed_event_collection
+            .find(doc! { "runId": run_id, "hash": { "$in": hashes } }, None)
+            .await?;
+        let processed: Vec<ProcessedEvent> = cursor.try_collect().await?;
+        Ok(processed.into_iter().map(|event| event.hash).collect())
+    }
+}
+
+#[async_trait]
+impl RunDb for MongoConnect {
+    async fn save_run(&self, run: &ScanRun) -> Result<()> {
+        self.ensure_writable()?;
+        self.run_collection
+            .replace_one(
+                doc! { "runId": &run.run_id },
+                run,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_run(&self, run_id: &str) -> Result<Option<ScanRun>> {
+        Ok(self
+            .run_collection
+            .find_one(doc! { "runId": run_id }, None)
+            .await?)
+    }
+
+    async fn list_runs(&self) -> Result<Vec<ScanRun>> {
+        let cursor = self
+            .run_collection
+            .find(
+                None,
+                FindOptions::builder()
+                    .sort(doc! { "startedAt": -1 })
+                    .build(),
+            )
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
+
+#[async_trait]
+impl FailureDb for MongoConnect {
+    async fn write_failures(&self, failures: &[SimFailure]) -> Result<()> {
+        self.ensure_writable()?;
+        if !failures.is_empty() {
+            self.failure_collection.insert_many(failures, None).await?;
+        }
+        Ok(())
+    }
+
+    async fn read_failures(&self, filter_params: &ArbFilterParams) -> Result<Vec<SimFailure>> {
+        let cursor = self
+            .failure_collection
+            .find(failure_filter(filter_params), None)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
+
+#[async_trait]
+impl SchemaDb for MongoConnect {
+    async fn schema_version(&self) -> Result<i32> {
+        schema_version(&self.db).await
+    }
+
+    fn latest_schema_version(&self) -> i32 {
+        latest_version(MONGO_MIGRATIONS)
+    }
+
+    async fn migrate(&self) -> Result<Vec<i32>> {
+        migrate(&self.db, MONGO_MIGRATIONS).await
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{config::Config, interfaces::SimArbResultBatch, Result};
+    use crate::{
+        config::Config,
+        data::{arbs::export_arbs_core, conformance, file::FileWriter},
+        interfaces::{
+            BackrunResult, PoolVariant, SimArbResult, SimArbResultBatch, TokenPair, UserTradeParams,
+        },
+        shutdown::Shutdown,
+        Result,
+    };
 
     async fn inject_test_arbs(
         connect: &MongoConnect,
@@ -225,15 +728,86 @@ mod test {
     }
 
     async fn connect() -> Result<MongoConnect> {
+        connect_with(ConflictPolicy::default()).await
+    }
+
+    async fn connect_with(conflict_policy: ConflictPolicy) -> Result<MongoConnect> {
         let config = Config::default();
         let connect = MongoConnect::new(MongoConfig {
-            url: config.mongo_url,
+            url: config.mongo_url.expect("MONGO_URL must be set"),
             tls_ca_file_path: config.tls_ca_file_mongo,
+            conflict_policy,
         })
         .await?;
+        // connecting doesn't migrate; `hindsight db migrate` does
+        connect.migrate().await?;
         Ok(connect)
     }
 
+    #[test]
+    fn it_pads_profits_in_documents() -> Result<()> {
+        let mut arb = SimArbResultBatch::test_example();
+        let (address, variant) = (ethers::types::Address::random(), PoolVariant::UniswapV2);
+        arb.results = vec![SimArbResult {
+            user_trade: UserTradeParams {
+                pool_variant: variant,
+                token_in: address,
+                token_out: address,
+                amount0_sent: 1.into(),
+                amount1_sent: (-1).into(),
+                token0_is_weth: true,
+                pool: address,
+                price: 1.into(),
+                tokens: TokenPair {
+                    weth: address,
+                    token: address,
+                    weth_metadata: None,
+                    token_metadata: None,
+                },
+                arb_pools: vec![],
+            },
+            backrun_trade: BackrunResult {
+                amount_in: 1.into(),
+                balance_end: 2.into(),
+                profit: 0x1337.into(),
+                start_pool: address,
+                end_pool: address,
+                start_variant: variant,
+                end_variant: variant,
+            },
+        }];
+        let doc = arb_document(&arb)?;
+        let padded = to_padded_hex(0x1337.into());
+        assert_eq!(doc.get_str("maxProfit")?, padded);
+        let result = doc.get_array("results")?[0]
+            .as_document()
+            .expect("result isn't a document");
+        assert_eq!(
+            result.get_document("backrunTrade")?.get_str("profit")?,
+            padded
+        );
+        // padding is only for mongo
+        assert_eq!(serde_json::to_value(&arb)?["maxProfit"], "0x1337");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_conforms_mongo() -> Result<()> {
+        conformance::check_backend(connect_with).await
+    }
+
+    #[tokio::test]
+    async fn it_migrates_mongo() -> Result<()> {
+        let connect = connect().await?;
+        // `connect` already migrated, so there's nothing left to apply
+        assert!(connect.migrate().await?.is_empty());
+        assert_eq!(
+            connect.schema_version().await?,
+            latest_version(MONGO_MIGRATIONS)
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn it_writes_to_db() -> Result<()> {
         let connect = connect().await?;
@@ -271,6 +845,7 @@ mod test {
                     timestamp_start: None,
                     timestamp_end: None,
                     min_profit: Some(1.into()),
+                    run_id: None,
                 },
                 Some(1),
                 Some(3),
@@ -289,6 +864,7 @@ mod test {
                     timestamp_start: Some(timestamp_first as u32),
                     timestamp_end: Some(timestamp_first as u32 + 5),
                     min_profit: Some(1.into()),
+                    run_id: None,
                 },
                 None,
                 Some(5),
@@ -318,12 +894,102 @@ mod test {
         // inject some test data first
         let connect = connect().await?;
         inject_test_arbs(&connect, 13).await?;
+        export_arbs_core(
+            &connect,
+            &FileWriter::new(Some("test_arbs.json".to_owned())),
+            &ArbFilterParams::default(),
+            &Shutdown::default(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_saves_checkpoints() -> Result<()> {
+        let connect = connect().await?;
+        let run_id = format!("test-{}", H256::random());
+        let mut checkpoint = ScanCheckpoint {
+            run_id: run_id.to_owned(),
+            block_start: 1,
+            block_end: None,
+            timestamp_start: 1,
+            timestamp_end: None,
+            offset: 500,
+            done: false,
+            updated_at: 1,
+        };
+        connect.save_checkpoint(&checkpoint).await?;
+        checkpoint.offset = 1000;
+        connect.save_checkpoint(&checkpoint).await?;
+        assert_eq!(connect.get_checkpoint(&run_id).await?, Some(checkpoint));
+
+        let arb = SimArbResultBatch::test_example();
         connect
-            .export_arbs(
-                WriteEngine::File(Some("test_arbs.json".to_owned())),
-                &ArbFilterParams::default(),
+            .mark_events_processed(&[ProcessedEvent {
+                run_id: run_id.to_owned(),
+                hash: arb.event.hint.hash,
+                block: arb.event.block,
+                timestamp: arb.event.timestamp,
+            }])
+            .await?;
+        let processed = connect
+            .filter_processed(&run_id, &[arb.event.hint.hash, H256::random()])
+            .await?;
+        assert_eq!(processed, vec![arb.event.hint.hash]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_resolves_concurrent_conflicts() -> Result<()> {
+        for policy in [ConflictPolicy::Replace, ConflictPolicy::KeepBestProfit] {
+            let connect = connect_with(policy).await?;
+            let run_id = format!("test-{}", H256::random());
+            let arbs = (1..=8u64)
+                .map(|profit| {
+                    let mut arb = SimArbResultBatch::test_example();
+                    arb.max_profit = profit.into();
+                    arb.run_id = Some(run_id.to_owned());
+                    arb
+                })
+                .collect::<Vec<_>>();
+            futures::future::try_join_all(
+                arbs.iter()
+                    .map(|arb| connect.write_arbs(std::slice::from_ref(arb))),
             )
             .await?;
+            let saved = connect
+                .read_arbs(
+                    &ArbFilterParams {
+                        run_id: Some(run_id),
+                        ..Default::default()
+                    },
+                    None,
+                    None,
+                )
+                .await?;
+            assert_eq!(saved.len(), 1, "{}", policy);
+            if policy == ConflictPolicy::KeepBestProfit {
+                assert_eq!(saved[0].max_profit, 8.into());
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_saves_failures() -> Result<()> {
+        let connect = connect().await?;
+        let run_id = format!("test-{}", H256::random());
+        let arb = SimArbResultBatch::test_example();
+        let err = crate::error::HindsightError::PoolNotFound(ethers::types::Address::zero());
+        let failure = SimFailure::new(Some(run_id.to_owned()), &arb.event, &err.into());
+        connect.write_failures(&[failure.clone()]).await?;
+        let failures = connect
+            .read_failures(&ArbFilterParams {
+                run_id: Some(run_id),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(failures, vec![failure]);
         Ok(())
     }
 
@@ -1,212 +1,1131 @@
-use super::arbs::{ArbDb, ArbFilterParams, WriteEngine};
+use super::arbs::{
+    ArbCursor, ArbFilterParams, ArbPage, ArbReader, ArbWriter, ConflictAction, ConflictPolicy,
+};
+use super::checkpoints::CheckpointDb;
+use super::failures::FailureDb;
+use super::migrations::{
+    check_version, ensure_writable, latest_version, Migration, SchemaDb, POSTGRES_MIGRATIONS,
+};
+use super::runs::RunDb;
+use super::tokens::TokenDb;
 use crate::{
-    interfaces::{SimArbResultBatch, StoredArbsRanges},
-    Result,
+    debug, info,
+    interfaces::{
+        BackrunResult, PairPool, PoolVariant, ProcessedEvent, ScanCheckpoint, ScanRun,
+        SimArbResult, SimArbResultBatch, SimFailure, StoredArbsRanges, TokenMetadata, TokenPair,
+        UserTradeParams,
+    },
+    log_error, Result,
 };
 use async_trait::async_trait;
 use chrono::NaiveDateTime;
 use ethers::{
-    types::{H256, U256},
-    utils::{format_ether, parse_ether},
+    types::{Address, H256, I256, U256},
+    utils::format_ether,
 };
-use futures::future::join_all;
 use mev_share_sse::{EventHistory, Hint};
 use rust_decimal::prelude::*;
-use std::sync::Arc;
-use tokio_postgres::{connect, Client, NoTls};
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+use tokio_postgres::{
+    connect,
+    types::{Json, ToSql},
+    Client, NoTls, Row, Transaction,
+};
 
 const ARBS_TABLE: &str = "hindsight";
+const TOKENS_TABLE: &str = "tokens";
+const CHECKPOINTS_TABLE: &str = "scan_checkpoints";
+const PROCESSED_EVENTS_TABLE: &str = "processed_events";
+const RUNS_TABLE: &str = "scan_runs";
+const FAILURES_TABLE: &str = "sim_failures";
+const MIGRATIONS_TABLE: &str = "schema_migrations";
+/// Advisory lock held while migrating, so concurrent hindsight processes don't migrate at once.
+const MIGRATION_LOCK_ID: i64 = 0x6869_6e64_7369_6768;
 
 pub struct PostgresConnect {
     client: Arc<Client>,
+    /// Arbs are written in transactions, which can't share a connection with concurrent queries.
+    writer: Arc<Mutex<Client>>,
+    /// Schema version of the DB when we connected. Writes are refused if it's newer than ours.
+    schema_version: i32,
+    conflict_policy: ConflictPolicy,
 }
 
 #[derive(Clone, Debug)]
 pub struct PostgresConfig {
     pub url: String,
+    pub conflict_policy: ConflictPolicy,
 }
 
 impl Default for PostgresConfig {
     fn default() -> Self {
         let config = crate::config::Config::default();
         Self {
-            url: config.postgres_url.unwrap(),
+            url: config.postgres_url.unwrap_or_default(),
+            conflict_policy: ConflictPolicy::default(),
         }
     }
 }
 
-fn where_filter(filter: &ArbFilterParams) -> String {
-    let mut params = vec![];
+/// Arb summary & event columns, w/ the timestamp as unix seconds.
+/// Arbs saved before the `events` table existed only have the summary's block & timestamp.
+const ARB_COLUMNS: &str =
+    "a.id, a.tx_hash, a.profit__wei__::TEXT AS profit__wei__, a.profit__usd__, a.run_id,
+    COALESCE(e.block, a.event_block) AS event_block,
+    COALESCE(e.timestamp, EXTRACT(EPOCH FROM a.event_timestamp)::BIGINT) AS event_timestamp,
+    e.hint_txs, e.gas_used::TEXT AS gas_used, e.mev_gas_price::TEXT AS mev_gas_price";
+
+/// Unix `timestamp` as a UTC `TIMESTAMP`, which is how event timestamps are stored.
+fn sql_timestamp(timestamp: u32) -> String {
+    format!("(to_timestamp({}) AT TIME ZONE 'UTC')", timestamp)
+}
+
+/// Values of a query's `$n` parameters, in order.
+fn sql_params(values: &[String]) -> Vec<&(dyn ToSql + Sync)> {
+    values
+        .iter()
+        .map(|value| value as &(dyn ToSql + Sync))
+        .collect()
+}
+
+/// Filters arbs by `filter`. Returns the condition & the values of its parameters.
+fn where_filter(filter: &ArbFilterParams) -> (String, Vec<String>) {
+    let mut params = vec!["TRUE".to_owned()];
+    let mut values = vec![];
     if let Some(block_start) = filter.block_start {
-        params.push(format!("block_number >= {}", block_start));
+        params.push(format!("event_block >= {}", block_start));
     }
     if let Some(block_end) = filter.block_end {
-        params.push(format!("block_number <= {}", block_end));
+        params.push(format!("event_block <= {}", block_end));
     }
     if let Some(timestamp_start) = filter.timestamp_start {
-        params.push(format!("timestamp >= {}", timestamp_start));
+        params.push(format!(
+            "event_timestamp >= {}",
+            sql_timestamp(timestamp_start)
+        ));
     }
     if let Some(timestamp_end) = filter.timestamp_end {
-        params.push(format!("timestamp <= {}", timestamp_end));
+        params.push(format!(
+            "event_timestamp <= {}",
+            sql_timestamp(timestamp_end)
+        ));
     }
     if let Some(min_profit) = filter.min_profit {
-        params.push(format!("profit__eth__ >= {}", format_ether(min_profit)));
+        params.push(format!("profit__wei__ >= {}", min_profit));
     }
-    params.join(" AND ")
+    if let Some(run_id) = &filter.run_id {
+        // run ids come from the CLI, so they're passed as a parameter
+        values.push(run_id.to_owned());
+        params.push(format!("run_id = ${}", values.len()));
+    }
+    (params.join(" AND "), values)
 }
 
-fn select_arbs_query(filter: &ArbFilterParams) -> String {
-    let mut query = "SELECT * FROM ".to_string();
-    query.push_str(ARBS_TABLE);
-    query.push_str(" WHERE ");
-    query.push_str(&where_filter(filter));
+/// Filters failures by the block, timestamp & run id of `filter`.
+/// Returns the condition & the values of its parameters.
+fn failure_where_filter(filter: &ArbFilterParams) -> (String, Vec<String>) {
+    let mut params = vec!["TRUE".to_owned()];
+    let mut values = vec![];
+    if let Some(block_start) = filter.block_start {
+        params.push(format!("event_block >= {}", block_start));
+    }
+    if let Some(block_end) = filter.block_end {
+        params.push(format!("event_block <= {}", block_end));
+    }
+    if let Some(timestamp_start) = filter.timestamp_start {
+        params.push(format!("event_timestamp >= {}", timestamp_start));
+    }
+    if let Some(timestamp_end) = filter.timestamp_end {
+        params.push(format!("event_timestamp <= {}", timestamp_end));
+    }
+    if let Some(run_id) = &filter.run_id {
+        values.push(run_id.to_owned());
+        params.push(format!("run_id = ${}", values.len()));
+    }
+    (params.join(" AND "), values)
+}
+
+fn select_arbs_query(
+    filter: &ArbFilterParams,
+    offset: Option<u64>,
+    limit: Option<i64>,
+) -> (String, Vec<String>) {
+    select_arbs_after_query(filter, None, offset, limit)
+}
+
+/// Selects arbs in (timestamp, hash) order, starting after `after` if given.
+fn select_arbs_after_query(
+    filter: &ArbFilterParams,
+    after: Option<ArbCursor>,
+    offset: Option<u64>,
+    limit: Option<i64>,
+) -> (String, Vec<String>) {
+    let (mut filter, values) = where_filter(filter);
+    if let Some(after) = after {
+        filter.push_str(&format!(
+            " AND (a.event_timestamp, a.tx_hash) > ({}, '{:?}')",
+            sql_timestamp(after.timestamp as u32),
+            after.hash
+        ));
+    }
+    (select_arbs_where_query(&filter, offset, limit), values)
+}
+
+/// Selects the arbs at `at` w/ ids greater than `after_id`, for finishing a page that ended partway through them.
+fn select_tied_arbs_query(
+    filter: &ArbFilterParams,
+    at: ArbCursor,
+    after_id: i64,
+) -> (String, Vec<String>) {
+    let (filter, values) = where_filter(filter);
+    let filter = format!(
+        "{} AND a.event_timestamp = {} AND a.tx_hash = '{:?}' AND a.id > {}",
+        filter,
+        sql_timestamp(at.timestamp as u32),
+        at.hash,
+        after_id
+    );
+    (select_arbs_where_query(&filter, None, None), values)
+}
+
+fn select_arbs_where_query(filter: &str, offset: Option<u64>, limit: Option<i64>) -> String {
+    let mut query = format!(
+        "SELECT {} FROM {} a LEFT JOIN events e ON e.tx_hash = a.tx_hash
+        WHERE {} ORDER BY a.event_timestamp, a.tx_hash, a.id",
+        ARB_COLUMNS, ARBS_TABLE, filter
+    );
+    if let Some(limit) = limit {
+        query.push_str(&format!(" LIMIT {}", limit));
+    }
+    if let Some(offset) = offset {
+        query.push_str(&format!(" OFFSET {}", offset));
+    }
     query
 }
 
-fn count_arbs_query(filter: &ArbFilterParams) -> String {
+fn count_arbs_query(filter: &ArbFilterParams) -> (String, Vec<String>) {
+    let (filter, values) = where_filter(filter);
     let mut query = "SELECT COUNT(*) FROM ".to_string();
     query.push_str(ARBS_TABLE);
     query.push_str(" WHERE ");
-    query.push_str(&where_filter(filter));
-    query
+    query.push_str(&filter);
+    (query, values)
+}
+
+/// Opens a connection to the DB at `url`.
+async fn open(url: &str) -> Result<Client> {
+    // TODO: add env var for postgres tls if/when implemented
+    // let pg_tls = false;
+    // let tls = if pg_tls {
+    //     OpenSsl...
+    // } else {
+    //     NoTls
+    // };
+    let (client, connection) = connect(url, NoTls).await?;
+    // The connection object performs the actual communication with the database,
+    // so spawn it off to run on its own.
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log_error!("postgres connection error: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+/// Version of the newest migration applied to the DB; 0 if it's never been migrated.
+async fn schema_version(client: &Client) -> Result<i32> {
+    let table = client
+        .query_one("SELECT to_regclass($1)::TEXT", &[&MIGRATIONS_TABLE])
+        .await?;
+    if table.get::<_, Option<&str>>(0).is_none() {
+        return Ok(0);
+    }
+    let row = client
+        .query_one(
+            &format!("SELECT COALESCE(MAX(version), 0) FROM {}", MIGRATIONS_TABLE),
+            &[],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Applies each migration in `migrations` that the DB hasn't seen yet, in its own transaction.
+/// Returns the versions applied.
+async fn migrate(client: &mut Client, migrations: &[Migration]) -> Result<Vec<i32>> {
+    ensure_writable(schema_version(client).await?, migrations)?;
+    client
+        .execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    version INTEGER NOT NULL PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    applied_at BIGINT NOT NULL
+                )",
+                MIGRATIONS_TABLE
+            ),
+            &[],
+        )
+        .await?;
+    let mut applied_versions = vec![];
+    for migration in migrations {
+        let tx = client.transaction().await?;
+        // released when the transaction ends
+        tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK_ID])
+            .await?;
+        let applied = tx
+            .query_opt(
+                &format!("SELECT 1 FROM {} WHERE version = $1", MIGRATIONS_TABLE),
+                &[&migration.version],
+            )
+            .await?
+            .is_some();
+        if applied {
+            continue;
+        }
+        info!(
+            "applying postgres migration {} ({})",
+            migration.version, migration.name
+        );
+        tx.batch_execute(migration.up).await?;
+        tx.execute(
+            &format!(
+                "INSERT INTO {} (version, name, applied_at) VALUES ($1, $2, $3)",
+                MIGRATIONS_TABLE
+            ),
+            &[
+                &migration.version,
+                &migration.name,
+                &(std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)?
+                    .as_secs() as i64),
+            ],
+        )
+        .await?;
+        tx.commit().await?;
+        applied_versions.push(migration.version);
+    }
+    Ok(applied_versions)
 }
 
 impl PostgresConnect {
+    /// Reads the arbs selected in `rows` (w/ `ARB_COLUMNS`), along w/ their hint logs & results.
+    async fn arbs_from_rows(&self, rows: &[Row]) -> Result<Vec<SimArbResultBatch>> {
+        let mut arbs = rows.iter().map(arb_from_row).collect::<Result<Vec<_>>>()?;
+        let ids = rows
+            .iter()
+            .map(|row| row.get::<_, i64>("id"))
+            .collect::<Vec<_>>();
+        let hashes = arbs
+            .iter()
+            .map(|arb| format!("{:?}", arb.event.hint.hash))
+            .collect::<Vec<_>>();
+
+        let mut logs: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
+        for row in self
+            .client
+            .query(
+                "SELECT tx_hash, address, topics FROM hint_logs
+                WHERE tx_hash = ANY($1) ORDER BY tx_hash, log_index",
+                &[&hashes],
+            )
+            .await?
+        {
+            logs.entry(row.get("tx_hash")).or_default().push(json!({
+                "address": row.get::<_, &str>("address"),
+                "topics": row.get::<_, Vec<&str>>("topics"),
+            }));
+        }
+
+        let mut arb_pools: HashMap<(i64, i32), Vec<PairPool>> = HashMap::new();
+        for row in self
+            .client
+            .query(
+                "SELECT ap.arb_id, ap.result_index, ap.pool, p.variant
+                FROM user_trade_arb_pools ap JOIN pools p ON p.address = ap.pool
+                WHERE ap.arb_id = ANY($1) ORDER BY ap.arb_id, ap.result_index, ap.pool_index",
+                &[&ids],
+            )
+            .await?
+        {
+            arb_pools
+                .entry((row.get("arb_id"), row.get("result_index")))
+                .or_default()
+                .push(PairPool {
+                    address: address_from_row(&row, "pool")?,
+                    variant: pool_variant_from_row(&row, "variant")?,
+                });
+        }
+
+        let mut results: HashMap<i64, Vec<SimArbResult>> = HashMap::new();
+        for row in self
+            .client
+            .query(
+                "SELECT t.arb_id, t.result_index, t.pool, p.variant AS pool_variant,
+                    t.token_in, t.token_out, t.amount0_sent::TEXT AS amount0_sent,
+                    t.amount1_sent::TEXT AS amount1_sent, t.token0_is_weth, t.price::TEXT AS price,
+                    t.weth, t.weth_metadata, t.weth_symbol, t.weth_name, t.weth_decimals,
+                    t.token, t.token_metadata, t.token_symbol, t.token_name, t.token_decimals,
+                    b.amount_in::TEXT AS amount_in, b.balance_end::TEXT AS balance_end,
+                    b.profit::TEXT AS profit, b.start_pool, sp.variant AS start_variant,
+                    b.end_pool, ep.variant AS end_variant
+                FROM user_trades t
+                JOIN pools p ON p.address = t.pool
+                JOIN backrun_results b ON b.arb_id = t.arb_id AND b.result_index = t.result_index
+                JOIN pools sp ON sp.address = b.start_pool
+                JOIN pools ep ON ep.address = b.end_pool
+                WHERE t.arb_id = ANY($1) ORDER BY t.arb_id, t.result_index",
+                &[&ids],
+            )
+            .await?
+        {
+            let key: (i64, i32) = (row.get("arb_id"), row.get("result_index"));
+            let result = SimArbResult {
+                user_trade: UserTradeParams {
+                    pool_variant: pool_variant_from_row(&row, "pool_variant")?,
+                    token_in: address_from_row(&row, "token_in")?,
+                    token_out: address_from_row(&row, "token_out")?,
+                    amount0_sent: i256_from_row(&row, "amount0_sent")?,
+                    amount1_sent: i256_from_row(&row, "amount1_sent")?,
+                    token0_is_weth: row.get("token0_is_weth"),
+                    pool: address_from_row(&row, "pool")?,
+                    price: u256_from_row(&row, "price")?,
+                    tokens: TokenPair {
+                        weth: address_from_row(&row, "weth")?,
+                        token: address_from_row(&row, "token")?,
+                        weth_metadata: token_metadata_from_row(&row, "weth_metadata", "weth")?,
+                        token_metadata: token_metadata_from_row(&row, "token_metadata", "token")?,
+                    },
+                    arb_pools: arb_pools.remove(&key).unwrap_or_default(),
+                },
+                backrun_trade: BackrunResult {
+                    amount_in: u256_from_row(&row, "amount_in")?,
+                    balance_end: u256_from_row(&row, "balance_end")?,
+                    profit: u256_from_row(&row, "profit")?,
+                    start_pool: address_from_row(&row, "start_pool")?,
+                    end_pool: address_from_row(&row, "end_pool")?,
+                    start_variant: pool_variant_from_row(&row, "start_variant")?,
+                    end_variant: pool_variant_from_row(&row, "end_variant")?,
+                },
+            };
+            results.entry(key.0).or_default().push(result);
+        }
+
+        for ((arb, hash), id) in arbs.iter_mut().zip(hashes).zip(ids) {
+            if let Some(logs) = logs.get(&hash) {
+                // rebuilt through serde, the same way they're parsed from the MEV-Share API
+                arb.event.hint.logs =
+                    serde_json::from_value(serde_json::Value::Array(logs.to_owned()))?;
+            }
+            arb.results = results.remove(&id).unwrap_or_default();
+        }
+        Ok(arbs)
+    }
+
+    /// Connects to the DB. A new DB is migrated right away; an existing one isn't (see
+    /// `check_version`), & one w/ a newer schema than this build's is opened read-only.
     pub async fn new(config: PostgresConfig) -> Result<Self> {
-        // TODO: add env var for postgres tls if/when implemented
-        // let pg_tls = false;
-        // let tls = if pg_tls {
-        //     OpenSsl...
-        // } else {
-        //     NoTls
-        // };
-        let (client, connection) = connect(&config.url, NoTls).await?;
-        // The connection object performs the actual communication with the database,
-        // so spawn it off to run on its own.
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("connection error: {}", e);
+        if config.url.is_empty() {
+            return Err(anyhow::format_err!(
+                "POSTGRES_URL must be set to use postgres"
+            ));
+        }
+        let mut writer = open(&config.url).await?;
+        let mut version = schema_version(&writer).await?;
+        if version == 0 {
+            migrate(&mut writer, POSTGRES_MIGRATIONS).await?;
+            version = schema_version(&writer).await?;
+        }
+        check_version("postgres", version, POSTGRES_MIGRATIONS);
+        let client = open(&config.url).await?;
+        Ok(Self {
+            client: Arc::new(client),
+            writer: Arc::new(Mutex::new(writer)),
+            schema_version: version,
+            conflict_policy: config.conflict_policy,
+        })
+    }
+
+    /// Errors if the DB's schema is newer than this build's.
+    fn ensure_writable(&self) -> Result<()> {
+        ensure_writable(self.schema_version, POSTGRES_MIGRATIONS)
+    }
+}
+
+fn u256_from_row(row: &Row, column: &str) -> Result<U256> {
+    Ok(U256::from_dec_str(row.get(column))?)
+}
+
+fn i256_from_row(row: &Row, column: &str) -> Result<I256> {
+    Ok(I256::from_dec_str(row.get(column))?)
+}
+
+fn address_from_row(row: &Row, column: &str) -> Result<Address> {
+    Ok(Address::from_str(row.get(column))?)
+}
+
+fn pool_variant_from_row(row: &Row, column: &str) -> Result<PoolVariant> {
+    Ok(row.get::<_, &str>(column).parse()?)
+}
+
+/// Metadata of the token whose address is in `column`, if the trade was saved with it.
+/// The token's columns are `{prefix}_symbol`, `{prefix}_name` & `{prefix}_decimals`.
+fn token_metadata_from_row(row: &Row, column: &str, prefix: &str) -> Result<Option<TokenMetadata>> {
+    row.get::<_, Option<&str>>(column)
+        .map(|address| {
+            Ok(TokenMetadata {
+                address: Address::from_str(address)?,
+                symbol: row.get(format!("{}_symbol", prefix).as_str()),
+                name: row.get(format!("{}_name", prefix).as_str()),
+                decimals: row
+                    .get::<_, Option<i16>>(format!("{}_decimals", prefix).as_str())
+                    .map(|d| d as u8),
+            })
+        })
+        .transpose()
+}
+
+fn arb_from_row(row: &Row) -> Result<SimArbResultBatch> {
+    Ok(SimArbResultBatch {
+        event: EventHistory {
+            block: row.get::<_, i64>("event_block") as u64,
+            timestamp: row.get::<_, i64>("event_timestamp") as u64,
+            hint: Hint {
+                txs: row
+                    .get::<_, Option<Json<_>>>("hint_txs")
+                    .map(|txs| txs.0)
+                    .unwrap_or_default(),
+                hash: H256::from_str(row.get::<_, &str>("tx_hash"))?,
+                // filled in by `arbs_from_rows`
+                logs: vec![],
+                gas_used: row
+                    .get::<_, Option<&str>>("gas_used")
+                    .map(U256::from_dec_str)
+                    .transpose()?,
+                mev_gas_price: row
+                    .get::<_, Option<&str>>("mev_gas_price")
+                    .map(U256::from_dec_str)
+                    .transpose()?,
+            },
+        },
+        max_profit: row
+            .get::<_, Option<&str>>("profit__wei__")
+            .map(U256::from_dec_str)
+            .transpose()?
+            .unwrap_or_default(),
+        max_profit_usd: row
+            .get::<_, Option<Decimal>>("profit__usd__")
+            .and_then(|usd| usd.to_f64()),
+        run_id: row.get("run_id"),
+        // filled in by `arbs_from_rows`
+        results: vec![],
+    })
+}
+
+/// Inserts a pool, unless it's already saved.
+async fn write_pool(tx: &Transaction<'_>, address: Address, variant: PoolVariant) -> Result<()> {
+    tx.execute(
+        "INSERT INTO pools (address, variant) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        &[&format!("{:?}", address), &variant.to_string()],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Saves everything in `arb` besides its summary row, replacing whatever was saved for its tx before.
+/// Saves the event & results of the arb w/ id `arb_id`.
+async fn write_arb_details(
+    tx: &Transaction<'_>,
+    arb_id: i64,
+    arb: &SimArbResultBatch,
+) -> Result<()> {
+    let txhash = format!("{:?}", arb.event.hint.hash);
+    let hint = &arb.event.hint;
+    tx.execute(
+        "INSERT INTO events (tx_hash, block, timestamp, hint_txs, gas_used, mev_gas_price)
+        VALUES ($1, $2, $3, $4, $5::TEXT::NUMERIC, $6::TEXT::NUMERIC)
+        ON CONFLICT (tx_hash) DO UPDATE SET block = EXCLUDED.block, timestamp = EXCLUDED.timestamp,
+            hint_txs = EXCLUDED.hint_txs, gas_used = EXCLUDED.gas_used, mev_gas_price = EXCLUDED.mev_gas_price",
+        &[
+            &txhash,
+            &(arb.event.block as i64),
+            &(arb.event.timestamp as i64),
+            &Json(&hint.txs),
+            &hint.gas_used.map(|gas| gas.to_string()),
+            &hint.mev_gas_price.map(|price| price.to_string()),
+        ],
+    )
+    .await?;
+    tx.execute("DELETE FROM hint_logs WHERE tx_hash = $1", &[&txhash])
+        .await?;
+    for (i, log) in hint.logs.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO hint_logs (tx_hash, log_index, address, topics) VALUES ($1, $2, $3, $4)",
+            &[
+                &txhash,
+                &(i as i32),
+                &format!("{:?}", log.address),
+                &log.topics
+                    .iter()
+                    .map(|topic| format!("{:?}", topic))
+                    .collect::<Vec<_>>(),
+            ],
+        )
+        .await?;
+    }
+
+    for (i, result) in arb.results.iter().enumerate() {
+        let (trade, backrun) = (&result.user_trade, &result.backrun_trade);
+        write_pool(tx, trade.pool, trade.pool_variant).await?;
+        write_pool(tx, backrun.start_pool, backrun.start_variant).await?;
+        write_pool(tx, backrun.end_pool, backrun.end_variant).await?;
+        for pool in &trade.arb_pools {
+            write_pool(tx, pool.address, pool.variant).await?;
+        }
+        for token in [&trade.tokens.weth_metadata, &trade.tokens.token_metadata]
+            .into_iter()
+            .flatten()
+        {
+            tx.execute(
+                &format!(
+                    "INSERT INTO {} (address, symbol, name, decimals)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (address) DO UPDATE SET symbol = $2, name = $3, decimals = $4",
+                    TOKENS_TABLE
+                ),
+                &[
+                    &format!("{:?}", token.address),
+                    &token.symbol,
+                    &token.name,
+                    &token.decimals.map(|d| d as i16),
+                ],
+            )
+            .await?;
+        }
+
+        let (weth, token) = (&trade.tokens.weth_metadata, &trade.tokens.token_metadata);
+        // the trade keeps its own copy of the metadata, since `tokens` is updated as tokens change
+        tx.execute(
+            "INSERT INTO user_trades (arb_id, result_index, pool, token_in, token_out,
+                amount0_sent, amount1_sent, token0_is_weth, price, weth, token, weth_metadata, token_metadata,
+                weth_symbol, weth_name, weth_decimals, token_symbol, token_name, token_decimals)
+            VALUES ($1, $2, $3, $4, $5, $6::TEXT::NUMERIC, $7::TEXT::NUMERIC, $8, $9::TEXT::NUMERIC,
+                $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)",
+            &[
+                &arb_id,
+                &(i as i32),
+                &format!("{:?}", trade.pool),
+                &format!("{:?}", trade.token_in),
+                &format!("{:?}", trade.token_out),
+                &trade.amount0_sent.to_string(),
+                &trade.amount1_sent.to_string(),
+                &trade.token0_is_weth,
+                &trade.price.to_string(),
+                &format!("{:?}", trade.tokens.weth),
+                &format!("{:?}", trade.tokens.token),
+                &weth.as_ref().map(|weth| format!("{:?}", weth.address)),
+                &token.as_ref().map(|token| format!("{:?}", token.address)),
+                &weth.as_ref().and_then(|weth| weth.symbol.to_owned()),
+                &weth.as_ref().and_then(|weth| weth.name.to_owned()),
+                &weth
+                    .as_ref()
+                    .and_then(|weth| weth.decimals.map(|d| d as i16)),
+                &token.as_ref().and_then(|token| token.symbol.to_owned()),
+                &token.as_ref().and_then(|token| token.name.to_owned()),
+                &token
+                    .as_ref()
+                    .and_then(|token| token.decimals.map(|d| d as i16)),
+            ],
+        )
+        .await?;
+        for (j, pool) in trade.arb_pools.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO user_trade_arb_pools (arb_id, result_index, pool_index, pool)
+                VALUES ($1, $2, $3, $4)",
+                &[
+                    &arb_id,
+                    &(i as i32),
+                    &(j as i32),
+                    &format!("{:?}", pool.address),
+                ],
+            )
+            .await?;
+        }
+        tx.execute(
+            "INSERT INTO backrun_results (arb_id, result_index, amount_in, balance_end, profit,
+                start_pool, end_pool)
+            VALUES ($1, $2, $3::TEXT::NUMERIC, $4::TEXT::NUMERIC, $5::TEXT::NUMERIC, $6, $7)",
+            &[
+                &arb_id,
+                &(i as i32),
+                &backrun.amount_in.to_string(),
+                &backrun.balance_end.to_string(),
+                &backrun.profit.to_string(),
+                &format!("{:?}", backrun.start_pool),
+                &format!("{:?}", backrun.end_pool),
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ArbWriter for PostgresConnect {
+    /// Saves all of `arbs` in one transaction, resolving conflicts w/ saved arbs by `self.conflict_policy`.
+    async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()> {
+        self.ensure_writable()?;
+        // lock events in the same order in every transaction, so concurrent writers can't deadlock
+        let mut arbs = arbs.iter().collect::<Vec<_>>();
+        arbs.sort_by_key(|arb| arb.event.hint.hash);
+        let mut writer = self.writer.lock().await;
+        let tx = writer.transaction().await?;
+        for arb in arbs {
+            // must be a better way than this :\
+            let txhash = format!("{:?}", arb.event.hint.hash);
+            // held until the transaction ends, so nobody else can save this event in the meantime
+            tx.execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[&txhash])
+                .await?;
+            let saved = tx
+                .query(
+                    &format!(
+                        "SELECT id, profit__wei__::TEXT AS profit__wei__ FROM {}
+                        WHERE tx_hash = $1 AND run_id IS NOT DISTINCT FROM $2",
+                        ARBS_TABLE
+                    ),
+                    &[&txhash, &arb.run_id],
+                )
+                .await?;
+            let saved_profits = saved
+                .iter()
+                .map(|row| {
+                    row.get::<_, Option<&str>>("profit__wei__")
+                        .map(U256::from_dec_str)
+                        .transpose()
+                        .map(Option::unwrap_or_default)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            match self.conflict_policy.resolve(&saved_profits, arb.max_profit) {
+                ConflictAction::Insert => {}
+                ConflictAction::Replace => {
+                    // deletes their results too
+                    let ids = saved
+                        .iter()
+                        .map(|row| row.get::<_, i64>("id"))
+                        .collect::<Vec<_>>();
+                    tx.execute(
+                        &format!("DELETE FROM {} WHERE id = ANY($1)", ARBS_TABLE),
+                        &[&ids],
+                    )
+                    .await?;
+                }
+                ConflictAction::Skip => continue,
             }
-        });
+            let max_profit = format_ether(arb.max_profit);
+            let max_profit_usd = arb.max_profit_usd.and_then(Decimal::from_f64);
+            let timestamp =
+                NaiveDateTime::from_timestamp_millis(arb.event.timestamp as i64 * 1000).ok_or(
+                    anyhow::format_err!("invalid event timestamp {}", arb.event.timestamp),
+                )?;
+            debug!("writing arb to postgres: {} {} eth", txhash, max_profit);
+            let row = tx.query_one(
+                &format!("INSERT INTO {} (tx_hash, profit__eth__, event_block, event_timestamp, profit__usd__, run_id, profit__wei__)
+                    VALUES ($1, $2::TEXT::NUMERIC, $3, $4, $5, $6, $7::TEXT::NUMERIC) RETURNING id",
+                    ARBS_TABLE
+                ),
+                &[
+                    &txhash,
+                    &max_profit,
+                    &(arb.event.block as i32),
+                    &timestamp,
+                    &max_profit_usd,
+                    &arb.run_id,
+                    &arb.max_profit.to_string(),
+                ],
+            )
+            .await?;
+            write_arb_details(&tx, row.get("id"), arb).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
 
-        // create arbs table pessimistically (simplified version for now: {hash, profit})
-        client
-            .execute(
+#[async_trait]
+impl ArbReader for PostgresConnect {
+    async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64> {
+        let (query, values) = count_arbs_query(filter_params);
+        let row = self.client.query_one(&query, &sql_params(&values)).await?;
+        let count: i64 = row.get(0);
+        Ok(count as u64)
+    }
+
+    async fn read_arbs(
+        &self,
+        filter_params: &ArbFilterParams,
+        offset: Option<u64>,
+        limit: Option<i64>,
+    ) -> Result<Vec<SimArbResultBatch>> {
+        let (query, values) = select_arbs_query(filter_params, offset, limit);
+        let rows = self.client.query(&query, &sql_params(&values)).await?;
+        self.arbs_from_rows(&rows).await
+    }
+
+    async fn read_arbs_page(
+        &self,
+        filter_params: &ArbFilterParams,
+        after: Option<ArbCursor>,
+        limit: i64,
+    ) -> Result<ArbPage> {
+        let (query, values) = select_arbs_after_query(filter_params, after, None, Some(limit));
+        let mut rows = self.client.query(&query, &sql_params(&values)).await?;
+        let mut arbs = self.arbs_from_rows(&rows).await?;
+        let next = ArbCursor::next(&arbs, limit);
+        if let (Some(last), Some(last_row)) = (next, rows.pop()) {
+            // finish the last event, so the next page can start after it
+            let (query, values) = select_tied_arbs_query(filter_params, last, last_row.get("id"));
+            let rows = self.client.query(&query, &sql_params(&values)).await?;
+            arbs.extend(self.arbs_from_rows(&rows).await?);
+        }
+        Ok(ArbPage { arbs, next })
+    }
+
+    /// Gets the extrema of the blocks and timestamps of the arbs in the DB.
+    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges> {
+        let row = self
+            .client
+            .query_one(
                 &format!(
-                    "CREATE TABLE IF NOT EXISTS {} (
-                        tx_hash VARCHAR(66) NOT NULL PRIMARY KEY,
-                        profit__eth__ NUMERIC,
-                        event_block INTEGER NOT NULL,
-                        event_timestamp TIMESTAMP NOT NULL
-                    )",
+                    "SELECT MIN(event_block), MAX(event_block),
+                        EXTRACT(EPOCH FROM MIN(event_timestamp))::BIGINT,
+                        EXTRACT(EPOCH FROM MAX(event_timestamp))::BIGINT
+                    FROM {}",
                     ARBS_TABLE
                 ),
                 &[],
             )
             .await?;
-
-        Ok(Self {
-            client: Arc::new(client),
+        // same defaults as mongo when there are no arbs yet
+        Ok(StoredArbsRanges {
+            earliest_block: row.get::<_, Option<i32>>(0).map_or(1, |b| b as u64),
+            latest_block: row.get::<_, Option<i32>>(1).map_or(2, |b| b as u64),
+            earliest_timestamp: row.get::<_, Option<i64>>(2).map_or(1, |t| t as u64),
+            latest_timestamp: row.get::<_, Option<i64>>(3).map_or(2, |t| t as u64),
         })
     }
 }
 
 #[async_trait]
-impl ArbDb for PostgresConnect {
-    async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()> {
-        let handles = arbs
-            .iter()
-            .map(|arb| {
-                let txhash = format!("{:?}", arb.event.hint.hash); // must be a better way than this :\
-                let max_profit = Decimal::from_str(&format_ether(arb.max_profit))
-                    .expect("failed to encode profit");
-                let timestamp =
-                    NaiveDateTime::from_timestamp_millis(arb.event.timestamp as i64 * 1000)
-                        .expect("failed to parse timestamp");
-
-                println!("writing arb to postgres: {} {} eth", txhash, max_profit);
-                // clone these to give to the tokio thread
-                let client = self.client.clone();
-                let arb = arb.clone();
-
-                tokio::task::spawn(async move {
-                    client
+impl TokenDb for PostgresConnect {
+    async fn write_tokens(&self, tokens: &[TokenMetadata]) -> Result<()> {
+        self.ensure_writable()?;
+        for token in tokens {
+            self.client
                 .execute(
-                    &format!("INSERT INTO {} (tx_hash, profit__eth__, event_block, event_timestamp)
+                    &format!(
+                        "INSERT INTO {} (address, symbol, name, decimals)
                         VALUES ($1, $2, $3, $4)
-                        ON CONFLICT (tx_hash) DO UPDATE SET profit__eth__ = $2",
-                        ARBS_TABLE
+                        ON CONFLICT (address) DO UPDATE SET symbol = $2, name = $3, decimals = $4",
+                        TOKENS_TABLE
                     ),
                     &[
-                        &txhash,
-                        &max_profit,
-                        &(arb.event.block as i32),
-                        &timestamp,
+                        &format!("{:?}", token.address),
+                        &token.symbol,
+                        &token.name,
+                        &token.decimals.map(|d| d as i16),
                     ],
                 )
-                .await.expect("failed to write arb to postgres");
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn read_tokens(&self) -> Result<Vec<TokenMetadata>> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT address, symbol, name, decimals FROM {}",
+                    TOKENS_TABLE
+                ),
+                &[],
+            )
+            .await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(TokenMetadata {
+                    address: Address::from_str(row.get::<_, &str>(0))?,
+                    symbol: row.get(1),
+                    name: row.get(2),
+                    decimals: row.get::<_, Option<i16>>(3).map(|d| d as u8),
                 })
             })
+            .collect()
+    }
+}
+
+fn checkpoint_from_row(row: &Row) -> ScanCheckpoint {
+    ScanCheckpoint {
+        run_id: row.get("run_id"),
+        block_start: row.get::<_, i64>("block_start") as u64,
+        block_end: row.get::<_, Option<i64>>("block_end").map(|b| b as u64),
+        timestamp_start: row.get::<_, i64>("timestamp_start") as u64,
+        timestamp_end: row.get::<_, Option<i64>>("timestamp_end").map(|t| t as u64),
+        offset: row.get::<_, i64>("event_offset") as u64,
+        done: row.get("done"),
+        updated_at: row.get::<_, i64>("updated_at") as u64,
+    }
+}
+
+#[async_trait]
+impl CheckpointDb for PostgresConnect {
+    async fn save_checkpoint(&self, checkpoint: &ScanCheckpoint) -> Result<()> {
+        self.ensure_writable()?;
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO {} (run_id, block_start, block_end, timestamp_start, timestamp_end, event_offset, done, updated_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    ON CONFLICT (run_id) DO UPDATE SET event_offset = $6, done = $7, updated_at = $8",
+                    CHECKPOINTS_TABLE
+                ),
+                &[
+                    &checkpoint.run_id,
+                    &(checkpoint.block_start as i64),
+                    &checkpoint.block_end.map(|b| b as i64),
+                    &(checkpoint.timestamp_start as i64),
+                    &checkpoint.timestamp_end.map(|t| t as i64),
+                    &(checkpoint.offset as i64),
+                    &checkpoint.done,
+                    &(checkpoint.updated_at as i64),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_checkpoint(&self, run_id: &str) -> Result<Option<ScanCheckpoint>> {
+        let row = self
+            .client
+            .query_opt(
+                &format!("SELECT * FROM {} WHERE run_id = $1", CHECKPOINTS_TABLE),
+                &[&run_id],
+            )
+            .await?;
+        Ok(row.as_ref().map(checkpoint_from_row))
+    }
+
+    async fn get_latest_unfinished_checkpoint(&self) -> Result<Option<ScanCheckpoint>> {
+        let row = self
+            .client
+            .query_opt(
+                &format!(
+                    "SELECT * FROM {} WHERE NOT done ORDER BY updated_at DESC LIMIT 1",
+                    CHECKPOINTS_TABLE
+                ),
+                &[],
+            )
+            .await?;
+        Ok(row.as_ref().map(checkpoint_from_row))
+    }
+
+    async fn mark_events_processed(&self, events: &[ProcessedEvent]) -> Result<()> {
+        self.ensure_writable()?;
+        for event in events {
+            self.client
+                .execute(
+                    &format!(
+                        "INSERT INTO {} (run_id, tx_hash, event_block, event_timestamp)
+                        VALUES ($1, $2, $3, $4)
+                        ON CONFLICT DO NOTHING",
+                        PROCESSED_EVENTS_TABLE
+                    ),
+                    &[
+                        &event.run_id,
+                        &format!("{:?}", event.hash),
+                        &(event.block as i64),
+                        &(event.timestamp as i64),
+                    ],
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn filter_processed(&self, run_id: &str, hashes: &[H256]) -> Result<Vec<H256>> {
+        let hashes = hashes
+            .iter()
+            .map(|hash| format!("{:?}", hash))
             .collect::<Vec<_>>();
-        join_all(handles).await;
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT tx_hash FROM {} WHERE run_id = $1 AND tx_hash = ANY($2)",
+                    PROCESSED_EVENTS_TABLE
+                ),
+                &[&run_id, &hashes],
+            )
+            .await?;
+        rows.into_iter()
+            .map(|row| Ok(H256::from_str(row.get::<_, &str>(0))?))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl RunDb for PostgresConnect {
+    async fn save_run(&self, run: &ScanRun) -> Result<()> {
+        self.ensure_writable()?;
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO {} (run_id, started_at, run) VALUES ($1, $2, $3)
+                    ON CONFLICT (run_id) DO UPDATE SET started_at = $2, run = $3",
+                    RUNS_TABLE
+                ),
+                &[&run.run_id, &(run.started_at as i64), &Json(run)],
+            )
+            .await?;
         Ok(())
     }
 
-    async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64> {
-        let query = count_arbs_query(filter_params);
-        let row = self.client.query_one(&query, &[]).await?;
-        let count: u32 = row.get(0);
-        Ok(count as u64)
+    async fn get_run(&self, run_id: &str) -> Result<Option<ScanRun>> {
+        let row = self
+            .client
+            .query_opt(
+                &format!("SELECT run FROM {} WHERE run_id = $1", RUNS_TABLE),
+                &[&run_id],
+            )
+            .await?;
+        Ok(row.map(|row| row.get::<_, Json<ScanRun>>(0).0))
     }
 
-    async fn read_arbs(
-        &self,
-        filter_params: &ArbFilterParams,
-        _offset: Option<u64>,
-        _limit: Option<i64>,
-    ) -> Result<Vec<SimArbResultBatch>> {
-        let query = select_arbs_query(filter_params);
-        let rows = self.client.query(&query, &[]).await?;
-        let arbs = rows
+    async fn list_runs(&self) -> Result<Vec<ScanRun>> {
+        let rows = self
+            .client
+            .query(
+                &format!("SELECT run FROM {} ORDER BY started_at DESC", RUNS_TABLE),
+                &[],
+            )
+            .await?;
+        Ok(rows
             .into_iter()
-            .map(|row| SimArbResultBatch {
-                event: EventHistory {
-                    // TODO: change this once the rest of the fields are added to postgres
-                    block: row.get::<usize, u32>(2) as u64,
-                    timestamp: row.get::<usize, u32>(3) as u64,
-                    hint: Hint {
-                        txs: vec![],
-                        hash: H256::from_str(&row.get::<_, String>(0)).unwrap(),
-                        logs: vec![],
-                        gas_used: None,
-                        mev_gas_price: None,
-                    },
-                },
-                max_profit: parse_ether(row.get::<usize, f64>(1).to_string())
-                    .unwrap_or(U256::zero()),
-                results: vec![],
-            })
+            .map(|row| row.get::<_, Json<ScanRun>>(0).0)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl FailureDb for PostgresConnect {
+    /// Saves `failures` in one statement. An event that already failed in the same run
+    /// has its failure replaced.
+    async fn write_failures(&self, failures: &[SimFailure]) -> Result<()> {
+        self.ensure_writable()?;
+        if failures.is_empty() {
+            return Ok(());
+        }
+        // a statement can't update the same row twice, so only the last failure of each event is kept
+        let mut latest: HashMap<(Option<String>, H256), &SimFailure> = HashMap::new();
+        for failure in failures {
+            latest.insert((failure.run_id.to_owned(), failure.hash), failure);
+        }
+        let failures = latest.into_values().collect::<Vec<_>>();
+        let run_ids = failures
+            .iter()
+            .map(|failure| failure.run_id.to_owned())
             .collect::<Vec<_>>();
-        Ok(arbs)
+        let hashes = failures
+            .iter()
+            .map(|failure| format!("{:?}", failure.hash))
+            .collect::<Vec<_>>();
+        let blocks = failures
+            .iter()
+            .map(|failure| failure.block as i64)
+            .collect::<Vec<_>>();
+        let timestamps = failures
+            .iter()
+            .map(|failure| failure.timestamp as i64)
+            .collect::<Vec<_>>();
+        let reasons = failures
+            .iter()
+            .map(|failure| failure.reason.to_string())
+            .collect::<Vec<_>>();
+        let messages = failures
+            .iter()
+            .map(|failure| failure.message.to_owned())
+            .collect::<Vec<_>>();
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO {} (run_id, tx_hash, event_block, event_timestamp, reason, message)
+                    SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::BIGINT[], $4::BIGINT[], $5::TEXT[], $6::TEXT[])
+                    ON CONFLICT ((COALESCE(run_id, '')), tx_hash) DO UPDATE SET
+                        event_block = EXCLUDED.event_block, event_timestamp = EXCLUDED.event_timestamp,
+                        reason = EXCLUDED.reason, message = EXCLUDED.message",
+                    FAILURES_TABLE
+                ),
+                &[&run_ids, &hashes, &blocks, &timestamps, &reasons, &messages],
+            )
+            .await?;
+        Ok(())
     }
 
-    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges> {
-        todo!()
+    async fn read_failures(&self, filter_params: &ArbFilterParams) -> Result<Vec<SimFailure>> {
+        let (filter, values) = failure_where_filter(filter_params);
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT run_id, tx_hash, event_block, event_timestamp, reason, message FROM {} WHERE {}",
+                    FAILURES_TABLE, filter
+                ),
+                &sql_params(&values),
+            )
+            .await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(SimFailure {
+                    run_id: row.get(0),
+                    hash: H256::from_str(row.get::<_, &str>(1))?,
+                    block: row.get::<_, i64>(2) as u64,
+                    timestamp: row.get::<_, i64>(3) as u64,
+                    reason: row.get::<_, &str>(4).parse()?,
+                    message: row.get(5),
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl SchemaDb for PostgresConnect {
+    async fn schema_version(&self) -> Result<i32> {
+        schema_version(&self.client).await
     }
 
-    async fn export_arbs(
-        &self,
-        _write_dest: WriteEngine,
-        _filter_params: &ArbFilterParams,
-    ) -> Result<()> {
-        todo!()
+    fn latest_schema_version(&self) -> i32 {
+        latest_version(POSTGRES_MIGRATIONS)
+    }
+
+    async fn migrate(&self) -> Result<Vec<i32>> {
+        let mut writer = self.writer.lock().await;
+        migrate(&mut writer, POSTGRES_MIGRATIONS).await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::{config::Config, data::conformance, error::FailureReason};
+
+    /// Connects & migrates; connecting alone doesn't, `hindsight db migrate` does.
+    async fn connect_with(config: PostgresConfig) -> Result<PostgresConnect> {
+        let connect = PostgresConnect::new(config).await?;
+        connect.migrate().await?;
+        Ok(connect)
+    }
 
     #[tokio::test]
     async fn it_connects_postgres() -> Result<()> {
@@ -217,6 +1136,7 @@ mod tests {
         }
         let connect = PostgresConnect::new(PostgresConfig {
             url: config.postgres_url.unwrap(),
+            conflict_policy: ConflictPolicy::default(),
         })
         .await?;
         let res = connect
@@ -229,6 +1149,44 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn it_migrates_postgres() -> Result<()> {
+        let config = Config::default();
+        if config.postgres_url.is_none() {
+            println!("no postgres url, skipping test");
+            return Ok(());
+        }
+        let connect = connect_with(PostgresConfig {
+            url: config.postgres_url.unwrap(),
+            conflict_policy: ConflictPolicy::default(),
+        })
+        .await?;
+        // `connect_with` already migrated, so there's nothing left to apply
+        assert!(connect.migrate().await?.is_empty());
+        assert_eq!(
+            connect.schema_version().await?,
+            latest_version(POSTGRES_MIGRATIONS)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_conforms_postgres() -> Result<()> {
+        let config = Config::default();
+        if config.postgres_url.is_none() {
+            println!("no postgres url, skipping test");
+            return Ok(());
+        }
+        let url = config.postgres_url.unwrap();
+        conformance::check_backend(|conflict_policy| {
+            connect_with(PostgresConfig {
+                url: url.to_owned(),
+                conflict_policy,
+            })
+        })
+        .await
+    }
+
     /// sends a test arb to the db
     async fn inject_test_arb(connect: &PostgresConnect) -> Result<()> {
         let arbs = vec![SimArbResultBatch::test_example()];
@@ -243,8 +1201,9 @@ mod tests {
             println!("no postgres url, skipping test");
             return Ok(());
         }
-        let connect = PostgresConnect::new(PostgresConfig {
+        let connect = connect_with(PostgresConfig {
             url: config.postgres_url.unwrap(),
+            conflict_policy: ConflictPolicy::default(),
         })
         .await?;
         inject_test_arb(&connect).await?;
@@ -257,6 +1216,203 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn it_replaces_failures_postgres() -> Result<()> {
+        let config = Config::default();
+        if config.postgres_url.is_none() {
+            println!("no postgres url, skipping test");
+            return Ok(());
+        }
+        let connect = connect_with(PostgresConfig {
+            url: config.postgres_url.unwrap(),
+            conflict_policy: ConflictPolicy::default(),
+        })
+        .await?;
+        let mut failure = SimFailure {
+            run_id: Some(format!("test-{:?}", H256::random())),
+            hash: H256::random(),
+            block: 10_001,
+            timestamp: 10_001,
+            reason: FailureReason::RpcError,
+            message: "rpc error".to_owned(),
+        };
+        let first = failure.clone();
+        failure.reason = FailureReason::PoolNotFound;
+        failure.message = "pool not found".to_owned();
+        // in the same batch & in a later one
+        connect
+            .write_failures(&[first.clone(), failure.clone()])
+            .await?;
+        connect.write_failures(&[first, failure.clone()]).await?;
+        let saved = connect
+            .read_failures(&ArbFilterParams {
+                run_id: failure.run_id.to_owned(),
+                ..ArbFilterParams::default()
+            })
+            .await?;
+        assert_eq!(saved, vec![failure]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_round_trips_arbs_postgres() -> Result<()> {
+        let config = Config::default();
+        if config.postgres_url.is_none() {
+            println!("no postgres url, skipping test");
+            return Ok(());
+        }
+        let connect = connect_with(PostgresConfig {
+            url: config.postgres_url.unwrap(),
+            conflict_policy: ConflictPolicy::default(),
+        })
+        .await?;
+        let mut arb = SimArbResultBatch::test_example();
+        arb.event = serde_json::from_value(json!({
+            "block": 17_000_000,
+            "timestamp": 1_681_000_000,
+            "hint": {
+                "txs": null,
+                "hash": format!("{:?}", arb.event.hint.hash),
+                "logs": [{
+                    "address": "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640",
+                    "topics": [
+                        "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67",
+                        "0x0000000000000000000000000000000000000000000000000000000000000000",
+                    ],
+                }],
+            },
+        }))?;
+        let (weth, token) = (Address::random(), Address::random());
+        let (pool, other_pool) = (Address::random(), Address::random());
+        arb.results = vec![SimArbResult {
+            user_trade: UserTradeParams {
+                pool_variant: PoolVariant::UniswapV3,
+                token_in: weth,
+                token_out: token,
+                amount0_sent: I256::from(-1_000_000_000_000_000_000i128),
+                amount1_sent: I256::from(42),
+                token0_is_weth: true,
+                pool,
+                price: U256::MAX,
+                tokens: TokenPair {
+                    weth,
+                    token,
+                    weth_metadata: Some(TokenMetadata {
+                        address: weth,
+                        symbol: Some("WETH".to_owned()),
+                        name: None,
+                        decimals: Some(18),
+                    }),
+                    token_metadata: None,
+                },
+                arb_pools: vec![PairPool {
+                    address: other_pool,
+                    variant: PoolVariant::UniswapV2,
+                }],
+            },
+            backrun_trade: BackrunResult {
+                amount_in: 1337.into(),
+                balance_end: 1337.into(),
+                profit: 0x1337.into(),
+                start_pool: pool,
+                end_pool: other_pool,
+                start_variant: PoolVariant::UniswapV3,
+                end_variant: PoolVariant::UniswapV2,
+            },
+        }];
+        // more digits than a 28-digit decimal can hold
+        arb.max_profit = U256::MAX;
+        connect.write_arbs(&[arb.clone()]).await?;
+        // write twice to make sure the details are replaced, not duplicated
+        connect.write_arbs(&[arb.clone()]).await?;
+        // the trade keeps the metadata it was saved with
+        connect
+            .write_tokens(&[TokenMetadata {
+                address: weth,
+                symbol: Some("WETH2".to_owned()),
+                name: Some("Wrapped Ether 2".to_owned()),
+                decimals: Some(6),
+            }])
+            .await?;
+
+        let saved = connect
+            .read_arbs(
+                &ArbFilterParams {
+                    block_start: Some(17_000_000),
+                    block_end: Some(17_000_000),
+                    ..ArbFilterParams::default()
+                },
+                None,
+                None,
+            )
+            .await?
+            .into_iter()
+            .find(|saved| saved.event.hint.hash == arb.event.hint.hash)
+            .expect("arb not saved");
+        assert_eq!(serde_json::to_value(saved)?, serde_json::to_value(arb)?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_reads_arbs_postgres() -> Result<()> {
+        let config = Config::default();
+        if config.postgres_url.is_none() {
+            println!("no postgres url, skipping test");
+            return Ok(());
+        }
+        let connect = connect_with(PostgresConfig {
+            url: config.postgres_url.unwrap(),
+            conflict_policy: ConflictPolicy::default(),
+        })
+        .await?;
+        inject_test_arb(&connect).await?;
+        let filter = ArbFilterParams::default();
+        assert!(connect.get_num_arbs(&filter).await? > 0);
+        let arbs = connect.read_arbs(&filter, Some(0), Some(1)).await?;
+        assert_eq!(arbs.len(), 1);
+        let ranges = connect.get_previously_saved_ranges().await?;
+        assert!(ranges.earliest_timestamp <= ranges.latest_timestamp);
+        assert!(ranges.earliest_block <= ranges.latest_block);
+        Ok(())
+    }
+
+    #[test]
+    fn it_filters_arbs_by_event_columns() {
+        assert_eq!(
+            where_filter(&ArbFilterParams::default()),
+            ("TRUE".to_owned(), vec![])
+        );
+        let (filter, values) = where_filter(&ArbFilterParams {
+            block_start: Some(1),
+            timestamp_end: Some(2),
+            run_id: Some("it's".to_owned()),
+            ..ArbFilterParams::default()
+        });
+        assert_eq!(
+            filter,
+            "TRUE AND event_block >= 1 AND event_timestamp <= (to_timestamp(2) AT TIME ZONE 'UTC') AND run_id = $1"
+        );
+        assert_eq!(values, vec!["it's".to_owned()]);
+    }
+
+    #[test]
+    fn it_pages_arbs_after_a_cursor() {
+        let (query, _) = select_arbs_after_query(
+            &ArbFilterParams::default(),
+            Some(ArbCursor {
+                timestamp: 12,
+                hash: H256::zero(),
+            }),
+            None,
+            Some(2),
+        );
+        assert!(query.contains(&format!(
+            "WHERE TRUE AND (a.event_timestamp, a.tx_hash) > ((to_timestamp(12) AT TIME ZONE 'UTC'), '{:?}')",
+            H256::zero()
+        )));
+        assert!(query.ends_with("ORDER BY a.event_timestamp, a.tx_hash, a.id LIMIT 2"));
+    }
+
     // #[tokio::test]
     // async fn it_reads_from_db() -> Result<()> {
     //     let config = Config::default();
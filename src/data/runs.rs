@@ -0,0 +1,17 @@
+use crate::{interfaces::ScanRun, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Stores the provenance of each scan run, so that results can be traced back to
+/// the code & config that produced them.
+#[async_trait]
+pub trait RunDb: Sync + Send {
+    /// Insert or update the record for `run.run_id`.
+    async fn save_run(&self, run: &ScanRun) -> Result<()>;
+    /// Get the record of the given run.
+    async fn get_run(&self, run_id: &str) -> Result<Option<ScanRun>>;
+    /// Get all runs, most recently started first.
+    async fn list_runs(&self) -> Result<Vec<ScanRun>>;
+}
+
+pub type RunDatabase = Arc<dyn RunDb>;
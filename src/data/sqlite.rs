@@ -0,0 +1,803 @@
+use super::arbs::{
+    ArbCursor, ArbFilterParams, ArbPage, ArbReader, ArbWriter, ConflictAction, ConflictPolicy,
+};
+use super::checkpoints::CheckpointDb;
+use super::failures::FailureDb;
+use super::migrations::{
+    check_version, ensure_writable, latest_version, Migration, SchemaDb, SQLITE_MIGRATIONS,
+};
+use super::runs::RunDb;
+use super::tokens::TokenDb;
+use crate::{
+    debug, info,
+    interfaces::{
+        to_padded_hex, ProcessedEvent, ScanCheckpoint, ScanRun, SimArbResultBatch, SimFailure,
+        StoredArbsRanges, TokenMetadata,
+    },
+    Result,
+};
+use async_trait::async_trait;
+use ethers::types::{Address, H256, U256};
+use rusqlite::{
+    params, params_from_iter, types::Value, Connection, OptionalExtension, Row, TransactionBehavior,
+};
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+const ARBS_TABLE: &str = "arbs";
+const TOKENS_TABLE: &str = "tokens";
+const CHECKPOINTS_TABLE: &str = "scan_checkpoints";
+const PROCESSED_EVENTS_TABLE: &str = "processed_events";
+const RUNS_TABLE: &str = "scan_runs";
+const FAILURES_TABLE: &str = "sim_failures";
+const MIGRATIONS_TABLE: &str = "schema_migrations";
+
+/// Stores everything in one local file; no DB server needed.
+pub struct SqliteConnect {
+    /// SQLite connections can't be shared between threads, so queries take turns.
+    conn: Arc<Mutex<Connection>>,
+    /// Schema version of the DB when we connected. Writes are refused if it's newer than ours.
+    schema_version: i32,
+    conflict_policy: ConflictPolicy,
+}
+
+#[derive(Clone, Debug)]
+pub struct SqliteConfig {
+    /// DB file, created if it doesn't exist. `:memory:` keeps the DB in memory.
+    pub path: PathBuf,
+    pub conflict_policy: ConflictPolicy,
+}
+
+impl Default for SqliteConfig {
+    fn default() -> Self {
+        let config = crate::config::Config::default();
+        Self {
+            path: config.sqlite_path,
+            conflict_policy: ConflictPolicy::default(),
+        }
+    }
+}
+
+/// SQL conditions joined by AND, w/ the params they bind in order.
+#[derive(Clone, Debug, Default)]
+struct Conditions {
+    sql: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl Conditions {
+    fn push(&mut self, condition: &str, params: Vec<Value>) {
+        self.sql.push(condition.to_owned());
+        self.params.extend(params);
+    }
+
+    fn to_sql(&self) -> String {
+        let mut sql = vec!["TRUE".to_owned()];
+        sql.extend(self.sql.iter().cloned());
+        sql.join(" AND ")
+    }
+}
+
+/// Filters rows by the block, timestamp & run id of `filter`.
+fn event_filter(filter: &ArbFilterParams) -> Conditions {
+    let mut conditions = Conditions::default();
+    if let Some(block_start) = filter.block_start {
+        conditions.push("event_block >= ?", vec![(block_start as i64).into()]);
+    }
+    if let Some(block_end) = filter.block_end {
+        conditions.push("event_block <= ?", vec![(block_end as i64).into()]);
+    }
+    if let Some(timestamp_start) = filter.timestamp_start {
+        conditions.push(
+            "event_timestamp >= ?",
+            vec![(timestamp_start as i64).into()],
+        );
+    }
+    if let Some(timestamp_end) = filter.timestamp_end {
+        conditions.push("event_timestamp <= ?", vec![(timestamp_end as i64).into()]);
+    }
+    if let Some(run_id) = &filter.run_id {
+        conditions.push("run_id = ?", vec![run_id.to_owned().into()]);
+    }
+    conditions
+}
+
+fn where_filter(filter: &ArbFilterParams) -> Conditions {
+    let mut conditions = event_filter(filter);
+    if let Some(min_profit) = filter.min_profit {
+        // padded hex compares like the number it encodes
+        conditions.push("max_profit >= ?", vec![to_padded_hex(min_profit).into()]);
+    }
+    conditions
+}
+
+/// Selects (id, arb) of the arbs matching `conditions`, in (timestamp, hash) order.
+fn select_arbs(
+    conn: &Connection,
+    conditions: &Conditions,
+    offset: Option<u64>,
+    limit: Option<i64>,
+) -> Result<Vec<(i64, SimArbResultBatch)>> {
+    let query = format!(
+        "SELECT id, arb FROM {} WHERE {} ORDER BY event_timestamp, tx_hash, id LIMIT ? OFFSET ?",
+        ARBS_TABLE,
+        conditions.to_sql()
+    );
+    let mut params = conditions.params.to_owned();
+    // a negative limit means no limit
+    params.push(limit.unwrap_or(-1).into());
+    params.push((offset.unwrap_or(0) as i64).into());
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(params_from_iter(params), |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+    rows.map(|row| {
+        let (id, arb) = row?;
+        Ok((id, serde_json::from_str(&arb)?))
+    })
+    .collect()
+}
+
+/// Version of the newest migration applied to the DB; 0 if it's never been migrated.
+fn schema_version(conn: &Connection) -> Result<i32> {
+    let has_migrations = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?",
+            [MIGRATIONS_TABLE],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !has_migrations {
+        return Ok(0);
+    }
+    Ok(conn.query_row(
+        &format!("SELECT COALESCE(MAX(version), 0) FROM {}", MIGRATIONS_TABLE),
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+/// Applies each migration in `migrations` that the DB hasn't seen yet, in its own transaction.
+/// Returns the versions applied.
+fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<Vec<i32>> {
+    ensure_writable(schema_version(conn)?, migrations)?;
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version INTEGER NOT NULL PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
+            )",
+            MIGRATIONS_TABLE
+        ),
+        [],
+    )?;
+    let mut applied_versions = vec![];
+    for migration in migrations {
+        // takes the write lock up front, so concurrent hindsight processes don't migrate at once
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let applied = tx
+            .query_row(
+                &format!("SELECT 1 FROM {} WHERE version = ?", MIGRATIONS_TABLE),
+                [migration.version],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if applied {
+            continue;
+        }
+        info!(
+            "applying sqlite migration {} ({})",
+            migration.version, migration.name
+        );
+        tx.execute_batch(migration.up)?;
+        tx.execute(
+            &format!(
+                "INSERT INTO {} (version, name, applied_at) VALUES (?, ?, ?)",
+                MIGRATIONS_TABLE
+            ),
+            params![
+                migration.version,
+                migration.name,
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)?
+                    .as_secs() as i64
+            ],
+        )?;
+        tx.commit()?;
+        applied_versions.push(migration.version);
+    }
+    Ok(applied_versions)
+}
+
+impl SqliteConnect {
+    /// Opens (or creates) the DB file. A new DB is migrated right away; an existing one isn't
+    /// (see `check_version`), & one w/ a newer schema than this build's is opened read-only.
+    pub async fn new(config: SqliteConfig) -> Result<Self> {
+        let path = config.path;
+        let (conn, version) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let mut conn = Connection::open(path)?;
+            let mut version = schema_version(&conn)?;
+            if version == 0 {
+                migrate(&mut conn, SQLITE_MIGRATIONS)?;
+                version = schema_version(&conn)?;
+            }
+            check_version("sqlite", version, SQLITE_MIGRATIONS);
+            Ok((conn, version))
+        })
+        .await??;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            schema_version: version,
+            conflict_policy: config.conflict_policy,
+        })
+    }
+
+    /// Errors if the DB's schema is newer than this build's.
+    fn ensure_writable(&self) -> Result<()> {
+        ensure_writable(self.schema_version, SQLITE_MIGRATIONS)
+    }
+
+    /// Runs `f` w/ the connection on a blocking thread, so queries don't stall the async runtime.
+    async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| anyhow::format_err!("sqlite connection poisoned"))?;
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl ArbWriter for SqliteConnect {
+    /// Saves all of `arbs` in one transaction, resolving conflicts w/ saved arbs by `self.conflict_policy`.
+    async fn write_arbs(&self, arbs: &[SimArbResultBatch]) -> Result<()> {
+        self.ensure_writable()?;
+        let arbs = arbs.to_vec();
+        let conflict_policy = self.conflict_policy;
+        self.call(move |conn| {
+            // sqlite allows one writer at a time, so nobody else can save these events in the meantime
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for arb in arbs {
+                let txhash = format!("{:?}", arb.event.hint.hash);
+                let saved_profits = tx
+                    .prepare(&format!(
+                        "SELECT max_profit FROM {} WHERE tx_hash = ? AND run_id IS ?",
+                        ARBS_TABLE
+                    ))?
+                    .query_map(params![txhash, arb.run_id], |row| row.get::<_, String>(0))?
+                    .map(|profit| {
+                        Ok(U256::from_str_radix(
+                            profit?.trim_start_matches("0x"),
+                            16,
+                        )?)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                match conflict_policy.resolve(&saved_profits, arb.max_profit) {
+                    ConflictAction::Insert => {}
+                    ConflictAction::Replace => {
+                        tx.execute(
+                            &format!(
+                                "DELETE FROM {} WHERE tx_hash = ? AND run_id IS ?",
+                                ARBS_TABLE
+                            ),
+                            params![txhash, arb.run_id],
+                        )?;
+                    }
+                    ConflictAction::Skip => continue,
+                }
+                debug!("writing arb to sqlite: {} {:?} wei", txhash, arb.max_profit);
+                tx.execute(
+                    &format!(
+                        "INSERT INTO {} (tx_hash, run_id, event_block, event_timestamp, max_profit, max_profit_usd, arb)
+                        VALUES (?, ?, ?, ?, ?, ?, ?)",
+                        ARBS_TABLE
+                    ),
+                    params![
+                        txhash,
+                        arb.run_id,
+                        arb.event.block as i64,
+                        arb.event.timestamp as i64,
+                        to_padded_hex(arb.max_profit),
+                        arb.max_profit_usd,
+                        serde_json::to_string(&arb)?,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl ArbReader for SqliteConnect {
+    async fn get_num_arbs(&self, filter_params: &ArbFilterParams) -> Result<u64> {
+        let conditions = where_filter(filter_params);
+        self.call(move |conn| {
+            let count: i64 = conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {} WHERE {}",
+                    ARBS_TABLE,
+                    conditions.to_sql()
+                ),
+                params_from_iter(conditions.params),
+                |row| row.get(0),
+            )?;
+            Ok(count as u64)
+        })
+        .await
+    }
+
+    async fn read_arbs(
+        &self,
+        filter_params: &ArbFilterParams,
+        offset: Option<u64>,
+        limit: Option<i64>,
+    ) -> Result<Vec<SimArbResultBatch>> {
+        let conditions = where_filter(filter_params);
+        self.call(move |conn| {
+            Ok(select_arbs(conn, &conditions, offset, limit)?
+                .into_iter()
+                .map(|(_, arb)| arb)
+                .collect())
+        })
+        .await
+    }
+
+    async fn read_arbs_page(
+        &self,
+        filter_params: &ArbFilterParams,
+        after: Option<ArbCursor>,
+        limit: i64,
+    ) -> Result<ArbPage> {
+        let conditions = where_filter(filter_params);
+        self.call(move |conn| {
+            let mut page_conditions = conditions.to_owned();
+            if let Some(after) = after {
+                page_conditions.push(
+                    "(event_timestamp, tx_hash) > (?, ?)",
+                    vec![
+                        (after.timestamp as i64).into(),
+                        format!("{:?}", after.hash).into(),
+                    ],
+                );
+            }
+            let (ids, mut arbs): (Vec<_>, Vec<_>) =
+                select_arbs(conn, &page_conditions, None, Some(limit))?
+                    .into_iter()
+                    .unzip();
+            let next = ArbCursor::next(&arbs, limit);
+            if let (Some(last), Some(last_id)) = (next, ids.last()) {
+                // finish the last event, so the next page can start after it
+                let mut tied_conditions = conditions;
+                tied_conditions.push(
+                    "event_timestamp = ? AND tx_hash = ? AND id > ?",
+                    vec![
+                        (last.timestamp as i64).into(),
+                        format!("{:?}", last.hash).into(),
+                        (*last_id).into(),
+                    ],
+                );
+                arbs.extend(
+                    select_arbs(conn, &tied_conditions, None, None)?
+                        .into_iter()
+                        .map(|(_, arb)| arb),
+                );
+            }
+            Ok(ArbPage { arbs, next })
+        })
+        .await
+    }
+
+    /// Gets the extrema of the blocks and timestamps of the arbs in the DB.
+    async fn get_previously_saved_ranges(&self) -> Result<StoredArbsRanges> {
+        self.call(|conn| {
+            Ok(conn.query_row(
+                &format!(
+                    "SELECT MIN(event_block), MAX(event_block), MIN(event_timestamp), MAX(event_timestamp)
+                    FROM {}",
+                    ARBS_TABLE
+                ),
+                [],
+                // same defaults as mongo when there are no arbs yet
+                |row| {
+                    Ok(StoredArbsRanges {
+                        earliest_block: row.get::<_, Option<i64>>(0)?.map_or(1, |b| b as u64),
+                        latest_block: row.get::<_, Option<i64>>(1)?.map_or(2, |b| b as u64),
+                        earliest_timestamp: row.get::<_, Option<i64>>(2)?.map_or(1, |t| t as u64),
+                        latest_timestamp: row.get::<_, Option<i64>>(3)?.map_or(2, |t| t as u64),
+                    })
+                },
+            )?)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl TokenDb for SqliteConnect {
+    async fn write_tokens(&self, tokens: &[TokenMetadata]) -> Result<()> {
+        self.ensure_writable()?;
+        let tokens = tokens.to_vec();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            for token in tokens {
+                tx.execute(
+                    &format!(
+                        "INSERT INTO {} (address, symbol, name, decimals) VALUES (?1, ?2, ?3, ?4)
+                        ON CONFLICT (address) DO UPDATE SET symbol = ?2, name = ?3, decimals = ?4",
+                        TOKENS_TABLE
+                    ),
+                    params![
+                        format!("{:?}", token.address),
+                        token.symbol,
+                        token.name,
+                        token.decimals
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn read_tokens(&self) -> Result<Vec<TokenMetadata>> {
+        self.call(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT address, symbol, name, decimals FROM {}",
+                TOKENS_TABLE
+            ))?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<u8>>(3)?,
+                ))
+            })?;
+            rows.map(|row| {
+                let (address, symbol, name, decimals) = row?;
+                Ok(TokenMetadata {
+                    address: Address::from_str(&address)?,
+                    symbol,
+                    name,
+                    decimals,
+                })
+            })
+            .collect()
+        })
+        .await
+    }
+}
+
+fn checkpoint_from_row(row: &Row) -> rusqlite::Result<ScanCheckpoint> {
+    Ok(ScanCheckpoint {
+        run_id: row.get("run_id")?,
+        block_start: row.get::<_, i64>("block_start")? as u64,
+        block_end: row.get::<_, Option<i64>>("block_end")?.map(|b| b as u64),
+        timestamp_start: row.get::<_, i64>("timestamp_start")? as u64,
+        timestamp_end: row
+            .get::<_, Option<i64>>("timestamp_end")?
+            .map(|t| t as u64),
+        offset: row.get::<_, i64>("event_offset")? as u64,
+        done: row.get("done")?,
+        updated_at: row.get::<_, i64>("updated_at")? as u64,
+    })
+}
+
+#[async_trait]
+impl CheckpointDb for SqliteConnect {
+    async fn save_checkpoint(&self, checkpoint: &ScanCheckpoint) -> Result<()> {
+        self.ensure_writable()?;
+        let checkpoint = checkpoint.to_owned();
+        self.call(move |conn| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO {} (run_id, block_start, block_end, timestamp_start, timestamp_end, event_offset, done, updated_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                    ON CONFLICT (run_id) DO UPDATE SET event_offset = ?6, done = ?7, updated_at = ?8",
+                    CHECKPOINTS_TABLE
+                ),
+                params![
+                    checkpoint.run_id,
+                    checkpoint.block_start as i64,
+                    checkpoint.block_end.map(|b| b as i64),
+                    checkpoint.timestamp_start as i64,
+                    checkpoint.timestamp_end.map(|t| t as i64),
+                    checkpoint.offset as i64,
+                    checkpoint.done,
+                    checkpoint.updated_at as i64,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_checkpoint(&self, run_id: &str) -> Result<Option<ScanCheckpoint>> {
+        let run_id = run_id.to_owned();
+        self.call(move |conn| {
+            Ok(conn
+                .query_row(
+                    &format!("SELECT * FROM {} WHERE run_id = ?", CHECKPOINTS_TABLE),
+                    [run_id],
+                    checkpoint_from_row,
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn get_latest_unfinished_checkpoint(&self) -> Result<Option<ScanCheckpoint>> {
+        self.call(|conn| {
+            Ok(conn
+                .query_row(
+                    &format!(
+                        "SELECT * FROM {} WHERE NOT done ORDER BY updated_at DESC LIMIT 1",
+                        CHECKPOINTS_TABLE
+                    ),
+                    [],
+                    checkpoint_from_row,
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn mark_events_processed(&self, events: &[ProcessedEvent]) -> Result<()> {
+        self.ensure_writable()?;
+        let events = events.to_vec();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            for event in events {
+                tx.execute(
+                    &format!(
+                        "INSERT INTO {} (run_id, tx_hash, event_block, event_timestamp)
+                        VALUES (?, ?, ?, ?)
+                        ON CONFLICT DO NOTHING",
+                        PROCESSED_EVENTS_TABLE
+                    ),
+                    params![
+                        event.run_id,
+                        format!("{:?}", event.hash),
+                        event.block as i64,
+                        event.timestamp as i64,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn filter_processed(&self, run_id: &str, hashes: &[H256]) -> Result<Vec<H256>> {
+        if hashes.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut params: Vec<Value> = vec![run_id.to_owned().into()];
+        params.extend(hashes.iter().map(|hash| format!("{:?}", hash).into()));
+        self.call(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT tx_hash FROM {} WHERE run_id = ? AND tx_hash IN ({})",
+                PROCESSED_EVENTS_TABLE,
+                vec!["?"; params.len() - 1].join(", ")
+            ))?;
+            let rows = stmt.query_map(params_from_iter(params), |row| row.get::<_, String>(0))?;
+            rows.map(|hash| Ok(H256::from_str(&hash?)?)).collect()
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl RunDb for SqliteConnect {
+    async fn save_run(&self, run: &ScanRun) -> Result<()> {
+        self.ensure_writable()?;
+        let (run_id, started_at, run) = (
+            run.run_id.to_owned(),
+            run.started_at as i64,
+            serde_json::to_string(run)?,
+        );
+        self.call(move |conn| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO {} (run_id, started_at, run) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (run_id) DO UPDATE SET started_at = ?2, run = ?3",
+                    RUNS_TABLE
+                ),
+                params![run_id, started_at, run],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_run(&self, run_id: &str) -> Result<Option<ScanRun>> {
+        let run_id = run_id.to_owned();
+        self.call(move |conn| {
+            conn.query_row(
+                &format!("SELECT run FROM {} WHERE run_id = ?", RUNS_TABLE),
+                [run_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .map(|run| Ok(serde_json::from_str(&run)?))
+            .transpose()
+        })
+        .await
+    }
+
+    async fn list_runs(&self) -> Result<Vec<ScanRun>> {
+        self.call(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT run FROM {} ORDER BY started_at DESC",
+                RUNS_TABLE
+            ))?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.map(|run| Ok(serde_json::from_str(&run?)?)).collect()
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl FailureDb for SqliteConnect {
+    async fn write_failures(&self, failures: &[SimFailure]) -> Result<()> {
+        self.ensure_writable()?;
+        let failures = failures.to_vec();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            for failure in failures {
+                tx.execute(
+                    &format!(
+                        "INSERT INTO {} (run_id, tx_hash, event_block, event_timestamp, reason, message)
+                        VALUES (?, ?, ?, ?, ?, ?)",
+                        FAILURES_TABLE
+                    ),
+                    params![
+                        failure.run_id,
+                        format!("{:?}", failure.hash),
+                        failure.block as i64,
+                        failure.timestamp as i64,
+                        failure.reason.to_string(),
+                        failure.message,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn read_failures(&self, filter_params: &ArbFilterParams) -> Result<Vec<SimFailure>> {
+        let conditions = event_filter(filter_params);
+        self.call(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT run_id, tx_hash, event_block, event_timestamp, reason, message FROM {} WHERE {}",
+                FAILURES_TABLE,
+                conditions.to_sql()
+            ))?;
+            let rows = stmt.query_map(params_from_iter(conditions.params), |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?;
+            rows.map(|row| {
+                let (run_id, hash, block, timestamp, reason, message) = row?;
+                Ok(SimFailure {
+                    run_id,
+                    hash: H256::from_str(&hash)?,
+                    block: block as u64,
+                    timestamp: timestamp as u64,
+                    reason: reason.parse()?,
+                    message,
+                })
+            })
+            .collect()
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl SchemaDb for SqliteConnect {
+    async fn schema_version(&self) -> Result<i32> {
+        self.call(|conn| schema_version(conn)).await
+    }
+
+    fn latest_schema_version(&self) -> i32 {
+        latest_version(SQLITE_MIGRATIONS)
+    }
+
+    async fn migrate(&self) -> Result<Vec<i32>> {
+        self.call(|conn| migrate(conn, SQLITE_MIGRATIONS)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::conformance;
+
+    /// Opens & migrates a fresh DB that only lives as long as the connection.
+    async fn connect_with(conflict_policy: ConflictPolicy) -> Result<SqliteConnect> {
+        let connect = SqliteConnect::new(SqliteConfig {
+            path: ":memory:".into(),
+            conflict_policy,
+        })
+        .await?;
+        connect.migrate().await?;
+        Ok(connect)
+    }
+
+    async fn connect() -> Result<SqliteConnect> {
+        connect_with(ConflictPolicy::default()).await
+    }
+
+    #[tokio::test]
+    async fn it_migrates_sqlite() -> Result<()> {
+        let connect = SqliteConnect::new(SqliteConfig {
+            path: ":memory:".into(),
+            conflict_policy: ConflictPolicy::default(),
+        })
+        .await?;
+        // a new DB is migrated when it's opened, so there's nothing left to apply
+        assert!(connect.migrate().await?.is_empty());
+        assert_eq!(
+            connect.schema_version().await?,
+            latest_version(SQLITE_MIGRATIONS)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_conforms_sqlite() -> Result<()> {
+        conformance::check_backend(connect_with).await
+    }
+
+    #[tokio::test]
+    async fn it_saves_checkpoints_sqlite() -> Result<()> {
+        let connect = connect().await?;
+        let mut checkpoint = ScanCheckpoint {
+            run_id: "test".to_owned(),
+            block_start: 1,
+            block_end: None,
+            timestamp_start: 1,
+            timestamp_end: Some(2),
+            offset: 0,
+            done: false,
+            updated_at: 1,
+        };
+        connect.save_checkpoint(&checkpoint).await?;
+        checkpoint.offset = 42;
+        connect.save_checkpoint(&checkpoint).await?;
+        let saved = connect
+            .get_latest_unfinished_checkpoint()
+            .await?
+            .expect("checkpoint not saved");
+        assert_eq!(saved.offset, 42);
+        Ok(())
+    }
+}
@@ -0,0 +1,14 @@
+use crate::{interfaces::TokenMetadata, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Persists token metadata so we only have to fetch it from the chain once per token.
+#[async_trait]
+pub trait TokenDb: Sync + Send {
+    /// Insert or update metadata for the given tokens.
+    async fn write_tokens(&self, tokens: &[TokenMetadata]) -> Result<()>;
+    /// Load metadata for all tokens saved in the DB.
+    async fn read_tokens(&self) -> Result<Vec<TokenMetadata>>;
+}
+
+pub type TokenDatabase = Arc<dyn TokenDb>;
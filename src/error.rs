@@ -1,5 +1,62 @@
 use crate::Error;
-use ethers::types::{Address, H256};
+use ethers::{
+    abi::{self, ParamType},
+    providers::ProviderError,
+    types::{Address, Bytes, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinError;
+
+/// `Error(string)` selector, used by `require` & `revert`.
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// `Panic(uint256)` selector, used by failed asserts, overflows, etc.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Decoded revert data of a reverted call.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RevertReason {
+    /// Message from `require(cond, "msg")` or `revert("msg")`.
+    Error(String),
+    /// Solidity panic code (e.g. 0x11 for arithmetic overflow).
+    Panic(U256),
+    /// Custom error or no revert data; can't be decoded w/o the contract ABI.
+    Raw(Bytes),
+}
+
+impl RevertReason {
+    pub fn decode(output: &[u8]) -> Self {
+        if output.len() >= 4 {
+            let (selector, data) = output.split_at(4);
+            if selector == ERROR_STRING_SELECTOR {
+                if let Some(msg) = abi::decode(&[ParamType::String], data)
+                    .ok()
+                    .and_then(|tokens| tokens[0].clone().into_string())
+                {
+                    return RevertReason::Error(msg);
+                }
+            } else if selector == PANIC_SELECTOR {
+                if let Some(code) = abi::decode(&[ParamType::Uint(256)], data)
+                    .ok()
+                    .and_then(|tokens| tokens[0].clone().into_uint())
+                {
+                    return RevertReason::Panic(code);
+                }
+            }
+        }
+        RevertReason::Raw(Bytes::from(output.to_vec()))
+    }
+}
+
+impl std::fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RevertReason::Error(msg) => write!(f, "{}", msg),
+            RevertReason::Panic(code) => write!(f, "panic (code={:#x})", code),
+            RevertReason::Raw(output) if output.is_empty() => write!(f, "no revert data"),
+            RevertReason::Raw(output) => write!(f, "{}", output),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub enum HindsightError {
@@ -17,37 +74,253 @@ pub enum HindsightError {
     MathError(String),
     /// Failed to parse data into revm core types.
     EvmParseError(String),
+    /// Every backrun swap in a round of simulations reverted (or halted).
+    AllSwapsReverted,
+    /// A simulation task panicked.
+    SimPanicked(String),
+    /// A simulation task was cancelled before it finished.
+    TaskCancelled,
+    /// A braindance swap reverted.
+    SwapReverted(RevertReason),
+    /// A read-only contract call reverted.
+    CallReverted(RevertReason),
+    /// EVM execution halted (out of gas, invalid opcode, etc).
+    Halted(String),
+    /// Failed to decode contract output or log data.
+    DecodeError(String),
+    /// Request to the RPC node failed.
+    RpcError(String),
+    /// The EVM failed to execute a tx (e.g. failed to fetch state from the fork's RPC node).
+    EvmError(String),
 }
 
-impl From<HindsightError> for Error {
-    fn from(val: HindsightError) -> Self {
-        match val {
+impl std::fmt::Display for HindsightError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
             HindsightError::BlockNotFound(block_number) => {
-                anyhow::format_err!("block not found (number={})", block_number)
+                write!(f, "block not found (number={})", block_number)
             }
             HindsightError::EventNotCached(tx_hash) => {
-                anyhow::format_err!("event not cached (hash={})", tx_hash)
+                write!(f, "event not cached (hash={})", tx_hash)
             }
             HindsightError::PoolNotFound(address) => {
-                anyhow::format_err!("no other pool found, (pool={})", address)
-            }
-            HindsightError::TxNotLanded(tx_hash) => {
-                anyhow::format_err!("tx not landed (hash={})", tx_hash)
-            }
-            HindsightError::CallError(msg) => anyhow::format_err!("call error: {}", msg),
-            HindsightError::MathError(msg) => {
-                anyhow::format_err!("math error: {}", msg,)
-            }
-            HindsightError::EvmParseError(msg) => {
-                anyhow::format_err!("evm parse error: {}", msg,)
+                write!(f, "no other pool found, (pool={})", address)
             }
+            HindsightError::TxNotLanded(tx_hash) => write!(f, "tx not landed (hash={})", tx_hash),
+            HindsightError::CallError(msg) => write!(f, "call error: {}", msg),
+            HindsightError::MathError(msg) => write!(f, "math error: {}", msg),
+            HindsightError::EvmParseError(msg) => write!(f, "evm parse error: {}", msg),
+            HindsightError::AllSwapsReverted => write!(f, "all swaps reverted"),
+            HindsightError::SimPanicked(msg) => write!(f, "sim panicked: {}", msg),
+            HindsightError::TaskCancelled => write!(f, "sim task cancelled"),
+            HindsightError::SwapReverted(reason) => write!(f, "swap reverted: {}", reason),
+            HindsightError::CallReverted(reason) => write!(f, "call reverted: {}", reason),
+            HindsightError::Halted(reason) => write!(f, "evm halted: {}", reason),
+            HindsightError::DecodeError(msg) => write!(f, "decode error: {}", msg),
+            HindsightError::RpcError(msg) => write!(f, "rpc error: {}", msg),
+            HindsightError::EvmError(msg) => write!(f, "evm error: {}", msg),
         }
     }
 }
 
+/// Converts into `anyhow::Error` via anyhow's blanket impl, which keeps the
+/// original error around for `downcast_ref`.
+impl std::error::Error for HindsightError {}
+
+impl From<ProviderError> for HindsightError {
+    fn from(err: ProviderError) -> Self {
+        HindsightError::RpcError(err.to_string())
+    }
+}
+
+impl From<abi::Error> for HindsightError {
+    fn from(err: abi::Error) -> Self {
+        HindsightError::DecodeError(err.to_string())
+    }
+}
+
+impl HindsightError {
+    /// Converts the error of a spawned task (panicked or cancelled).
+    pub fn from_join_error(err: JoinError) -> Self {
+        if err.is_panic() {
+            let panic = err.into_panic();
+            let msg = panic
+                .downcast_ref::<String>()
+                .cloned()
+                .or(panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+            HindsightError::SimPanicked(msg)
+        } else {
+            HindsightError::TaskCancelled
+        }
+    }
+
+    /// Wraps an untyped error from a helper that talks to the RPC node.
+    pub fn rpc(err: Error) -> Self {
+        HindsightError::RpcError(format!("{:#}", err))
+    }
+
+    /// Wraps an untyped error from a math helper.
+    pub fn math(err: Error) -> Self {
+        HindsightError::MathError(format!("{:#}", err))
+    }
+}
+
+/// Reason code of a failed simulation, used to group failures in reports.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Serialize,
+    PartialEq,
+    Eq,
+    Hash,
+    strum::Display,
+    strum::EnumString,
+)]
+pub enum FailureReason {
+    BlockNotFound,
+    EventNotCached,
+    PoolNotFound,
+    TxNotLanded,
+    CallError,
+    MathError,
+    EvmParseError,
+    AllSwapsReverted,
+    Panic,
+    Cancelled,
+    SwapReverted,
+    CallReverted,
+    Halted,
+    DecodeError,
+    RpcError,
+    EvmError,
+    /// Any error that isn't a HindsightError (RPC errors, etc).
+    Other,
+}
+
+impl FailureReason {
+    /// True if simulating the event again would fail the same way, e.g. its tx never landed
+    /// or every backrun reverted. Failures that may be the RPC node's fault aren't.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            FailureReason::PoolNotFound
+            | FailureReason::TxNotLanded
+            | FailureReason::MathError
+            | FailureReason::EvmParseError
+            | FailureReason::AllSwapsReverted
+            | FailureReason::Panic
+            | FailureReason::SwapReverted
+            | FailureReason::CallReverted
+            | FailureReason::Halted
+            | FailureReason::DecodeError => true,
+            FailureReason::BlockNotFound
+            | FailureReason::EventNotCached
+            | FailureReason::CallError
+            | FailureReason::Cancelled
+            | FailureReason::RpcError
+            | FailureReason::EvmError
+            | FailureReason::Other => false,
+        }
+    }
+}
+
+impl From<&HindsightError> for FailureReason {
+    fn from(err: &HindsightError) -> Self {
+        match err {
+            HindsightError::BlockNotFound(_) => FailureReason::BlockNotFound,
+            HindsightError::EventNotCached(_) => FailureReason::EventNotCached,
+            HindsightError::PoolNotFound(_) => FailureReason::PoolNotFound,
+            HindsightError::TxNotLanded(_) => FailureReason::TxNotLanded,
+            HindsightError::CallError(_) => FailureReason::CallError,
+            HindsightError::MathError(_) => FailureReason::MathError,
+            HindsightError::EvmParseError(_) => FailureReason::EvmParseError,
+            HindsightError::AllSwapsReverted => FailureReason::AllSwapsReverted,
+            HindsightError::SimPanicked(_) => FailureReason::Panic,
+            HindsightError::TaskCancelled => FailureReason::Cancelled,
+            HindsightError::SwapReverted(_) => FailureReason::SwapReverted,
+            HindsightError::CallReverted(_) => FailureReason::CallReverted,
+            HindsightError::Halted(_) => FailureReason::Halted,
+            HindsightError::DecodeError(_) => FailureReason::DecodeError,
+            HindsightError::RpcError(_) => FailureReason::RpcError,
+            HindsightError::EvmError(_) => FailureReason::EvmError,
+        }
+    }
+}
+
+impl From<&Error> for FailureReason {
+    fn from(err: &Error) -> Self {
+        err.downcast_ref::<HindsightError>()
+            .map(FailureReason::from)
+            .unwrap_or(FailureReason::Other)
+    }
+}
+
 #[macro_export]
 macro_rules! err {
     ($($arg:tt)*) => {
         Err(anyhow::anyhow!(format!($($arg)*)))
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_classifies_errors() {
+        let err: Error = HindsightError::PoolNotFound(Address::zero()).into();
+        assert_eq!(FailureReason::from(&err), FailureReason::PoolNotFound);
+        // context doesn't hide the original error
+        let err = err.context("sim failed");
+        assert_eq!(FailureReason::from(&err), FailureReason::PoolNotFound);
+        let err = anyhow::anyhow!("rpc timeout");
+        assert_eq!(FailureReason::from(&err), FailureReason::Other);
+        assert!(!FailureReason::from(&err).is_terminal());
+        let err: Error = HindsightError::TxNotLanded(H256::zero()).into();
+        assert!(FailureReason::from(&err).is_terminal());
+    }
+
+    #[tokio::test]
+    async fn it_classifies_panics() {
+        let err = tokio::spawn(async { panic!("weird token") })
+            .await
+            .unwrap_err();
+        let err: Error = HindsightError::from_join_error(err).into();
+        assert_eq!(FailureReason::from(&err), FailureReason::Panic);
+        assert!(err.to_string().contains("weird token"));
+    }
+
+    #[test]
+    fn it_decodes_revert_reasons() {
+        let mut output = ERROR_STRING_SELECTOR.to_vec();
+        output.extend(abi::encode(&[abi::Token::String(
+            "UniswapV2: K".to_owned(),
+        )]));
+        assert_eq!(
+            RevertReason::decode(&output),
+            RevertReason::Error("UniswapV2: K".to_owned())
+        );
+
+        let mut output = PANIC_SELECTOR.to_vec();
+        output.extend(abi::encode(&[abi::Token::Uint(0x11.into())]));
+        assert_eq!(
+            RevertReason::decode(&output),
+            RevertReason::Panic(0x11.into())
+        );
+        assert_eq!(
+            RevertReason::decode(&output).to_string(),
+            "panic (code=0x11)"
+        );
+
+        assert_eq!(
+            RevertReason::decode(&[]),
+            RevertReason::Raw(Bytes::default())
+        );
+        assert_eq!(
+            RevertReason::decode(&[0xde, 0xad, 0xbe, 0xef]),
+            RevertReason::Raw(Bytes::from(vec![0xde, 0xad, 0xbe, 0xef]))
+        );
+    }
+}
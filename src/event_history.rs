@@ -1,7 +1,14 @@
-use crate::Result;
-use mev_share_sse::{EventClient, EventHistory, EventHistoryParams};
+use crate::{info, Result};
+use futures::{stream, StreamExt, TryStreamExt};
+use mev_share_sse::{EventClient, EventHistory, EventHistoryInfo, EventHistoryParams};
+use std::collections::HashSet;
 
 const FLASHBOTS_EVENTS_API_URL: &str = "https://mev-share.flashbots.net/api/v1";
+const FLASHBOTS_EVENTS_STREAM_URL: &str = "https://mev-share.flashbots.net";
+/// Max number of pages (or sub-ranges) fetched at once.
+pub const FETCH_CONCURRENCY: usize = 8;
+/// Max number of sub-ranges a range is split into.
+const MAX_SUB_RANGES: u64 = 1024;
 
 pub fn event_history_info_url() -> String {
     format!("{}/{}", FLASHBOTS_EVENTS_API_URL, "history/info")
@@ -9,43 +16,332 @@ pub fn event_history_info_url() -> String {
 pub fn event_history_url() -> String {
     format!("{}/{}", FLASHBOTS_EVENTS_API_URL, "history")
 }
+pub fn event_stream_url() -> String {
+    FLASHBOTS_EVENTS_STREAM_URL.to_owned()
+}
+
+/// Events per page for `params`: its `limit`, clamped to the most the API returns at once.
+///
+/// Pages shorter than this are the last of their range; with a bigger `limit`, every page
+/// would come back short.
+pub fn page_size(params: &EventHistoryParams, info: &EventHistoryInfo) -> u64 {
+    params
+        .limit
+        .unwrap_or(info.max_limit)
+        .clamp(1, info.max_limit.max(1))
+}
 
-/// Fetches events from the Flashbots MEV-Share SSE API. Iteratively queries for
-/// events in chunks of `info.max_limit` until all events in the specified range
-/// have been fetched.
+/// Fetches events from the Flashbots MEV-Share SSE API.
 ///
-/// TODO: fetch events in parallel
+/// The requested range is split into sub-ranges of about one page (`info.max_limit` events)
+/// each, by block if a block bound is given, otherwise by timestamp. Sub-ranges are paged
+/// through concurrently, at most `FETCH_CONCURRENCY` at a time. Events are de-duplicated by
+/// hint hash (sub-ranges share their boundaries) and returned in block order.
 pub async fn fetch_latest_events(
     client: &EventClient,
     params: EventHistoryParams,
 ) -> Result<Vec<EventHistory>> {
-    let mut current_offset = 0;
-    let mut done = false;
-    let mut events = vec![];
     let info = client.event_history_info(&event_history_info_url()).await?;
-    while !done {
+    let page_size = page_size(&params, &info);
+    let sub_ranges = split_range(&params, &info);
+    info!(
+        "fetching events in {} sub-ranges ({} at a time)",
+        sub_ranges.len(),
+        FETCH_CONCURRENCY
+    );
+    let chunks = stream::iter(sub_ranges)
+        .map(|params| fetch_range(client, params, page_size))
+        .buffer_unordered(FETCH_CONCURRENCY)
+        .try_collect::<Vec<_>>()
+        .await?;
+    let events = dedup_events(chunks.into_iter().flatten().collect());
+    info!("fetched {} events", events.len());
+    Ok(events)
+}
+
+/// Pages through all events matching `params`, `page_size` events at a time.
+async fn fetch_range(
+    client: &EventClient,
+    params: EventHistoryParams,
+    page_size: u64,
+) -> Result<Vec<EventHistory>> {
+    let mut events = vec![];
+    let mut offset = params.offset.unwrap_or_default();
+    loop {
         let mut chunk = client
             .event_history(
                 &event_history_url(),
                 EventHistoryParams {
-                    block_start: params.block_start,
-                    block_end: params.block_end,
-                    timestamp_start: params.timestamp_start,
-                    timestamp_end: params.timestamp_end,
-                    limit: Some(info.max_limit),
-                    offset: Some(current_offset),
+                    limit: Some(page_size),
+                    offset: Some(offset),
+                    ..params.clone()
                 },
             )
             .await?;
         let chunk_len = chunk.len() as u64;
-        current_offset += chunk_len;
+        offset += chunk_len;
         events.append(&mut chunk);
-        done = chunk_len < params.limit.unwrap_or(500);
-        println!(
-            "Fetched {} events ({} events total)",
-            chunk_len,
-            events.len()
+        if chunk_len < page_size {
+            return Ok(events);
+        }
+    }
+}
+
+/// Splits the range of `params` into contiguous sub-ranges with shared boundaries, sized so that
+/// each holds about one page of events (estimated from the API's overall event density).
+///
+/// Splits by block if either block bound is set, otherwise by timestamp. Open bounds are
+/// filled in from `info`. The other dimension's bounds are kept on every sub-range.
+fn split_range(params: &EventHistoryParams, info: &EventHistoryInfo) -> Vec<EventHistoryParams> {
+    let by_block = params.block_start.is_some()
+        || params.block_end.is_some()
+        || (params.timestamp_start.is_none() && params.timestamp_end.is_none());
+    let (start, end, total_span) = if by_block {
+        (
+            params.block_start.unwrap_or(info.min_block),
+            params.block_end.unwrap_or(info.max_block),
+            info.max_block.saturating_sub(info.min_block),
+        )
+    } else {
+        (
+            params.timestamp_start.unwrap_or(info.min_timestamp),
+            params.timestamp_end.unwrap_or(info.max_timestamp),
+            info.max_timestamp.saturating_sub(info.min_timestamp),
+        )
+    };
+    let span = end.saturating_sub(start);
+    let estimated_events = info.count.saturating_mul(span) / total_span.max(1);
+    let num_ranges = (estimated_events / page_size(params, info))
+        .clamp(1, MAX_SUB_RANGES)
+        .min(span.max(1));
+    let step = span / num_ranges;
+
+    (0..num_ranges)
+        .map(|i| {
+            let range_start = start + step * i;
+            let range_end = if i == num_ranges - 1 {
+                end
+            } else {
+                range_start + step
+            };
+            // each sub-range is paged from its own start
+            let offset = None;
+            if by_block {
+                EventHistoryParams {
+                    block_start: Some(range_start),
+                    block_end: Some(range_end),
+                    offset,
+                    ..params.clone()
+                }
+            } else {
+                EventHistoryParams {
+                    timestamp_start: Some(range_start),
+                    timestamp_end: Some(range_end),
+                    offset,
+                    ..params.clone()
+                }
+            }
+        })
+        .collect()
+}
+
+/// Drops events w/ the same hint hash as an earlier event & sorts them by block.
+fn dedup_events(mut events: Vec<EventHistory>) -> Vec<EventHistory> {
+    events.sort_by_key(|event| (event.block, event.timestamp));
+    let mut seen = HashSet::new();
+    events.retain(|event| seen.insert(event.hint.hash));
+    events
+}
+
+/// Fetches up to `num_pages` consecutive pages of `page_size` events matching `params`,
+/// starting at `params.offset`, concurrently (at most `FETCH_CONCURRENCY` at a time).
+/// `page_size` should come from `page_size`, so that only the last page can be short.
+///
+/// Pages are returned in order, each w/ the offset right after it, so they can be checkpointed
+/// one by one. Pages after the first short one (i.e. past the latest events) are dropped.
+pub async fn fetch_pages(
+    client: &EventClient,
+    params: &EventHistoryParams,
+    page_size: u64,
+    num_pages: usize,
+) -> Result<Vec<(Vec<EventHistory>, u64)>> {
+    let offset = params.offset.unwrap_or_default();
+    let url = event_history_url();
+    let pages = stream::iter(0..num_pages.max(1) as u64)
+        .map(|i| {
+            client.event_history(
+                &url,
+                EventHistoryParams {
+                    limit: Some(page_size),
+                    offset: Some(offset + i * page_size),
+                    ..params.clone()
+                },
+            )
+        })
+        .buffered(FETCH_CONCURRENCY)
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(full_pages(pages, offset, page_size))
+}
+
+/// Pairs each page w/ the offset after it, up to & including the first short page.
+///
+/// Events indexed while the pages were fetched shift later pages, so an event can show up
+/// on two of them; it's only kept on the first. Each page is sorted by block.
+fn full_pages(
+    pages: Vec<Vec<EventHistory>>,
+    mut offset: u64,
+    page_size: u64,
+) -> Vec<(Vec<EventHistory>, u64)> {
+    let mut full = vec![];
+    let mut seen = HashSet::new();
+    for mut page in pages {
+        let is_last = (page.len() as u64) < page_size;
+        // the offset counts every event the API returned, duplicates included
+        offset += page.len() as u64;
+        page.sort_by_key(|event| (event.block, event.timestamp));
+        page.retain(|event| seen.insert(event.hint.hash));
+        full.push((page, offset));
+        if is_last {
+            break;
+        }
+    }
+    full
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn info() -> EventHistoryInfo {
+        EventHistoryInfo {
+            count: 1_000_000,
+            min_block: 17_000_000,
+            max_block: 18_000_000,
+            min_timestamp: 1_680_000_000,
+            max_timestamp: 1_692_000_000,
+            max_limit: 500,
+        }
+    }
+
+    fn event(block: u64, hash: u64) -> EventHistory {
+        serde_json::from_value(json!({
+            "block": block,
+            "timestamp": block * 12,
+            "hint": {
+                "txs": null,
+                "hash": format!("{:#066x}", hash),
+                "logs": [],
+            }
+        }))
+        .expect("bad test event")
+    }
+
+    #[test]
+    fn it_clamps_page_sizes() {
+        let params = |limit| EventHistoryParams {
+            limit,
+            ..Default::default()
+        };
+        assert_eq!(page_size(&params(None), &info()), 500);
+        assert_eq!(page_size(&params(Some(100)), &info()), 100);
+        // the API would return 500 at most, so every page would look like the last one
+        assert_eq!(page_size(&params(Some(10_000)), &info()), 500);
+    }
+
+    #[test]
+    fn it_splits_block_ranges() {
+        let params = EventHistoryParams {
+            block_start: Some(17_500_000),
+            block_end: Some(17_510_000),
+            timestamp_start: Some(1),
+            ..Default::default()
+        };
+        // ~10k events in range => 20 pages of 500
+        let ranges = split_range(&params, &info());
+        assert_eq!(ranges.len(), 20);
+        assert_eq!(ranges[0].block_start, Some(17_500_000));
+        assert_eq!(ranges[19].block_end, Some(17_510_000));
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].block_end, pair[1].block_start);
+        }
+        // the other bounds are kept
+        assert!(ranges.iter().all(|r| r.timestamp_start == Some(1)));
+    }
+
+    #[test]
+    fn it_splits_open_timestamp_ranges() {
+        let params = EventHistoryParams {
+            timestamp_start: Some(1_691_999_000),
+            ..Default::default()
+        };
+        let ranges = split_range(&params, &info());
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].timestamp_start, Some(1_691_999_000));
+        assert_eq!(ranges[0].timestamp_end, Some(1_692_000_000));
+        assert_eq!(ranges[0].block_start, None);
+    }
+
+    #[test]
+    fn it_dedups_and_sorts_overlapping_sub_ranges() {
+        // sub-ranges share their boundary blocks, so both return the events in them
+        let chunks = vec![
+            vec![event(3, 4), event(2, 3), event(1, 1)],
+            vec![event(3, 4), event(4, 5)],
+            vec![event(1, 1), event(1, 2)],
+        ];
+        let events = dedup_events(chunks.into_iter().flatten().collect());
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| (e.block, e.hint.hash.to_low_u64_be()))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (1, 2), (2, 3), (3, 4), (4, 5)]
+        );
+    }
+
+    #[test]
+    fn it_stops_at_the_first_short_page() {
+        let pages = vec![
+            vec![event(1, 1), event(1, 2)],
+            vec![event(2, 3), event(2, 4)],
+            vec![event(3, 5)],
+            // fetched concurrently, but past the end of the range when it was requested
+            vec![],
+        ];
+        let offsets = full_pages(pages, 10, 2)
+            .into_iter()
+            .map(|(page, offset)| (page.len(), offset))
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![(2, 12), (2, 14), (1, 15)]);
+    }
+
+    #[test]
+    fn it_dedups_and_sorts_overlapping_pages() {
+        // an event indexed mid-fetch pushed (2, 3) onto the next page too
+        let pages = vec![
+            vec![event(2, 3), event(1, 1), event(1, 2)],
+            vec![event(2, 3), event(3, 5), event(2, 4)],
+            vec![event(3, 6)],
+        ];
+        let pages = full_pages(pages, 0, 3)
+            .into_iter()
+            .map(|(page, offset)| {
+                let events = page
+                    .iter()
+                    .map(|e| (e.block, e.hint.hash.to_low_u64_be()))
+                    .collect::<Vec<_>>();
+                (events, offset)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pages,
+            vec![
+                (vec![(1, 1), (1, 2), (2, 3)], 3),
+                (vec![(2, 4), (3, 5)], 6),
+                (vec![(3, 6)], 7),
+            ]
         );
     }
-    Ok(events)
 }
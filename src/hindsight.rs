@@ -1,82 +1,105 @@
 use crate::{
-    data::arbs::ArbDatabase,
+    config::Config,
+    data::{arbs::ArbDatabase, failures::FailureDatabase, tokens::TokenDatabase},
     info,
-    sim::processor::{simulate_backrun_arbs, H256Map},
+    interfaces::SimStats,
+    pipeline::{Pipeline, PipelineConfig},
+    sim::{
+        backend::{anvil::AnvilConfig, SimBackendKind, SimBackendRef},
+        pricing::{EthPriceCache, EthPriceSource},
+    },
+    tokens::TokenCache,
     util::WsClient,
     Result,
 };
-use ethers::types::Transaction;
-use futures::future;
 use mev_share_sse::EventHistory;
 
+/// How hindsight simulates & prices arbs.
+#[derive(Clone, Debug)]
+pub struct HindsightConfig {
+    pub eth_price_source: EthPriceSource,
+    pub sim_backend: SimBackendKind,
+    /// Only used by the anvil backend.
+    pub anvil: AnvilConfig,
+}
+
+impl Default for HindsightConfig {
+    fn default() -> Self {
+        let config = Config::default();
+        Self {
+            eth_price_source: config.eth_price_source,
+            sim_backend: config.sim_backend,
+            anvil: AnvilConfig::from(&config),
+        }
+    }
+}
+
 /// Transaction processor for hindsight. Requires a websocket connection to an archive node.
 #[derive(Clone, Debug)]
 pub struct Hindsight {
     pub client: WsClient,
+    pub tokens: TokenCache,
+    pub eth_prices: EthPriceCache,
+    pub sim_backend: SimBackendRef,
 }
 
 impl Hindsight {
-    pub async fn new(ws_client: WsClient) -> Result<Self> {
-        Ok(Self { client: ws_client })
+    /// `token_db` is used to persist token metadata between runs; if None, metadata is only cached in memory.
+    pub async fn new(
+        ws_client: WsClient,
+        token_db: Option<TokenDatabase>,
+        config: &HindsightConfig,
+    ) -> Result<Self> {
+        Ok(Self {
+            sim_backend: crate::sim::backend::connect(
+                &ws_client,
+                config.sim_backend,
+                &config.anvil,
+            )
+            .await?,
+            client: ws_client,
+            tokens: TokenCache::new(token_db).await?,
+            eth_prices: EthPriceCache::new(config.eth_price_source),
+        })
     }
 
-    /// For each tx in `txs`, simulates an optimal backrun-arbitrage in a parallel thread,
-    /// caching results in batches of size `batch_size`.
+    /// Simulates an optimal backrun-arbitrage for each event in `events`, streaming them
+    /// through a `Pipeline` that runs at most `sim_concurrency` simulations at a time.
     ///
-    /// Saves results into `db` after each batch is processed. Returns when all txs are processed.
-    pub async fn process_orderflow(
+    /// Saves results into `db` and failed sims into `failure_db` as they finish.
+    /// A failed or panicked sim doesn't stop the others; it's recorded as a failure
+    /// and counted in the returned stats. Returns when all events are processed.
+    pub async fn process_events(
         self,
-        txs: &Vec<Transaction>,
-        batch_size: usize,
+        events: Vec<EventHistory>,
+        sim_concurrency: usize,
         db: Option<ArbDatabase>,
-        event_map: H256Map<EventHistory>,
-    ) -> Result<()> {
-        info!("loaded {} transactions total...", txs.len());
-        let mut processed_txs = 0;
-        while processed_txs < txs.len() {
-            let mut handlers = vec![];
-            let txs_batch = txs
-                .iter()
-                .skip(processed_txs)
-                .take(batch_size)
-                .map(|tx| tx.to_owned())
-                .collect::<Vec<Transaction>>();
-            processed_txs += txs_batch.len();
-            info!("processing {} txs", txs_batch.len());
-            for tx in txs_batch {
-                let event_map = event_map.clone();
-                let client = self.client.clone();
-                handlers.push(tokio::task::spawn(async move {
-                    simulate_backrun_arbs(&client, tx, &event_map).await.ok()
-                }));
-            }
-            let results = future::join_all(handlers).await;
-            let results = results
-                .into_iter()
-                .filter_map(|res| res.ok())
-                .flatten()
-                .collect::<Vec<_>>();
-            info!("batch results: {:#?}", results);
-            if let Some(db) = db.to_owned() {
-                if !results.is_empty() {
-                    db.to_owned().write_arbs(&results).await?;
-                }
-            }
-        }
-        Ok(())
+        failure_db: Option<FailureDatabase>,
+    ) -> Result<SimStats> {
+        info!("loaded {} events total...", events.len());
+        let num_events = events.len() as u64;
+        let pipeline = Pipeline::spawn(
+            self,
+            PipelineConfig::new(sim_concurrency),
+            db,
+            failure_db,
+            None,
+        );
+        pipeline.submit_page(events, num_events).await?;
+        pipeline.finish().await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use ethers::{providers::Middleware, types::H256};
+    use ethers::types::H256;
     use serde_json::json;
 
     use crate::{
         data::{
             arbs::ArbFilterParams,
             db::{Db, DbEngine},
-            MongoConfig,
+            MemoryConfig,
         },
         util::get_ws_client,
     };
@@ -84,9 +107,9 @@ mod tests {
     use super::*;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
-    async fn it_processes_orderflow() -> Result<()> {
+    async fn it_processes_events() -> Result<()> {
         let client = get_ws_client(None, 1).await?;
-        let hindsight = Hindsight::new(client).await?;
+        let hindsight = Hindsight::new(client, None, &HindsightConfig::default()).await?;
 
         // data from an actual juicy event
         let juicy_event: EventHistory = serde_json::from_value(json!({
@@ -125,26 +148,18 @@ mod tests {
         }))?;
         let juicy_tx_hash: H256 =
             "0xf00df02ad86f04a8b32d9f738394ee1b7ff791647f753923c60522363132f84a".parse::<H256>()?;
-        let juicy_tx = get_ws_client(None, 1)
-            .await?
-            .get_transaction(juicy_tx_hash)
-            .await?
-            .expect("failed to find juicy tx on chain");
-        let event_map = [juicy_event]
-            .iter()
-            .map(|event| (event.hint.hash, event.to_owned()))
-            .collect::<H256Map<EventHistory>>();
-        let test_db = Db::new(DbEngine::Mongo(MongoConfig::default())).await;
+        let test_db = Db::new(DbEngine::Memory(MemoryConfig::default())).await;
 
-        // run the sim, it will save a result to the "test" DB
-        hindsight
-            .process_orderflow(
-                vec![juicy_tx].as_ref(),
+        // run the sim, it will save a result to the in-memory DB
+        let stats = hindsight
+            .process_events(
+                vec![juicy_event],
                 1,
                 Some(test_db.connect.clone()),
-                event_map,
+                Some(test_db.failures.clone()),
             )
             .await?;
+        assert_eq!(stats.succeeded, 1);
 
         // check DB for result
         let arbs = test_db
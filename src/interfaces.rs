@@ -1,4 +1,5 @@
-use ethers::types::{Address, I256, U256};
+use crate::{error::FailureReason, Error};
+use ethers::types::{Address, H256, I256, U256};
 use mev_share_sse::EventHistory;
 use serde::{self, Deserialize, Serialize};
 
@@ -27,6 +28,12 @@ pub struct SimArbResultBatch {
     pub event: EventHistory,
     pub results: Vec<SimArbResult>,
     pub max_profit: U256,
+    /// `max_profit` in USD, using the price of ETH at the simulated block.
+    #[serde(default)]
+    pub max_profit_usd: Option<f64>,
+    /// Id of the scan run that produced this result.
+    #[serde(default)]
+    pub run_id: Option<String>,
 }
 
 /// Information derived from user's trade tx.
@@ -50,6 +57,22 @@ pub struct UserTradeParams {
 pub struct TokenPair {
     pub weth: Address,
     pub token: Address,
+    #[serde(default)]
+    pub weth_metadata: Option<TokenMetadata>,
+    #[serde(default)]
+    pub token_metadata: Option<TokenMetadata>,
+}
+
+/// ERC20 metadata, as reported by the token contract.
+///
+/// Fields are `None` if the token doesn't implement the respective method.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenMetadata {
+    pub address: Address,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub decimals: Option<u8>,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
@@ -67,18 +90,153 @@ pub struct StoredArbsRanges {
     pub latest_block: u64,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+/// Progress of a scan run. Saved after each page of events is fully processed,
+/// so that a scan can be resumed exactly where it left off.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanCheckpoint {
+    pub run_id: String,
+    /// Original params of the scan; the API offset is only meaningful w/ the same params.
+    pub block_start: u64,
+    pub block_end: Option<u64>,
+    pub timestamp_start: u64,
+    pub timestamp_end: Option<u64>,
+    /// API offset of the next page of events to fetch.
+    pub offset: u64,
+    /// True if the scan reached the end of its range.
+    pub done: bool,
+    /// Unix timestamp (seconds) of the last update.
+    pub updated_at: u64,
+}
+
+/// An event that has been fully processed (simulated or skipped) during a scan run.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessedEvent {
+    pub run_id: String,
+    pub hash: H256,
+    pub block: u64,
+    pub timestamp: u64,
+}
+
+/// An event that could not be simulated, and why.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimFailure {
+    /// Id of the scan run that tried to simulate the event.
+    pub run_id: Option<String>,
+    pub hash: H256,
+    pub block: u64,
+    pub timestamp: u64,
+    pub reason: FailureReason,
+    /// Full error message, including context.
+    pub message: String,
+}
+
+impl SimFailure {
+    pub fn new(run_id: Option<String>, event: &EventHistory, err: &Error) -> Self {
+        Self {
+            run_id,
+            hash: event.hint.hash,
+            block: event.block,
+            timestamp: event.timestamp,
+            reason: err.into(),
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+/// Outcome counts of simulating a set of txs.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct SimStats {
+    pub succeeded: usize,
+    /// Sims that returned an error (includes `panicked`).
+    pub failed: usize,
+    /// Sims whose task panicked.
+    pub panicked: usize,
+}
+
+impl SimStats {
+    pub fn record_failure(&mut self, failure: &SimFailure) {
+        self.failed += 1;
+        if failure.reason == FailureReason::Panic {
+            self.panicked += 1;
+        }
+    }
+}
+
+impl std::ops::AddAssign for SimStats {
+    fn add_assign(&mut self, other: Self) {
+        self.succeeded += other.succeeded;
+        self.failed += other.failed;
+        self.panicked += other.panicked;
+    }
+}
+
+/// A factory contract that hindsight looks up trading pools from.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolFactory {
+    pub name: String,
+    pub variant: PoolVariant,
+    pub address: Address,
+    /// Fee tier of the pools we look up (V3 only).
+    pub fee: Option<u32>,
+}
+
+/// Provenance of a scan run: everything needed to tell how its results were produced.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanRun {
+    pub run_id: String,
+    /// Unix timestamp (seconds) of when the run started.
+    pub started_at: u64,
+    /// Output of `git describe` for the source tree the binary was built from.
+    pub git_version: String,
+    pub hindsight_version: String,
+    pub cli_args: Vec<String>,
+    pub chain_id: u64,
+    /// RPC host(s), comma-separated (w/o credentials or path, which may contain API keys).
+    pub rpc_host: String,
+    pub eth_price_source: String,
+    /// EVM the sims ran on; None for runs from before it was configurable, which used revm.
+    #[serde(default)]
+    pub sim_backend: Option<String>,
+    pub max_depth: usize,
+    pub step_intervals: usize,
+    pub pool_registry: Vec<PoolFactory>,
+}
+
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Copy, PartialEq, strum::Display, strum::EnumString,
+)]
 pub enum PoolVariant {
     UniswapV2,
     UniswapV3,
 }
 
+/// `value` as 0x-prefixed hex, zero-padded to 64 digits. Unlike minimal hex, these sort
+/// as strings in the same order as the numbers they represent.
+pub fn to_padded_hex(value: U256) -> String {
+    // U256's hex formatting ignores width, so pad it separately
+    format!("0x{:0>64}", format!("{:x}", value))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use ethers::types::H256;
     use mev_share_sse::Hint;
     use rand::Rng;
+
+    #[test]
+    fn it_pads_hex() {
+        assert_eq!(to_padded_hex(0x1337.into()), format!("0x{:0>64}", "1337"));
+        assert!(to_padded_hex(0x100.into()) > to_padded_hex(0xff.into()));
+        // padded hex parses like minimal hex
+        let padded: U256 = serde_json::from_value(to_padded_hex(0x1337.into()).into()).unwrap();
+        assert_eq!(padded, 0x1337.into());
+    }
+
     impl SimArbResultBatch {
         pub fn test_example() -> Self {
             // get random u64
@@ -98,6 +256,8 @@ mod test {
                 },
                 results: vec![],
                 max_profit: 0x1337.into(),
+                max_profit_usd: None,
+                run_id: None,
             }
         }
     }
@@ -5,7 +5,11 @@ pub mod error;
 pub mod event_history;
 pub mod hindsight;
 pub mod interfaces;
+pub mod pipeline;
+pub mod rpc;
+pub mod shutdown;
 pub mod sim;
+pub mod tokens;
 pub mod util;
 
 pub use anyhow::{Error, Result};
@@ -1,36 +1,45 @@
 use ethers::types::U256;
 use hindsight::{
     commands::{self},
+    // debug,
     data::{
         arbs::{ArbFilterParams, WriteEngine},
         db::Db,
     },
-    // debug,
-    hindsight::Hindsight,
+    hindsight::{Hindsight, HindsightConfig},
     info,
+    shutdown::Shutdown,
     util::get_ws_client,
+    warn,
 };
 use mev_share_sse::EventClient;
 use revm::primitives::bitvec::macros::internal::funty::Fundamental;
-use std::thread::available_parallelism;
+use std::{thread::available_parallelism, time::Duration};
 mod cli;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, DbCommands};
+
+/// Simulate on half the CPU cores by default.
+fn default_batch_size() -> usize {
+    available_parallelism()
+        .map(|n| usize::from(n) / 2)
+        .unwrap_or(4)
+        .max(1)
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     let cli = Cli::parse_args();
 
-    ctrlc::set_handler(move || {
-        println!("\nstopping hindsight!");
-        std::process::exit(0);
-    })
-    .expect("Error setting Ctrl-C handler");
+    // first ctrl-c stops long-running commands gracefully, the second exits right away
+    let shutdown = Shutdown::new(Duration::from_secs(cli.shutdown_timeout));
+    shutdown
+        .install_signal_handler()
+        .expect("Error setting Ctrl-C handler");
 
     let max_reconnects = cli.ws_max_reconnects.unwrap_or_default();
     let ws_client = get_ws_client(None, max_reconnects).await?;
     let mevshare = EventClient::default();
-    let hindsight = Hindsight::new(ws_client.clone()).await?;
 
     match cli.command {
         Some(Commands::Scan {
@@ -41,54 +50,137 @@ async fn main() -> anyhow::Result<()> {
             timestamp_start,
             batch_size,
             db_engine,
+            resume,
+            poll_interval,
+            on_conflict,
         }) => {
-            /* If no start/end params are defined,
-                refine params based on ranges present in DB.
-                Overwriting old results may be accomplished by setting the start/end timestamp/block params.
-                Replace the provided timestamp/block params with the latest respective
-                value + 1 in the DB if it's higher than the param.
-                We add 1 to prevent duplicates. If an arb is saved in the DB,
-                then we know we've scanned & simulated up to that point.
-                Timestamp is evaluated by default, falls back to block.
-            */
-            let db_engine = db_engine.unwrap_or_default();
+            let db_engine = db_engine
+                .unwrap_or_default()
+                .with_conflict_policy(on_conflict);
             let db = Db::new(db_engine.to_owned()).await;
-            let (block_start, timestamp_start) =
-                if block_start.is_none() && timestamp_start.is_none() {
-                    let db_ranges = db.connect.get_previously_saved_ranges().await?;
-                    info!("previously saved event ranges: {:?}", db_ranges);
-                    let block_start = db_ranges.latest_block;
-                    let timestamp_start = db_ranges.latest_timestamp;
-                    (block_start as u32, timestamp_start as u32)
+            let hindsight = Hindsight::new(
+                ws_client.clone(),
+                Some(db.tokens.clone()),
+                &HindsightConfig::default(),
+            )
+            .await?;
+            let batch_size = batch_size.unwrap_or_else(default_batch_size);
+            info!("batch size: {}", batch_size);
+            let poll_interval = Duration::from_secs(poll_interval);
+
+            /* Resume from a saved checkpoint if one was requested with --resume,
+                or if no start params are defined and an unfinished run exists.
+            */
+            let checkpoint =
+                if let Some(run_id) = resume {
+                    Some(db.checkpoints.get_checkpoint(&run_id).await?.ok_or(
+                        anyhow::format_err!("no checkpoint found for run {}", run_id),
+                    )?)
+                } else if block_start.is_none() && timestamp_start.is_none() {
+                    db.checkpoints.get_latest_unfinished_checkpoint().await?
                 } else {
-                    if block_start.is_some() && timestamp_start.is_some() {
-                        panic!("cannot specify both block_start and timestamp_start");
-                    }
-                    // use whichever is specified; the other (being 1) will not alter the selection
-                    (block_start.unwrap_or(1), timestamp_start.unwrap_or(1))
+                    None
                 };
 
-            let batch_size = batch_size.unwrap_or(
-                available_parallelism()
-                    .map(|n| usize::from(n) / 2)
-                    .unwrap_or(4)
-                    .max(1),
-            );
-            info!("batch size: {}", batch_size);
-            let scan_options = commands::scan::ScanOptions {
-                block_start,
-                block_end,
-                timestamp_start,
-                timestamp_end,
-                batch_size,
-                db_engine,
+            let scan_options = if let Some(checkpoint) = checkpoint {
+                info!("resuming scan from checkpoint: {:?}", checkpoint);
+                commands::scan::ScanOptions::resume(
+                    checkpoint,
+                    batch_size,
+                    db_engine,
+                    poll_interval,
+                )
+            } else {
+                /* If no start/end params are defined,
+                    start from the latest event saved in the DB; if an arb is saved,
+                    we know we've scanned & simulated up to that point.
+                    This is a new run, so the events at the boundary are saved again under
+                    its run id, alongside the previous run's arbs (--on-conflict only applies
+                    to arbs of the same event & run); filter by run id to tell them apart.
+                    Timestamp is evaluated by default, falls back to block.
+                */
+                let (block_start, timestamp_start) =
+                    if block_start.is_none() && timestamp_start.is_none() {
+                        let db_ranges = db.connect.get_previously_saved_ranges().await?;
+                        info!("previously saved event ranges: {:?}", db_ranges);
+                        let block_start = db_ranges.latest_block;
+                        let timestamp_start = db_ranges.latest_timestamp;
+                        (block_start as u32, timestamp_start as u32)
+                    } else {
+                        if block_start.is_some() && timestamp_start.is_some() {
+                            panic!("cannot specify both block_start and timestamp_start");
+                        }
+                        // use whichever is specified; the other (being 1) will not alter the selection
+                        (block_start.unwrap_or(1), timestamp_start.unwrap_or(1))
+                    };
+                commands::scan::ScanOptions {
+                    block_start,
+                    block_end,
+                    timestamp_start,
+                    timestamp_end,
+                    batch_size,
+                    db_engine,
+                    run_id: commands::scan::new_run_id(),
+                    offset: 0,
+                    poll_interval,
+                }
             };
+            info!("scan run id: {}", scan_options.run_id);
+            let run = commands::scan::new_scan_run(
+                &scan_options.run_id,
+                &ws_client,
+                hindsight.eth_prices.source(),
+                hindsight.sim_backend.kind(),
+            )
+            .await?;
+            if let Some(prev_run) = db.runs.get_run(&run.run_id).await? {
+                // keep the original record; results from before the resume were produced by it
+                if prev_run.git_version != run.git_version {
+                    warn!(
+                        "resuming run {} with a different version: {} -> {}",
+                        run.run_id, prev_run.git_version, run.git_version
+                    );
+                }
+            } else {
+                db.runs.save_run(&run).await?;
+            }
             commands::scan::run(
                 scan_options.to_owned(),
-                &ws_client,
                 &mevshare,
                 &hindsight,
                 &db.connect,
+                &db.failures,
+                &db.checkpoints,
+                &shutdown,
+            )
+            .await?;
+        }
+        Some(Commands::Live {
+            batch_size,
+            db_engine,
+            on_conflict,
+        }) => {
+            let db = Db::new(
+                db_engine
+                    .unwrap_or_default()
+                    .with_conflict_policy(on_conflict),
+            )
+            .await;
+            let hindsight = Hindsight::new(
+                ws_client.clone(),
+                Some(db.tokens.clone()),
+                &HindsightConfig::default(),
+            )
+            .await?;
+            let batch_size = batch_size.unwrap_or_else(default_batch_size);
+            info!("batch size: {}", batch_size);
+            commands::live::run(
+                batch_size,
+                &mevshare,
+                &hindsight,
+                &db.connect,
+                &db.failures,
+                &shutdown,
             )
             .await?;
         }
@@ -100,8 +192,10 @@ async fn main() -> anyhow::Result<()> {
             timestamp_end,
             timestamp_start,
             min_profit,
+            run_id,
             read_db,
             write_db,
+            on_conflict,
         }) => {
             let min_profit = min_profit.unwrap_or(0f64);
             if min_profit < 0f64 {
@@ -118,7 +212,7 @@ async fn main() -> anyhow::Result<()> {
             let write_dest = if filename.is_some() {
                 WriteEngine::File(filename)
             } else if let Some(write_db) = write_db {
-                WriteEngine::Db(write_db)
+                WriteEngine::Db(write_db.with_conflict_policy(on_conflict))
             } else {
                 WriteEngine::File(None)
             };
@@ -130,12 +224,70 @@ async fn main() -> anyhow::Result<()> {
                     timestamp_end,
                     timestamp_start,
                     min_profit: Some(umin_profit),
+                    run_id,
                 },
                 &read_db,
                 write_dest,
+                &shutdown,
             )
             .await?;
         }
+        Some(Commands::Stats {
+            // cli args:
+            block_end,
+            block_start,
+            timestamp_end,
+            timestamp_start,
+            run_id,
+            read_db,
+        }) => {
+            let read_db = Db::new(read_db.unwrap_or_default()).await.connect;
+            commands::stats::run(
+                ArbFilterParams {
+                    block_end,
+                    block_start,
+                    timestamp_end,
+                    timestamp_start,
+                    min_profit: None,
+                    run_id,
+                },
+                &read_db,
+            )
+            .await?;
+        }
+        Some(Commands::Failures {
+            // cli args:
+            block_end,
+            block_start,
+            timestamp_end,
+            timestamp_start,
+            run_id,
+            read_db,
+        }) => {
+            let read_db = Db::new(read_db.unwrap_or_default()).await.failures;
+            commands::failures::run(
+                ArbFilterParams {
+                    block_end,
+                    block_start,
+                    timestamp_end,
+                    timestamp_start,
+                    min_profit: None,
+                    run_id,
+                },
+                &read_db,
+            )
+            .await?;
+        }
+        Some(Commands::Runs { run_id, read_db }) => {
+            let db = Db::new(read_db.unwrap_or_default()).await;
+            commands::runs::run(run_id, &db.runs).await?;
+        }
+        Some(Commands::Db {
+            command: DbCommands::Migrate { db_engine },
+        }) => {
+            let db = Db::new(db_engine.unwrap_or_default()).await;
+            commands::db::migrate(&db.schema).await?;
+        }
         None => {
             let program = std::env::args().next().unwrap_or("hindsight".to_owned());
             println!("for usage, run: {} --help", program);
@@ -0,0 +1,465 @@
+use crate::{
+    data::{arbs::ArbDatabase, checkpoints::ScanProgress, failures::FailureDatabase},
+    error::HindsightError,
+    hindsight::Hindsight,
+    info,
+    interfaces::{SimArbResultBatch, SimFailure, SimStats},
+    rpc,
+    shutdown::Shutdown,
+    sim::processor::{prepare_sim, run_sim, SimJob},
+    util::WsClient,
+    warn, Error, Result,
+};
+use ethers::{providers::Middleware, types::Transaction};
+use futures::Future;
+use mev_share_sse::EventHistory;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc, Mutex, Semaphore},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Max time results wait in the writer before they're saved.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Concurrency limits & buffer sizes of the simulation pipeline.
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineConfig {
+    /// Max concurrent `eth_getTransactionByHash` requests.
+    pub fetch_concurrency: usize,
+    /// Max concurrent trade-param derivations.
+    pub derive_concurrency: usize,
+    /// Max concurrent simulations.
+    pub sim_concurrency: usize,
+    /// Capacity of the channel feeding each stage.
+    pub channel_size: usize,
+    /// Results are saved to the DB once this many are waiting (or every `FLUSH_INTERVAL`).
+    pub write_batch_size: usize,
+}
+
+impl PipelineConfig {
+    /// Sizes every stage relative to the number of concurrent simulations.
+    /// Fetching & deriving are mostly waiting on the RPC node, so they get more slots.
+    pub fn new(sim_concurrency: usize) -> Self {
+        let sim_concurrency = sim_concurrency.max(1);
+        Self {
+            fetch_concurrency: sim_concurrency * 4,
+            derive_concurrency: sim_concurrency * 2,
+            sim_concurrency,
+            channel_size: sim_concurrency * 4,
+            write_batch_size: sim_concurrency * 4,
+        }
+    }
+}
+
+/// An event moving through the pipeline, along with the output of the last stage it passed.
+#[derive(Debug)]
+struct Job<T> {
+    /// Page of events this one was submitted with.
+    page: u64,
+    event: EventHistory,
+    data: T,
+}
+
+/// Final outcome of a job, consumed by the writer.
+type Outcome = Job<Result<SimArbResultBatch>>;
+
+#[derive(Debug)]
+struct PendingPage {
+    /// API offset of the page after this one.
+    next_offset: u64,
+    /// Events of this page that haven't been saved yet.
+    remaining: usize,
+}
+
+/// Tracks which pages of events have been completely processed, so that the
+/// checkpoint offset never skips past an event that's still in flight.
+#[derive(Debug, Default)]
+pub struct PageTracker {
+    pages: BTreeMap<u64, PendingPage>,
+    next_page: u64,
+}
+
+impl PageTracker {
+    /// Registers a page of `num_events` events. Returns the page's id.
+    pub fn add_page(&mut self, next_offset: u64, num_events: usize) -> u64 {
+        let page = self.next_page;
+        self.next_page += 1;
+        self.pages.insert(
+            page,
+            PendingPage {
+                next_offset,
+                remaining: num_events,
+            },
+        );
+        page
+    }
+
+    /// Marks one event of `page` as saved.
+    pub fn complete(&mut self, page: u64) {
+        if let Some(pending) = self.pages.get_mut(&page) {
+            pending.remaining = pending.remaining.saturating_sub(1);
+        }
+    }
+
+    /// Removes leading pages that are done. Returns the offset to resume from, if any page was removed.
+    pub fn pop_done(&mut self) -> Option<u64> {
+        let mut offset = None;
+        while let Some(entry) = self.pages.first_entry() {
+            if entry.get().remaining > 0 {
+                break;
+            }
+            offset = Some(entry.remove().next_offset);
+        }
+        offset
+    }
+}
+
+/// Streams events through tx fetch → trade-param derivation → simulation → DB writer.
+///
+/// Stages are connected by bounded channels, and each stage runs at most a fixed
+/// number of jobs at a time. A slow job only holds up its own slot; when a stage is
+/// saturated, the stages feeding it (and eventually `submit_page`) wait.
+pub struct Pipeline {
+    events: mpsc::Sender<Job<()>>,
+    pages: Arc<Mutex<PageTracker>>,
+    stages: Vec<JoinHandle<Result<()>>>,
+    writer: JoinHandle<Result<SimStats>>,
+    /// Cancelled to abandon jobs in flight.
+    abort: CancellationToken,
+}
+
+impl Pipeline {
+    /// Spawns all stages. Results are saved into `db` and failures into `failure_db`.
+    /// If `progress` is given, results are tagged with its run id, and its
+    /// checkpoint offset is advanced as pages are completed.
+    pub fn spawn(
+        hindsight: Hindsight,
+        config: PipelineConfig,
+        db: Option<ArbDatabase>,
+        failure_db: Option<FailureDatabase>,
+        progress: Option<ScanProgress>,
+    ) -> Self {
+        let (events_tx, events_rx) = mpsc::channel(config.channel_size);
+        let (txs_tx, txs_rx) = mpsc::channel(config.channel_size);
+        let (jobs_tx, jobs_rx) = mpsc::channel(config.channel_size);
+        let (outcomes_tx, outcomes_rx) = mpsc::channel(config.channel_size);
+        let pages = Arc::new(Mutex::new(PageTracker::default()));
+        let abort = CancellationToken::new();
+
+        let client = hindsight.client.clone();
+        let fetch = spawn_stage(
+            events_rx,
+            txs_tx,
+            outcomes_tx.clone(),
+            config.fetch_concurrency,
+            abort.clone(),
+            move |event, _| {
+                let client = client.clone();
+                async move { fetch_tx(&client, &event).await }
+            },
+        );
+        let client = hindsight.client.clone();
+        let tokens = hindsight.tokens.clone();
+        let derive = spawn_stage(
+            txs_rx,
+            jobs_tx,
+            outcomes_tx.clone(),
+            config.derive_concurrency,
+            abort.clone(),
+            move |event, tx| {
+                let client = client.clone();
+                let tokens = tokens.clone();
+                async move { prepare_sim(&client, &tokens, tx, &event).await }
+            },
+        );
+        let client = hindsight.client.clone();
+        let backend = hindsight.sim_backend.clone();
+        let eth_prices = hindsight.eth_prices.clone();
+        let sim = spawn_stage(
+            jobs_rx,
+            outcomes_tx.clone(),
+            outcomes_tx,
+            config.sim_concurrency,
+            abort.clone(),
+            move |_, job: SimJob| {
+                let client = client.clone();
+                let backend = backend.clone();
+                let eth_prices = eth_prices.clone();
+                // sim errors are outcomes too; they're recorded by the writer
+                async move { Ok(run_sim(&client, &backend, &eth_prices, job).await) }
+            },
+        );
+        let writer_done = abort.clone();
+        let writer = tokio::spawn(write_outcomes(
+            hindsight.client.clone(),
+            outcomes_rx,
+            pages.clone(),
+            db,
+            failure_db,
+            progress,
+            config.write_batch_size,
+        ));
+        let writer = tokio::spawn(async move {
+            let res = writer.await;
+            // if the writer failed, nothing will save what the stages are working on, so stop them
+            writer_done.cancel();
+            res?
+        });
+
+        Self {
+            events: events_tx,
+            pages,
+            stages: vec![fetch, derive, sim],
+            writer,
+            abort,
+        }
+    }
+
+    /// Feeds a page of events into the pipeline. `next_offset` is the API offset of the
+    /// page after this one; it's saved to the checkpoint once every event of this page
+    /// (and every page before it) has been saved.
+    ///
+    /// Waits while the pipeline is full.
+    pub async fn submit_page(&self, events: Vec<EventHistory>, next_offset: u64) -> Result<()> {
+        if self.writer.is_finished() {
+            // the writer only stops early if it failed to save results; `finish` returns the error
+            return Err(anyhow::format_err!("simulation pipeline stopped"));
+        }
+        let page = self.pages.lock().await.add_page(next_offset, events.len());
+        for event in events {
+            self.events
+                .send(Job {
+                    page,
+                    event,
+                    data: (),
+                })
+                .await
+                .map_err(|_| anyhow::format_err!("simulation pipeline stopped"))?;
+        }
+        Ok(())
+    }
+
+    /// Waits for every submitted event to be processed & saved, then shuts down the pipeline.
+    pub async fn finish(self) -> Result<SimStats> {
+        // closing the first channel shuts down each stage in turn once it's drained
+        drop(self.events);
+        for stage in self.stages {
+            stage.await??;
+        }
+        self.writer.await?
+    }
+
+    /// Like `finish`, but once `shutdown` is requested, jobs still in flight after its
+    /// timeout are abandoned. Abandoned events aren't saved or marked as processed, and
+    /// their pages aren't checkpointed, so a resumed scan picks them up again.
+    pub async fn finish_or_abandon(self, shutdown: &Shutdown) -> Result<SimStats> {
+        let abort = self.abort.clone();
+        let expired = shutdown.clone();
+        let timer = tokio::spawn(async move {
+            expired.expired().await;
+            abort.cancel();
+        });
+        let res = self.finish().await;
+        timer.abort();
+        res
+    }
+}
+
+/// Runs `work` on each job from `jobs`, with at most `concurrency` jobs in flight.
+/// Successful jobs are passed to `next`; failed or panicked jobs go straight to `outcomes`.
+/// Once `abort` is cancelled, jobs in flight are aborted & new jobs are dropped.
+fn spawn_stage<I, O, F, Fut>(
+    mut jobs: mpsc::Receiver<Job<I>>,
+    next: mpsc::Sender<Job<O>>,
+    outcomes: mpsc::Sender<Outcome>,
+    concurrency: usize,
+    abort: CancellationToken,
+    work: F,
+) -> JoinHandle<Result<()>>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    F: Fn(EventHistory, I) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<O>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let permits = Arc::new(Semaphore::new(concurrency));
+        while let Some(job) = jobs.recv().await {
+            if abort.is_cancelled() {
+                continue;
+            }
+            // the permit is held until the result is handed off, so a full `next` stalls this stage
+            let permit = permits.clone().acquire_owned().await?;
+            let mut task = tokio::spawn(work(job.event.clone(), job.data));
+            let next = next.clone();
+            let outcomes = outcomes.clone();
+            let abort = abort.clone();
+            let (page, event) = (job.page, job.event);
+            tokio::spawn(async move {
+                let res = tokio::select! {
+                    res = &mut task => res,
+                    _ = abort.cancelled() => {
+                        task.abort();
+                        return;
+                    }
+                };
+                let res = res
+                    .map_err(|err| Error::from(HindsightError::from_join_error(err)))
+                    .and_then(|res| res);
+                // sends only fail if the writer stopped, in which case `finish` reports its error
+                match res {
+                    Ok(data) => {
+                        let _ = next.send(Job { page, event, data }).await;
+                    }
+                    Err(err) => {
+                        let _ = outcomes
+                            .send(Job {
+                                page,
+                                event,
+                                data: Err(err),
+                            })
+                            .await;
+                    }
+                }
+                drop(permit);
+            });
+        }
+        // wait for jobs in flight before dropping `next`, which closes the next stage
+        let _ = permits.acquire_many(concurrency as u32).await?;
+        Ok(())
+    })
+}
+
+async fn fetch_tx(client: &WsClient, event: &EventHistory) -> Result<Transaction> {
+    let tx = client
+        .get_transaction(event.hint.hash)
+        .await
+        .map_err(HindsightError::from)?;
+    Ok(tx.ok_or(HindsightError::TxNotLanded(event.hint.hash))?)
+}
+
+/// Saves outcomes in batches, then advances the checkpoint past completed pages.
+async fn write_outcomes(
+    client: WsClient,
+    mut outcomes: mpsc::Receiver<Outcome>,
+    pages: Arc<Mutex<PageTracker>>,
+    db: Option<ArbDatabase>,
+    failure_db: Option<FailureDatabase>,
+    mut progress: Option<ScanProgress>,
+    batch_size: usize,
+) -> Result<SimStats> {
+    let run_id = progress
+        .as_ref()
+        .map(|progress| progress.run_id().to_owned());
+    let mut stats = SimStats::default();
+    let mut buffer = vec![];
+    loop {
+        let closed = match tokio::time::timeout(FLUSH_INTERVAL, outcomes.recv()).await {
+            Ok(Some(outcome)) => {
+                buffer.push(outcome);
+                if buffer.len() < batch_size {
+                    continue;
+                }
+                false
+            }
+            Ok(None) => true,
+            // nothing new for a while; save what we have
+            Err(_) => false,
+        };
+
+        let mut results = vec![];
+        let mut failures = vec![];
+        let mut events = vec![];
+        // events that won't be simulated again if their page is fetched again (e.g. on resume)
+        let mut processed = vec![];
+        let mut batch_stats = SimStats::default();
+        for outcome in buffer.drain(..) {
+            match outcome.data {
+                Ok(mut result) => {
+                    result.run_id = run_id.to_owned();
+                    results.push(result);
+                    batch_stats.succeeded += 1;
+                    processed.push(outcome.event.to_owned());
+                }
+                Err(err) => {
+                    warn!("sim failed for tx {:?}: {:#}", outcome.event.hint.hash, err);
+                    let failure = SimFailure::new(run_id.to_owned(), &outcome.event, &err);
+                    batch_stats.record_failure(&failure);
+                    // e.g. RPC errors; the event is retried if its page is scanned again
+                    if failure.reason.is_terminal() {
+                        processed.push(outcome.event.to_owned());
+                    }
+                    failures.push(failure);
+                }
+            }
+            events.push((outcome.page, outcome.event));
+        }
+        if let Some(db) = &db {
+            if !results.is_empty() {
+                db.write_arbs(&results).await?;
+            }
+        }
+        if let Some(failure_db) = &failure_db {
+            if !failures.is_empty() {
+                failure_db.write_failures(&failures).await?;
+            }
+        }
+        if let Some(progress) = &progress {
+            progress.mark_processed(&processed).await?;
+        }
+        if !events.is_empty() {
+            info!("batch stats: {:?}", batch_stats);
+            info!(
+                "rpc calls: {}",
+                rpc::transport(&client).counters().take_report()
+            );
+            stats += batch_stats;
+        }
+
+        // only now that the events are saved is it safe to move the checkpoint past them
+        let offset = {
+            let mut pages = pages.lock().await;
+            for (page, _) in &events {
+                pages.complete(*page);
+            }
+            pages.pop_done()
+        };
+        if let (Some(progress), Some(offset)) = (&mut progress, offset) {
+            info!("pages done up to offset {}", offset);
+            progress.save_offset(offset).await?;
+        }
+
+        if closed {
+            break;
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_tracks_pages_in_order() {
+        let mut tracker = PageTracker::default();
+        let first = tracker.add_page(500, 2);
+        let second = tracker.add_page(1000, 1);
+        let empty = tracker.add_page(1500, 0);
+
+        // later pages finishing first don't move the offset
+        tracker.complete(second);
+        assert_eq!(tracker.pop_done(), None);
+        tracker.complete(first);
+        assert_eq!(tracker.pop_done(), None);
+        tracker.complete(first);
+        assert_eq!(tracker.pop_done(), Some(1500));
+        assert!(tracker.pages.is_empty());
+
+        let last = tracker.add_page(2000, 1);
+        assert!(last > empty);
+        assert_eq!(tracker.pop_done(), None);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{debug, util::WsClient, warn};
+use ethers::providers::Middleware;
+use ethers::types::{Block, H256};
+use futures::{stream::BoxStream, StreamExt};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Time between `eth_blockNumber` polls while there's no subscription.
+const BLOCK_NUMBER_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Why `NewBlocks::wait` returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wake {
+    /// A new block arrived.
+    NewBlock(u64),
+    /// No block arrived within the poll interval.
+    Timeout,
+    /// Shutdown was requested.
+    Shutdown,
+}
+
+/// Waits for new blocks from a `newHeads` subscription. When subscriptions aren't available
+/// (e.g. w/ HTTP-only RPC urls) or the subscription drops, polls `eth_blockNumber` instead.
+/// Either way, gives up after `poll_interval`.
+pub struct NewBlocks<'a> {
+    client: &'a WsClient,
+    blocks: Option<BoxStream<'a, Block<H256>>>,
+    /// False if the node refused the first subscription; there's no point asking again.
+    can_subscribe: bool,
+    poll_interval: Duration,
+    /// Latest block number seen, if any.
+    latest: Option<u64>,
+}
+
+impl<'a> NewBlocks<'a> {
+    pub async fn subscribe(client: &'a WsClient, poll_interval: Duration) -> NewBlocks<'a> {
+        let mut new_blocks = Self {
+            client,
+            blocks: None,
+            can_subscribe: true,
+            poll_interval,
+            latest: None,
+        };
+        new_blocks.resubscribe().await;
+        if new_blocks.blocks.is_none() {
+            new_blocks.can_subscribe = false;
+        }
+        new_blocks
+    }
+
+    async fn resubscribe(&mut self) {
+        match self.client.subscribe_blocks().await {
+            Ok(blocks) => self.blocks = Some(blocks.boxed()),
+            Err(err) => {
+                warn!(
+                    "failed to subscribe to new blocks, polling every {:?} instead: {:?}",
+                    BLOCK_NUMBER_POLL_INTERVAL, err
+                );
+                self.blocks = None;
+            }
+        }
+    }
+
+    /// Waits for the next block, `poll_interval` at most, or until `shutdown` is cancelled.
+    pub async fn wait(&mut self, shutdown: &CancellationToken) -> Wake {
+        if self.blocks.is_none() && self.can_subscribe {
+            self.resubscribe().await;
+        }
+        let deadline = Instant::now() + self.poll_interval;
+        let number = tokio::select! {
+            _ = shutdown.cancelled() => return Wake::Shutdown,
+            number = tokio::time::timeout_at(deadline, self.next_block()) => number,
+        };
+        match number {
+            Ok(number) => {
+                debug!("new block {}", number);
+                self.latest = Some(number);
+                Wake::NewBlock(number)
+            }
+            Err(_) => Wake::Timeout,
+        }
+    }
+
+    /// Number of the next block, from the subscription if there is one, otherwise by polling.
+    async fn next_block(&mut self) -> u64 {
+        if let Some(blocks) = self.blocks.as_mut() {
+            match blocks.next().await {
+                Some(block) => return block.number.unwrap_or_default().as_u64(),
+                None => {
+                    // resubscribed on the next wait
+                    warn!("new block subscription ended, polling until it's back");
+                    self.blocks = None;
+                }
+            }
+        }
+        loop {
+            match self.client.get_block_number().await {
+                Ok(number) if self.latest.map_or(false, |latest| number.as_u64() > latest) => {
+                    return number.as_u64()
+                }
+                // the first poll only tells us where we are
+                Ok(number) => self.latest = self.latest.or(Some(number.as_u64())),
+                Err(err) => debug!("failed to poll the latest block: {:?}", err),
+            }
+            tokio::time::sleep(BLOCK_NUMBER_POLL_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use super::{endpoints::EndpointPool, ThrottledTransport};
+use crate::{debug, warn, Result};
+use ethers::providers::{JsonRpcClient, Provider, RpcError, Ws};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, Weak};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_tungstenite::tungstenite::{handshake::server, http::StatusCode, Message};
+
+/// JSON-RPC "internal error", for requests that failed w/o an error response from the node.
+const INTERNAL_ERROR: i64 = -32603;
+/// JSON-RPC "parse error".
+const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC "method not found".
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Methods the forked DB & Anvil fork state with. Requests are sent w/ our endpoints' API keys,
+/// so anything else (sending txs, node admin, ...) is refused.
+const ALLOWED_METHODS: &[&str] = &[
+    "eth_blockNumber",
+    "eth_chainId",
+    "net_version",
+    "eth_gasPrice",
+    "eth_feeHistory",
+    "eth_getBalance",
+    "eth_getCode",
+    "eth_getStorageAt",
+    "eth_getTransactionCount",
+    "eth_getBlockByNumber",
+    "eth_getBlockByHash",
+    "eth_getTransactionByHash",
+    "eth_getTransactionReceipt",
+    "eth_getLogs",
+    // rusty-sando diffs the fork block's state w/ it
+    "trace_callMany",
+];
+
+type Client = Provider<ThrottledTransport<EndpointPool>>;
+
+/// A local websocket JSON-RPC server that forwards every request through our transport,
+/// so that code which only takes a plain `Provider<Ws>` (rusty-sando's forked DB) or an
+/// RPC url (a spawned Anvil node) is still rate-limited, retried, counted & failed over,
+/// whichever kind of endpoints are configured.
+///
+/// Only connections to `url`'s path, a random token made per process, are accepted, & only
+/// read methods (`ALLOWED_METHODS`) are forwarded.
+#[derive(Debug)]
+pub struct ForkProxy {
+    /// `ws://127.0.0.1:<port>/<token>`. Anyone w/ it can read state through our endpoints
+    /// (& spend their rate limit), so don't log it or pass it beyond the nodes we spawn.
+    pub url: String,
+    /// Connected to `url`.
+    pub provider: Arc<Provider<Ws>>,
+}
+
+impl ForkProxy {
+    /// Starts serving on a free local port. The server stops accepting requests once `client` is dropped.
+    pub async fn start(client: Weak<Client>) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let path: Arc<str> = format!("/{:032x}", rand::random::<u128>()).into();
+        let url = format!("ws://{}{}", addr, path);
+        tokio::spawn(serve(listener, client, path));
+        let provider = Arc::new(Provider::new(Ws::connect(url.as_str()).await?));
+        debug!("forwarding forked state requests through {}", addr);
+        Ok(Self { url, provider })
+    }
+}
+
+async fn serve(listener: TcpListener, client: Weak<Client>, path: Arc<str>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                warn!("fork proxy failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        if client.strong_count() == 0 {
+            return;
+        }
+        let client = client.clone();
+        let path = path.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, client, &path).await {
+                debug!("fork proxy connection closed: {}", err);
+            }
+        });
+    }
+}
+
+/// Answers requests from one connection concurrently, in whatever order they finish.
+/// Refuses the handshake unless it's for `path`.
+async fn handle_connection(stream: TcpStream, client: Weak<Client>, path: &str) -> Result<()> {
+    let check_path = |req: &server::Request, res: server::Response| {
+        if req.uri().path() == path {
+            Ok(res)
+        } else {
+            let mut err = server::ErrorResponse::new(None);
+            *err.status_mut() = StatusCode::NOT_FOUND;
+            Err(err)
+        }
+    };
+    let (mut sink, mut requests) = tokio_tungstenite::accept_hdr_async(stream, check_path)
+        .await?
+        .split();
+    let (responses, mut to_send) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        while let Some(response) = to_send.recv().await {
+            if sink.send(Message::Text(response)).await.is_err() {
+                return;
+            }
+        }
+    });
+    while let Some(message) = requests.next().await {
+        let request = match message? {
+            Message::Text(request) => request,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let Some(client) = client.upgrade() else {
+            break;
+        };
+        let responses = responses.clone();
+        tokio::spawn(async move {
+            // the receiver is only gone if the connection is, & then nobody's waiting for this
+            let _ = responses.send(forward(&client, &request).await.to_string());
+        });
+    }
+    drop(responses);
+    writer.await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct Request {
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Sends `request` through `client`'s transport; returns the JSON-RPC response.
+/// Methods outside `ALLOWED_METHODS` aren't sent.
+async fn forward(client: &Client, request: &str) -> Value {
+    let transport: &ThrottledTransport<EndpointPool> = client.as_ref();
+    let request: Request = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(err) => return error_response(Value::Null, PARSE_ERROR, err.to_string(), None),
+    };
+    if !ALLOWED_METHODS.contains(&request.method.as_str()) {
+        return error_response(
+            request.id,
+            METHOD_NOT_FOUND,
+            format!("{} isn't forwarded by the fork proxy", request.method),
+            None,
+        );
+    }
+    let res = if request.params.is_null() {
+        transport.request::<_, Value>(&request.method, ()).await
+    } else {
+        transport
+            .request::<_, Value>(&request.method, &request.params)
+            .await
+    };
+    match res {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": request.id, "result": result }),
+        // pass the node's error through as-is, so callers can still decode reverts
+        Err(err) => match err.as_error_response() {
+            Some(res) => error_response(
+                request.id,
+                res.code,
+                res.message.to_owned(),
+                res.data.to_owned(),
+            ),
+            None => error_response(request.id, INTERNAL_ERROR, err.to_string(), None),
+        },
+    }
+}
+
+fn error_response(id: Value, code: i64, message: String, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::{transport, MethodCounts, RpcLimits};
+    use ethers::providers::Middleware;
+    use std::time::Duration;
+
+    /// Nothing listens on port 1, so every request fails after going through the transport.
+    async fn client() -> Result<Arc<Client>> {
+        let endpoints = EndpointPool::connect(&["http://localhost:1".to_owned()], 1).await?;
+        Ok(Arc::new(Provider::new(ThrottledTransport::new(
+            endpoints,
+            RpcLimits {
+                requests_per_second: 0.0,
+                burst: 1.0,
+                max_retries: 1,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+            },
+        ))))
+    }
+
+    #[tokio::test]
+    async fn it_forwards_requests_through_the_transport() -> Result<()> {
+        let client = client().await?;
+        let proxy = ForkProxy::start(Arc::downgrade(&client)).await?;
+        assert!(proxy.url.starts_with("ws://127.0.0.1:"));
+        assert!(proxy.provider.get_block_number().await.is_err());
+        assert_eq!(
+            transport(&client).counters().take().get("eth_blockNumber"),
+            Some(&MethodCounts {
+                calls: 2,
+                retries: 1,
+                errors: 1
+            })
+        );
+        Ok(())
+    }
+    #[tokio::test]
+    async fn it_rejects_connections_without_the_token() -> Result<()> {
+        let client = client().await?;
+        let proxy = ForkProxy::start(Arc::downgrade(&client)).await?;
+        let (addr, token) = proxy.url.rsplit_once('/').expect("no token in proxy url");
+        assert_eq!(token.len(), 32);
+        assert!(Ws::connect(addr).await.is_err());
+        assert!(Ws::connect(format!("{}/{}", addr, "0".repeat(32)))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_only_forwards_read_methods() -> Result<()> {
+        let client = client().await?;
+        let res = forward(
+            &client,
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_sendRawTransaction","params":["0x00"]}"#,
+        )
+        .await;
+        assert_eq!(res["id"], 1);
+        assert_eq!(res["error"]["code"], METHOD_NOT_FOUND);
+        // refused before reaching the transport
+        assert!(transport(&client).counters().take().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+pub mod blocks;
+pub mod endpoints;
+pub mod fork_proxy;
+
+use crate::{config::Config, debug, util::WsClient, Result};
+use async_trait::async_trait;
+use endpoints::EndpointPool;
+use ethers::{
+    providers::{JsonRpcClient, PubsubClient, RpcError},
+    types::U256,
+};
+use fork_proxy::ForkProxy;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, Mutex as StdMutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// JSON-RPC error codes that providers use to say "slow down".
+const RATE_LIMIT_CODES: [i64; 2] = [429, -32005];
+/// Error messages that mean a request may succeed if it's sent again.
+const TRANSIENT_MESSAGES: [&str; 4] = ["rate limit", "too many requests", "timeout", "timed out"];
+
+/// Limits applied to every request sent to the RPC node.
+#[derive(Clone, Copy, Debug)]
+pub struct RpcLimits {
+    /// Sustained requests per second. Zero or less disables the rate limit.
+    pub requests_per_second: f64,
+    /// Max requests sent at once after the client has been idle.
+    pub burst: f64,
+    /// Times a request is retried after a transient error.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles after each attempt (plus jitter).
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl From<&Config> for RpcLimits {
+    fn from(config: &Config) -> Self {
+        Self {
+            requests_per_second: config.rpc_requests_per_second,
+            burst: config.rpc_requests_per_second.max(1.0),
+            max_retries: config.rpc_max_retries,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Token bucket; refills at `rate` tokens per second, up to `burst` tokens.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    /// (tokens left, time of last refill)
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            bucket: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    /// Waits until a request may be sent.
+    pub async fn acquire(&self) {
+        if self.rate <= 0.0 {
+            return;
+        }
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                let (tokens, last) = *bucket;
+                let tokens =
+                    (tokens + now.duration_since(last).as_secs_f64() * self.rate).min(self.burst);
+                if tokens >= 1.0 {
+                    *bucket = (tokens - 1.0, now);
+                    return;
+                }
+                *bucket = (tokens, now);
+                Duration::from_secs_f64((1.0 - tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Request counts for a single RPC method.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MethodCounts {
+    /// Requests sent, including retries.
+    pub calls: u64,
+    pub retries: u64,
+    /// Requests that failed after all retries (or with a non-transient error).
+    pub errors: u64,
+}
+
+impl std::fmt::Display for MethodCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} calls ({} retries, {} errors)",
+            self.calls, self.retries, self.errors
+        )
+    }
+}
+
+/// Per-method request counters.
+#[derive(Debug, Default)]
+pub struct RpcCounters {
+    methods: StdMutex<BTreeMap<String, MethodCounts>>,
+}
+
+impl RpcCounters {
+    fn record(&self, method: &str, update: impl FnOnce(&mut MethodCounts)) {
+        // counters are plain numbers, so a poisoned lock can't leave them in a bad state
+        let mut methods = self.methods.lock().unwrap_or_else(PoisonError::into_inner);
+        update(methods.entry(method.to_owned()).or_default());
+    }
+
+    /// Returns the counts recorded since the last call, and resets them.
+    pub fn take(&self) -> BTreeMap<String, MethodCounts> {
+        std::mem::take(&mut *self.methods.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Takes the current counts (see `take`) as a one-line summary.
+    pub fn take_report(&self) -> String {
+        self.take()
+            .iter()
+            .map(|(method, counts)| format!("{}: {}", method, counts))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// JSON-RPC transport that rate-limits, retries & counts every request sent through `inner`.
+#[derive(Debug)]
+pub struct ThrottledTransport<T> {
+    inner: T,
+    limits: RpcLimits,
+    limiter: RateLimiter,
+    counters: RpcCounters,
+}
+
+impl<T> ThrottledTransport<T> {
+    pub fn new(inner: T, limits: RpcLimits) -> Self {
+        Self {
+            inner,
+            limits,
+            limiter: RateLimiter::new(limits.requests_per_second, limits.burst),
+            counters: RpcCounters::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn counters(&self) -> &RpcCounters {
+        &self.counters
+    }
+
+    /// Exponential backoff with jitter, so that concurrent retries don't all land at once.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .limits
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.limits.max_delay);
+        delay.mul_f64(0.5 + rand::random::<f64>() * 0.5)
+    }
+}
+
+/// Returns true if a request that failed with `err` may succeed if it's sent again.
+fn is_transient<E: RpcError>(err: &E) -> bool {
+    if err.as_serde_error().is_some() {
+        // we got a response, it just wasn't what we expected; sending it again won't change that
+        return false;
+    }
+    match err.as_error_response() {
+        Some(res) => {
+            let message = res.message.to_lowercase();
+            RATE_LIMIT_CODES.contains(&res.code)
+                || TRANSIENT_MESSAGES.iter().any(|msg| message.contains(msg))
+        }
+        // no response at all, e.g. the connection dropped
+        None => true,
+    }
+}
+
+#[async_trait]
+impl<T: JsonRpcClient> JsonRpcClient for ThrottledTransport<T> {
+    type Error = T::Error;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire().await;
+            self.counters.record(method, |counts| counts.calls += 1);
+            match self.inner.request(method, &params).await {
+                Ok(res) => return Ok(res),
+                Err(err) if attempt < self.limits.max_retries && is_transient(&err) => {
+                    let delay = self.backoff(attempt);
+                    attempt += 1;
+                    self.counters.record(method, |counts| counts.retries += 1);
+                    debug!(
+                        "{} failed, retrying in {:?} (attempt {}): {}",
+                        method, delay, attempt, err
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => {
+                    self.counters.record(method, |counts| counts.errors += 1);
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+impl<T: PubsubClient> PubsubClient for ThrottledTransport<T> {
+    type NotificationStream = T::NotificationStream;
+
+    fn subscribe<I: Into<U256>>(&self, id: I) -> Result<Self::NotificationStream, Self::Error> {
+        self.inner.subscribe(id)
+    }
+
+    fn unsubscribe<I: Into<U256>>(&self, id: I) -> Result<(), Self::Error> {
+        self.inner.unsubscribe(id)
+    }
+}
+
+/// Returns the transport of `client`, for its counters, rate limit & endpoints.
+pub fn transport(client: &WsClient) -> &ThrottledTransport<EndpointPool> {
+    (**client).as_ref()
+}
+
+/// Returns the local proxy that forwards requests through `client`'s transport, starting it
+/// the first time it's needed. See `ForkProxy`.
+pub async fn fork_proxy(client: &WsClient) -> Result<&ForkProxy> {
+    transport(client)
+        .inner()
+        .fork_proxy
+        .get_or_try_init(|| ForkProxy::start(Arc::downgrade(client)))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        providers::{JsonRpcError, MockProvider, MockResponse},
+        types::U64,
+    };
+
+    fn test_limits() -> RpcLimits {
+        RpcLimits {
+            requests_per_second: 0.0,
+            burst: 1.0,
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    fn rpc_error(code: i64, message: &str) -> MockResponse {
+        MockResponse::Error(JsonRpcError {
+            code,
+            message: message.to_owned(),
+            data: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn it_retries_transient_errors() -> Result<()> {
+        let mock = MockProvider::new();
+        let transport = ThrottledTransport::new(mock.clone(), test_limits());
+        // mock responses are popped last-in-first-out
+        mock.push::<U64, _>(U64::from(17637019))?;
+        mock.push_response(rpc_error(429, "Too Many Requests"));
+        let block: U64 = transport.request("eth_blockNumber", ()).await?;
+        assert_eq!(block, U64::from(17637019));
+        let counts = transport.counters().take();
+        assert_eq!(
+            counts.get("eth_blockNumber"),
+            Some(&MethodCounts {
+                calls: 2,
+                retries: 1,
+                errors: 0
+            })
+        );
+        // counts are reset after they're taken
+        assert!(transport.counters().take().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_does_not_retry_permanent_errors() -> Result<()> {
+        let mock = MockProvider::new();
+        let transport = ThrottledTransport::new(mock.clone(), test_limits());
+        mock.push::<U64, _>(U64::from(1))?;
+        mock.push_response(rpc_error(-32000, "execution reverted"));
+        let res: Result<U64, _> = transport.request("eth_call", ()).await;
+        assert!(res.is_err());
+        assert_eq!(
+            transport.counters().take().get("eth_call"),
+            Some(&MethodCounts {
+                calls: 1,
+                retries: 0,
+                errors: 1
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_caps_backoff() {
+        let transport = ThrottledTransport::new((), test_limits());
+        for attempt in 0..40 {
+            assert!(transport.backoff(attempt) <= Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{warn, Result};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Time in-flight work gets to finish after a shutdown is requested, by default.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tells long-running commands to stop taking new work, and how long they have
+/// to finish the work they've already started.
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    token: CancellationToken,
+    timeout: Duration,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHUTDOWN_TIMEOUT)
+    }
+}
+
+impl Shutdown {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            timeout,
+        }
+    }
+
+    /// Installs a ctrl-c handler that requests a shutdown on the first signal,
+    /// and exits the process right away on the second.
+    pub fn install_signal_handler(&self) -> Result<()> {
+        let token = self.token.clone();
+        let timeout = self.timeout;
+        let signaled = AtomicBool::new(false);
+        ctrlc::set_handler(move || {
+            if signaled.swap(true, Ordering::SeqCst) {
+                println!("\nstopping hindsight now!");
+                std::process::exit(130);
+            }
+            println!(
+                "\nstopping hindsight! finishing work in progress (up to {:?}); press ctrl-c again to force exit",
+                timeout
+            );
+            token.cancel();
+        })?;
+        Ok(())
+    }
+
+    /// Asks everything watching this shutdown to stop.
+    pub fn request(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Completes once a shutdown is requested.
+    pub async fn requested(&self) {
+        self.token.cancelled().await
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Completes `timeout` after a shutdown is requested; for giving up on work in flight.
+    pub async fn expired(&self) {
+        self.requested().await;
+        tokio::time::sleep(self.timeout).await;
+        warn!("work in flight didn't finish within {:?}", self.timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_expires_after_the_timeout() {
+        let shutdown = Shutdown::new(Duration::from_millis(10));
+        let expired = tokio::time::timeout(Duration::from_millis(50), shutdown.expired()).await;
+        assert!(expired.is_err(), "expired before shutdown was requested");
+
+        shutdown.request();
+        assert!(shutdown.is_requested());
+        let expired = tokio::time::timeout(Duration::from_millis(500), shutdown.expired()).await;
+        assert!(expired.is_ok());
+    }
+}
@@ -0,0 +1,475 @@
+use super::{SimBackend, SimBackendKind};
+use crate::{
+    config::Config,
+    debug,
+    error::{HindsightError, RevertReason},
+    info,
+    interfaces::{PoolVariant, TokenPair},
+    rpc::{self, endpoints::redact_rpc_url},
+    sim::{
+        evm::{
+            braindance_swap_data, bytes_from_str, decode_braindance_swap, decode_price_v2,
+            decode_price_v3, token0_of, DECIMALS_SELECTOR, GET_RESERVES_SELECTOR,
+            LIQUIDITY_SELECTOR, SLOT0_SELECTOR,
+        },
+        prefetch::PrefetchedState,
+    },
+    util::{WsClient, ETH},
+    Result,
+};
+use async_trait::async_trait;
+use ethers::{
+    abi::{self, Token},
+    providers::{Http, Middleware, Provider, ProviderError, RpcError},
+    types::{Address, Bytes, Transaction, TransactionRequest, H256, U256, U64},
+    utils::keccak256,
+};
+use futures::future;
+use revm::{
+    db::{CacheDB, EmptyDB},
+    Database,
+};
+use rusty_sando::{
+    forked_db::fork_factory::ForkFactory,
+    simulate::{
+        attach_braindance_module, braindance_address, braindance_controller_address,
+        braindance_starting_balance,
+    },
+    types::BlockInfo,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use std::{
+    fmt::Debug,
+    process::Stdio,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::{
+    process::{Child, Command},
+    sync::{Mutex, MutexGuard},
+    time::Instant,
+};
+
+/// WETH (mainnet); the braindance contract trades with its WETH balance.
+const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+/// Storage slot of WETH's `balanceOf` mapping.
+const WETH_BALANCE_SLOT: u64 = 3;
+/// Max time a spawned node gets to start answering requests.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Seconds between the fork block & the block the sims are mined in.
+const BLOCK_TIME: u64 = 12;
+
+/// Where to find the nodes to simulate on.
+#[derive(Clone, Debug)]
+pub struct AnvilConfig {
+    /// Url of an already running Anvil-compatible node. If None, `path` is spawned.
+    pub url: Option<String>,
+    /// Anvil binary to spawn.
+    pub path: String,
+    /// Number of nodes to spawn; each can be forked at a different block.
+    pub nodes: usize,
+}
+
+impl From<&Config> for AnvilConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            url: config.anvil_url.to_owned(),
+            path: config.anvil_path.to_owned(),
+            nodes: config.anvil_nodes,
+        }
+    }
+}
+
+/// Where a node is forked, & the state every sim starts from.
+#[derive(Debug, Default)]
+struct ForkState {
+    block: Option<U64>,
+    snapshot: U256,
+}
+
+/// Simulates on a pool of local Anvil nodes, each forked at a simulated block.
+///
+/// Sims go to a node that's already forked at their block if there is one, otherwise
+/// to an idle node, which is re-forked. Each sim starts by reverting its node to a
+/// snapshot taken right after forking, and a node only holds one state at a time, so
+/// sims on the same node take turns. Anvil keeps the state it forks in its own cache,
+/// so repeated runs over the same blocks mostly skip the RPC node (& its rate limit).
+#[derive(Debug)]
+pub struct AnvilBackend {
+    nodes: Vec<AnvilNode>,
+    /// Picks the node to wait for when they're all busy w/ other blocks.
+    next: AtomicUsize,
+}
+
+/// One Anvil node & the fork it holds.
+#[derive(Debug)]
+struct AnvilNode {
+    provider: Provider<Http>,
+    /// The node we spawned, if any; killed when the backend is dropped.
+    _process: Option<Child>,
+    braindance_code: Bytes,
+    fork: Mutex<ForkState>,
+    /// Block the node is forked at (0 if none); readable w/o waiting for `fork`.
+    forked_at: AtomicU64,
+}
+
+impl AnvilBackend {
+    /// Connects to the node at `config.url`, or spawns `config.nodes` nodes.
+    ///
+    /// Spawned nodes fork state through `rpc::fork_proxy`, so their requests are throttled &
+    /// failed over like ours, and no RPC url (or its API key) ends up on their command line.
+    pub async fn connect(client: &WsClient, config: &AnvilConfig) -> Result<Self> {
+        let braindance_code = braindance_code(client).await?;
+        let nodes = match &config.url {
+            Some(url) => vec![(None, url.to_owned())],
+            None => {
+                let fork_url = &rpc::fork_proxy(client).await?.url;
+                (0..config.nodes.max(1))
+                    .map(|_| {
+                        let (process, url) = spawn_anvil(&config.path, fork_url)?;
+                        Ok((Some(process), url))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            }
+        };
+        let nodes = future::try_join_all(
+            nodes
+                .into_iter()
+                .map(|(process, url)| AnvilNode::connect(process, url, braindance_code.clone())),
+        )
+        .await?;
+        Ok(Self {
+            nodes,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Locks a node & puts it in the state at the end of `block_info`.
+    async fn node(
+        &self,
+        block_info: &BlockInfo,
+    ) -> Result<(&AnvilNode, MutexGuard<'_, ForkState>), HindsightError> {
+        let block = block_info.number.as_u64();
+        let forked = self
+            .nodes
+            .iter()
+            .find(|node| node.forked_at.load(Ordering::Relaxed) == block);
+        let idle = || {
+            self.nodes
+                .iter()
+                .find_map(|node| node.fork.try_lock().ok().map(|fork| (node, fork)))
+        };
+        let (node, mut fork) = match forked {
+            // waiting our turn is cheaper than forking another node at the same block
+            Some(node) => (node, node.fork.lock().await),
+            None => match idle() {
+                Some(idle) => idle,
+                None => {
+                    let i = self.next.fetch_add(1, Ordering::Relaxed) % self.nodes.len();
+                    (&self.nodes[i], self.nodes[i].fork.lock().await)
+                }
+            },
+        };
+        node.reset(&mut fork, block_info).await?;
+        Ok((node, fork))
+    }
+}
+
+impl AnvilNode {
+    async fn connect(process: Option<Child>, url: String, braindance_code: Bytes) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(url.as_str())?;
+        wait_until_ready(&provider).await?;
+        info!("simulating on anvil node at {}", redact_rpc_url(&url));
+        Ok(Self {
+            provider,
+            _process: process,
+            braindance_code,
+            fork: Mutex::new(ForkState::default()),
+            forked_at: AtomicU64::new(0),
+        })
+    }
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, HindsightError>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        Ok(self.provider.request(method, params).await?)
+    }
+
+    /// Sends a request whose result we don't need.
+    async fn set<P>(&self, method: &str, params: P) -> Result<(), HindsightError>
+    where
+        P: Debug + Serialize + Send + Sync,
+    {
+        self.request::<_, serde_json::Value>(method, params)
+            .await
+            .map(|_| ())
+    }
+
+    /// Puts the node in the state at the end of `block_info`, forking it there if it isn't already.
+    async fn reset(
+        &self,
+        fork: &mut ForkState,
+        block_info: &BlockInfo,
+    ) -> Result<(), HindsightError> {
+        if fork.block == Some(block_info.number) {
+            let reverted: bool = self.request("evm_revert", [fork.snapshot]).await?;
+            if !reverted {
+                return Err(HindsightError::EvmError(format!(
+                    "failed to revert anvil to snapshot {}",
+                    fork.snapshot
+                )));
+            }
+        } else {
+            debug!("forking anvil at block {}", block_info.number);
+            fork.block = None;
+            self.forked_at.store(0, Ordering::Relaxed);
+            self.set(
+                "anvil_reset",
+                [json!({ "forking": { "blockNumber": block_info.number.as_u64() } })],
+            )
+            .await?;
+            self.attach_braindance().await?;
+            fork.block = Some(block_info.number);
+            self.forked_at
+                .store(block_info.number.as_u64(), Ordering::Relaxed);
+        }
+        // reverting deletes the snapshot, so we take a new one every time
+        fork.snapshot = self.request("evm_snapshot", ()).await?;
+        // gas is paid in ETH & profit is measured in WETH, so a zero base fee doesn't change
+        // the results; it just keeps the user's tx from being priced out of the sim block
+        self.set("anvil_setNextBlockBaseFeePerGas", [U256::zero()])
+            .await?;
+        self.set(
+            "evm_setNextBlockTimestamp",
+            [block_info.timestamp.as_u64() + BLOCK_TIME],
+        )
+        .await
+    }
+
+    /// Same setup as rusty-sando's `attach_braindance_module` does for revm forks.
+    async fn attach_braindance(&self) -> Result<(), HindsightError> {
+        let controller = braindance_controller_address();
+        self.set(
+            "anvil_setCode",
+            (braindance_address(), &self.braindance_code),
+        )
+        .await?;
+        self.set("anvil_setBalance", (controller, ETH * 100))
+            .await?;
+        self.set("anvil_impersonateAccount", [controller]).await?;
+        let balance_slot = H256(keccak256(abi::encode(&[
+            Token::Address(braindance_address()),
+            Token::Uint(WETH_BALANCE_SLOT.into()),
+        ])));
+        let mut balance = [0u8; 32];
+        braindance_starting_balance().to_big_endian(&mut balance);
+        self.set("anvil_setStorageAt", (weth()?, balance_slot, H256(balance)))
+            .await
+    }
+
+    /// Calls a no-arg view function on `contract`.
+    async fn call_function(
+        &self,
+        method: &str,
+        contract: Address,
+    ) -> Result<Bytes, HindsightError> {
+        let tx = TransactionRequest::new()
+            .to(contract)
+            .data(bytes_from_str(method)?);
+        self.provider
+            .call(&tx.into(), None)
+            .await
+            .map_err(|err| revert_or_rpc_error(err, HindsightError::CallReverted))
+    }
+
+    /// Executes `user_tx` from its original sender. Like the revm backend, a failed
+    /// user tx doesn't stop the sim.
+    async fn send_user_tx(&self, user_tx: &Transaction) -> Result<(), HindsightError> {
+        self.set("anvil_impersonateAccount", [user_tx.from]).await?;
+        let tx = TransactionRequest::new()
+            .from(user_tx.from)
+            .to(user_tx.to.unwrap_or_default())
+            .data(user_tx.input.to_owned())
+            .value(user_tx.value)
+            .gas(user_tx.gas)
+            .gas_price(0);
+        if let Err(err) = self.request::<_, H256>("eth_sendTransaction", [tx]).await {
+            debug!("user tx {:?} failed on anvil: {}", user_tx.hash, err);
+        }
+        Ok(())
+    }
+
+    /// Executes a braindance swap & mines it.
+    ///
+    /// Returns balance of token_out after the swap.
+    async fn commit_braindance_swap(
+        &self,
+        pool_variant: PoolVariant,
+        amount_in: U256,
+        target_pool: Address,
+        token_in: Address,
+        token_out: Address,
+    ) -> Result<U256, HindsightError> {
+        let tx = TransactionRequest::new()
+            .from(braindance_controller_address())
+            .to(braindance_address())
+            .data(braindance_swap_data(
+                pool_variant,
+                amount_in,
+                target_pool,
+                token_in,
+                token_out,
+            ))
+            .gas(700_000)
+            .gas_price(0);
+        // a mined tx doesn't give us its output, so we call it first
+        let output = self
+            .provider
+            .call(&tx.to_owned().into(), None)
+            .await
+            .map_err(|err| revert_or_rpc_error(err, HindsightError::SwapReverted))?;
+        self.request::<_, H256>("eth_sendTransaction", [tx]).await?;
+        decode_braindance_swap(pool_variant, output)
+    }
+}
+
+#[async_trait]
+impl SimBackend for AnvilBackend {
+    fn kind(&self) -> SimBackendKind {
+        SimBackendKind::Anvil
+    }
+
+    /// `prefetched` is ignored; the node loads (& caches) state itself.
+    async fn pool_price(
+        &self,
+        block_info: &BlockInfo,
+        _prefetched: &PrefetchedState,
+        pool: (Address, PoolVariant),
+        token_in: Address,
+        token_out: Address,
+    ) -> Result<U256, HindsightError> {
+        let (node, _fork) = self.node(block_info).await?;
+        let decimals = node
+            .call_function(DECIMALS_SELECTOR, token0_of(token_in, token_out))
+            .await?;
+        match pool.1 {
+            PoolVariant::UniswapV2 => {
+                let reserves = node.call_function(GET_RESERVES_SELECTOR, pool.0).await?;
+                decode_price_v2(&reserves, &decimals)
+            }
+            PoolVariant::UniswapV3 => {
+                let slot0 = node.call_function(SLOT0_SELECTOR, pool.0).await?;
+                let liquidity = node.call_function(LIQUIDITY_SELECTOR, pool.0).await?;
+                decode_price_v3(&slot0, &liquidity, &decimals)
+            }
+        }
+    }
+
+    async fn sim_arb(
+        &self,
+        block_info: &BlockInfo,
+        _prefetched: &PrefetchedState,
+        user_tx: &Transaction,
+        tokens: &TokenPair,
+        amount_in: U256,
+        start_pool: (Address, PoolVariant),
+        end_pool: (Address, PoolVariant),
+    ) -> Result<(U256, U256), HindsightError> {
+        let (node, _fork) = self.node(block_info).await?;
+        node.send_user_tx(user_tx).await?;
+
+        /* Buy tokens on one exchange. */
+        let res = node
+            .commit_braindance_swap(
+                start_pool.1,
+                amount_in,
+                start_pool.0,
+                tokens.weth,
+                tokens.token,
+            )
+            .await;
+        debug!("braindance 1 completed. {:?}", res);
+        let amount_received = res.unwrap_or(0.into());
+
+        /* Sell them on other exchange. */
+        let res = node
+            .commit_braindance_swap(
+                end_pool.1,
+                amount_received,
+                end_pool.0,
+                tokens.token,
+                tokens.weth,
+            )
+            .await?;
+        debug!("braindance 2 completed. {:?}", res);
+        Ok((amount_in, res))
+    }
+}
+
+fn weth() -> Result<Address, HindsightError> {
+    WETH.parse::<Address>()
+        .map_err(|err| HindsightError::EvmParseError(err.to_string()))
+}
+
+/// Maps a failed call to `revert` w/ the decoded reason if the node returned revert data,
+/// otherwise to an RPC error.
+fn revert_or_rpc_error(
+    err: ProviderError,
+    revert: fn(RevertReason) -> HindsightError,
+) -> HindsightError {
+    match err.as_error_response().and_then(|res| res.as_revert_data()) {
+        Some(data) => revert(RevertReason::decode(&data)),
+        None => err.into(),
+    }
+}
+
+/// Spawns anvil on a free local port, forking from `fork_url`. Returns the process & its url.
+fn spawn_anvil(path: &str, fork_url: &str) -> Result<(Child, String)> {
+    // let the OS pick a free port
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    let node = Command::new(path)
+        .args(["--fork-url", fork_url, "--port", &port.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| anyhow::format_err!("failed to spawn {}: {}", path, err))?;
+    Ok((node, format!("http://127.0.0.1:{}", port)))
+}
+
+async fn wait_until_ready(provider: &Provider<Http>) -> Result<()> {
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        match provider.get_chainid().await {
+            Ok(_) => return Ok(()),
+            Err(_) if Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(200)).await
+            }
+            Err(err) => return Err(anyhow::format_err!("anvil node isn't answering: {}", err)),
+        }
+    }
+}
+
+/// Reads the code of the braindance contract from the module rusty-sando attaches
+/// to revm forks, so both backends trade through the same contract.
+async fn braindance_code(client: &WsClient) -> Result<Bytes> {
+    let mut fork_factory = ForkFactory::new_sandbox_factory(
+        rpc::fork_proxy(client).await?.provider.clone(),
+        CacheDB::new(EmptyDB::default()),
+        None,
+    );
+    attach_braindance_module(&mut fork_factory);
+    let account = fork_factory
+        .new_sandbox_fork()
+        .basic(braindance_address().0.into())
+        .map_err(|err| anyhow::format_err!("failed to read braindance module: {:?}", err))?;
+    account
+        .and_then(|account| account.code)
+        .map(|code| code.original_bytes().into())
+        .ok_or(anyhow::format_err!("braindance module has no code"))
+}
@@ -0,0 +1,267 @@
+pub mod anvil;
+
+use crate::{
+    debug,
+    error::HindsightError,
+    interfaces::{PoolVariant, TokenPair},
+    sim::{
+        core::fork_evm_prefetched,
+        evm::{commit_braindance_swap, sim_bundle, sim_price_v2, sim_price_v3},
+        prefetch::PrefetchedState,
+    },
+    util::WsClient,
+    Result,
+};
+use anvil::{AnvilBackend, AnvilConfig};
+use async_trait::async_trait;
+use ethers::types::{Address, Transaction, U256};
+use revm::EVM;
+use rusty_sando::{prelude::fork_db::ForkDB, types::BlockInfo};
+use std::{fmt::Debug, str::FromStr, sync::Arc};
+
+/// Which EVM the backrun search runs its simulations on.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SimBackendKind {
+    /// In-process revm, forked from the RPC node.
+    #[default]
+    Revm,
+    /// A local Anvil (or Anvil-compatible) node, forked at the simulated block.
+    Anvil,
+}
+
+impl FromStr for SimBackendKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "revm" => Ok(SimBackendKind::Revm),
+            "anvil" => Ok(SimBackendKind::Anvil),
+            _ => Err(format!("invalid sim backend: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for SimBackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimBackendKind::Revm => write!(f, "revm"),
+            SimBackendKind::Anvil => write!(f, "anvil"),
+        }
+    }
+}
+
+/// Runs the simulations of the backrun search on an EVM forked at a given block.
+///
+/// Every call starts from the state at the end of `block_info`; nothing carries over
+/// between calls. Calls may be made concurrently.
+#[async_trait]
+pub trait SimBackend: Debug + Send + Sync {
+    fn kind(&self) -> SimBackendKind;
+
+    /// Returns the price (token1/token0) of `pool`.
+    async fn pool_price(
+        &self,
+        block_info: &BlockInfo,
+        prefetched: &PrefetchedState,
+        pool: (Address, PoolVariant),
+        token_in: Address,
+        token_out: Address,
+    ) -> Result<U256, HindsightError>;
+
+    /// Executes `user_tx`, then a two-step arbitrage with a fixed trade amount & path:
+    ///
+    /// 1. Buy `amount_in` WETH worth of token on `start_pool`
+    ///
+    /// 2. Sell the whole balance of token on `end_pool` for WETH, completing the arb.
+    ///
+    /// Returns `amount_in` & the braindance contract's WETH balance after the arb.
+    #[allow(clippy::too_many_arguments)]
+    async fn sim_arb(
+        &self,
+        block_info: &BlockInfo,
+        prefetched: &PrefetchedState,
+        user_tx: &Transaction,
+        tokens: &TokenPair,
+        amount_in: U256,
+        start_pool: (Address, PoolVariant),
+        end_pool: (Address, PoolVariant),
+    ) -> Result<(U256, U256), HindsightError>;
+}
+
+pub type SimBackendRef = Arc<dyn SimBackend>;
+
+/// Connects to a sim backend of the given kind. `anvil` is only used by the anvil backend.
+pub async fn connect(
+    client: &WsClient,
+    kind: SimBackendKind,
+    anvil: &AnvilConfig,
+) -> Result<SimBackendRef> {
+    Ok(match kind {
+        SimBackendKind::Revm => Arc::new(RevmBackend::new(client.clone())),
+        SimBackendKind::Anvil => Arc::new(AnvilBackend::connect(client, anvil).await?),
+    })
+}
+
+/// Simulates on in-process revm forks that load state from the RPC node.
+///
+/// Every call gets a fresh fork, so calls run in parallel.
+#[derive(Clone, Debug)]
+pub struct RevmBackend {
+    client: WsClient,
+}
+
+impl RevmBackend {
+    pub fn new(client: WsClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl SimBackend for RevmBackend {
+    fn kind(&self) -> SimBackendKind {
+        SimBackendKind::Revm
+    }
+
+    async fn pool_price(
+        &self,
+        block_info: &BlockInfo,
+        prefetched: &PrefetchedState,
+        pool: (Address, PoolVariant),
+        token_in: Address,
+        token_out: Address,
+    ) -> Result<U256, HindsightError> {
+        let mut evm = fork_evm_prefetched(&self.client, block_info, prefetched).await?;
+        match pool.1 {
+            PoolVariant::UniswapV2 => sim_price_v2(pool.0, token_in, token_out, &mut evm).await,
+            PoolVariant::UniswapV3 => sim_price_v3(pool.0, token_in, token_out, &mut evm).await,
+        }
+    }
+
+    async fn sim_arb(
+        &self,
+        block_info: &BlockInfo,
+        prefetched: &PrefetchedState,
+        user_tx: &Transaction,
+        tokens: &TokenPair,
+        amount_in: U256,
+        start_pool: (Address, PoolVariant),
+        end_pool: (Address, PoolVariant),
+    ) -> Result<(U256, U256), HindsightError> {
+        let evm = fork_evm_prefetched(&self.client, block_info, prefetched).await?;
+        sim_arb_single(
+            evm,
+            user_tx.to_owned(),
+            block_info,
+            tokens,
+            amount_in,
+            start_pool,
+            end_pool,
+        )
+        .await
+    }
+}
+
+/// Simulate a two-step arbitrage on a forked EVM with fixed trade amount & path.
+///
+/// 1. Buy `amount_in` WETH worth of token on start_pair
+///
+/// 2. Sell balance of token on end_pair for WETH, completing the arb.
+async fn sim_arb_single(
+    mut evm: EVM<ForkDB>,
+    user_tx: Transaction,
+    block_info: &BlockInfo,
+    tokens: &TokenPair,
+    amount_in: U256,
+    start_pair_variant: (Address, PoolVariant),
+    end_pair_variant: (Address, PoolVariant),
+) -> Result<(U256, U256), HindsightError> {
+    let (start_pool, start_variant) = start_pair_variant;
+    let (end_pool, end_variant) = end_pair_variant;
+    sim_bundle(&mut evm, vec![user_tx.to_owned()]).await?;
+
+    /*
+    - if the price is denoted in TKN/ETH, we want to buy where the price is highest
+    - if the price is denoted in ETH/TKN, we want to buy where the price is lowest
+    - price is always denoted in tkn1/tkn0
+    */
+
+    /* Buy tokens on one exchange. */
+    let res = commit_braindance_swap(
+        &mut evm,
+        start_variant,
+        amount_in,
+        start_pool,
+        tokens.weth,
+        tokens.token,
+        block_info.base_fee,
+        None,
+    );
+    debug!("braindance 1 completed. {:?}", res);
+    let amount_received = res.unwrap_or(0.into());
+    debug!("amount received {:?}", amount_received);
+
+    /* Sell them on other exchange. */
+    let res = commit_braindance_swap(
+        &mut evm,
+        end_variant,
+        amount_received,
+        end_pool,
+        tokens.token,
+        tokens.weth,
+        block_info.base_fee + (block_info.base_fee * 2500) / 10000,
+        None,
+    )?;
+    debug!("braindance 2 completed. {:?}", res);
+    Ok((amount_in, res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        util::{get_block_info, test::get_test_ws_client},
+    };
+    use ethers::providers::Middleware;
+
+    #[test]
+    fn it_parses_sim_backends() {
+        assert_eq!("revm".parse(), Ok(SimBackendKind::Revm));
+        assert_eq!("anvil".parse(), Ok(SimBackendKind::Anvil));
+        assert!("geth".parse::<SimBackendKind>().is_err());
+        assert_eq!(SimBackendKind::Anvil.to_string(), "anvil");
+    }
+
+    /// Requires `anvil` (or ANVIL_URL) in addition to the test RPC node.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn it_prices_pools_the_same_on_both_backends() -> Result<()> {
+        let client = get_test_ws_client().await?;
+        let block_info =
+            get_block_info(&client, client.get_block_number().await?.as_u64() - 4).await?;
+        let revm = RevmBackend::new(client.clone());
+        let anvil = AnvilBackend::connect(&client, &AnvilConfig::from(&Config::default())).await?;
+        let weth = Address::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")?;
+        let shib = Address::from_str("0x95aD61b0a150d79219dCF64E1E6Cc01f0B64C4cE")?;
+        let prefetched = PrefetchedState::default();
+        for pool in [
+            // UniV2 SHIB/WETH
+            (
+                Address::from_str("0x811beEd0119b4AfCE20D2583EB608C6F7AF1954f")?,
+                PoolVariant::UniswapV2,
+            ),
+            // UniV3 SHIB/WETH (fee=3000)
+            (
+                Address::from_str("0x2F62f2B4c5fcd7570a709DeC05D68EA19c82A9ec")?,
+                PoolVariant::UniswapV3,
+            ),
+        ] {
+            let revm_price = revm
+                .pool_price(&block_info, &prefetched, pool, weth, shib)
+                .await?;
+            let anvil_price = anvil
+                .pool_price(&block_info, &prefetched, pool, weth, shib)
+                .await?;
+            assert_eq!(revm_price, anvil_price);
+        }
+        Ok(())
+    }
+}
@@ -2,12 +2,13 @@ use crate::error::HindsightError;
 use crate::interfaces::{
     BackrunResult, PairPool, PoolVariant, SimArbResult, TokenPair, UserTradeParams,
 };
-use crate::sim::evm::{commit_braindance_swap, sim_bundle, sim_price_v2, sim_price_v3};
-use crate::util::{
-    get_all_trading_pools, get_decimals, get_pair_tokens, get_price_v2, get_price_v3, WsClient,
-};
+use crate::rpc;
+use crate::sim::backend::{RevmBackend, SimBackendRef};
+use crate::sim::prefetch::{prefetch_state, PrefetchedState};
+use crate::tokens::TokenCache;
+use crate::util::{get_all_trading_pools, get_pair_tokens, get_price_v2, get_price_v3, WsClient};
+use crate::Result;
 use crate::{debug, info};
-use crate::{Error, Result};
 use async_recursion::async_recursion;
 use ethers::providers::Middleware;
 use ethers::types::{AccountDiff, Address, BlockNumber, Transaction, H160, H256, I256, U256};
@@ -23,16 +24,38 @@ use rusty_sando::types::BlockInfo;
 use rusty_sando::{forked_db::fork_factory::ForkFactory, utils::state_diff};
 use std::collections::BTreeMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
-const MAX_DEPTH: usize = 7;
-const STEP_INTERVALS: usize = 15;
+/// Max recursion depth of the backrun amount search.
+pub const MAX_DEPTH: usize = 7;
+/// Number of amounts tried at each depth of the backrun amount search.
+pub const STEP_INTERVALS: usize = 15;
 
 /// Return an evm instance forked from the provided block info and client state
 /// with braindance module initialized.
 /// Braindance contracts starts w/ braindance_starting_balance, which is 420 WETH.
-pub async fn fork_evm(client: &WsClient, block_info: &BlockInfo) -> Result<EVM<ForkDB>> {
+pub async fn fork_evm(
+    client: &WsClient,
+    block_info: &BlockInfo,
+) -> Result<EVM<ForkDB>, HindsightError> {
+    fork_evm_prefetched(client, block_info, &PrefetchedState::default()).await
+}
+
+/// Like `fork_evm`, but the fork starts out with `prefetched` state loaded,
+/// so it doesn't have to fetch it lazily.
+pub async fn fork_evm_prefetched(
+    client: &WsClient,
+    block_info: &BlockInfo,
+    prefetched: &PrefetchedState,
+) -> Result<EVM<ForkDB>, HindsightError> {
     let fork_block_num = BlockNumber::Number(block_info.number);
     let fork_block = Some(ethers::types::BlockId::Number(fork_block_num));
+    // the forked DB only takes a plain `Provider<Ws>`, so its state requests go through a
+    // local proxy to reach our rate limit, retries & failover
+    let client = &rpc::fork_proxy(client)
+        .await
+        .map_err(HindsightError::rpc)?
+        .provider;
 
     let state_diffs =
         if let Some(sd) = state_diff::get_from_txs(client, &vec![], fork_block_num).await {
@@ -40,7 +63,10 @@ pub async fn fork_evm(client: &WsClient, block_info: &BlockInfo) -> Result<EVM<F
         } else {
             BTreeMap::<H160, AccountDiff>::new()
         };
-    let initial_db = state_diff::to_cache_db(&state_diffs, fork_block, client).await?;
+    let mut initial_db = state_diff::to_cache_db(&state_diffs, fork_block, client)
+        .await
+        .map_err(|err| HindsightError::RpcError(format!("failed to fork state: {:?}", err)))?;
+    prefetched.apply(&mut initial_db);
     let mut fork_factory = ForkFactory::new_sandbox_factory(client.clone(), initial_db, fork_block);
     attach_braindance_module(&mut fork_factory);
 
@@ -50,24 +76,40 @@ pub async fn fork_evm(client: &WsClient, block_info: &BlockInfo) -> Result<EVM<F
     Ok(evm)
 }
 
+fn parse_h256(hex: &str) -> Result<H256, HindsightError> {
+    H256::from_str(hex).map_err(|err| HindsightError::EvmParseError(err.to_string()))
+}
+
+/// Reads the `index`th 32-byte word of log data.
+fn log_word(data: &[u8], index: usize) -> Result<U256, HindsightError> {
+    data.get(index * 32..(index + 1) * 32)
+        .map(U256::from_big_endian)
+        .ok_or(HindsightError::DecodeError(format!(
+            "log data too short for word {} (len={})",
+            index,
+            data.len()
+        )))
+}
+
 /// Returns None if trade params can't be derived.
 ///
 /// May derive multiple trades from a single tx.
-async fn derive_trade_params(
+pub async fn derive_trade_params(
     client: &WsClient,
+    tokens: &TokenCache,
     tx: Transaction,
     event: &EventHistory,
-) -> Result<Vec<UserTradeParams>> {
+) -> Result<Vec<UserTradeParams>, HindsightError> {
     // Swap(address,address,int256,int256,uint160,uint128,int24)
     let univ3_topic =
-        H256::from_str("0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67")?;
+        parse_h256("0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67")?;
     let sync_topic =
-        H256::from_str("0x1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1")?;
+        parse_h256("0x1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1")?;
     let uniswap_topics = [
-        H256::from_str("0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67")?,
+        parse_h256("0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67")?,
         // univ2
         // Swap(address,uint256,uint256,uint256,uint256,address)
-        H256::from_str("0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822")?,
+        parse_h256("0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822")?,
     ];
 
     // get potential pool addresses from event, relying on mev-share hints
@@ -75,7 +117,11 @@ async fn derive_trade_params(
         .hint
         .logs
         .iter()
-        .filter(|log| uniswap_topics.contains(&log.topics[0]))
+        .filter(|log| {
+            log.topics
+                .first()
+                .map_or(false, |topic| uniswap_topics.contains(topic))
+        })
         .map(|log| log.to_owned())
         .collect::<Vec<EventTransactionLog>>();
     debug!("swap logs {:?}", swap_logs);
@@ -83,7 +129,7 @@ async fn derive_trade_params(
     let tx_receipt = client
         .get_transaction_receipt(tx.hash)
         .await?
-        .ok_or::<Error>(HindsightError::TxNotLanded(tx.hash).into())?;
+        .ok_or(HindsightError::TxNotLanded(tx.hash))?;
 
     // collect trade params for each pair derived from swap logs
     let mut trade_params = vec![];
@@ -97,10 +143,10 @@ async fn derive_trade_params(
             .logs
             .iter()
             .find(|log| log.topics.contains(&swap_topic) && log.address == pool_address)
-            .ok_or(anyhow::format_err!(
+            .ok_or(HindsightError::DecodeError(format!(
                 "no swap logs found for tx {:?}",
                 tx.hash
-            ))?;
+            )))?;
 
         // derive pool variant from event log topics
         let pool_variant = if swap_topic == univ3_topic {
@@ -112,26 +158,47 @@ async fn derive_trade_params(
 
         // get token addrs from pool address
         // tokens may vary per swap log -- many swaps can happen in one tx
-        let (token0, token1) = get_pair_tokens(client, pool_address).await?;
+        let (token0, token1) = get_pair_tokens(client, pool_address)
+            .await
+            .map_err(HindsightError::rpc)?;
         debug!("token0\t{:?}\ntoken1\t{:?}", token0, token1);
-        let token0_is_weth =
-            token0 == "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".parse::<H160>()?;
-        let token0_decimals = get_decimals(client, token0).await?;
+        let token0_is_weth = token0
+            == "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"
+                .parse::<H160>()
+                .map_err(|err| HindsightError::EvmParseError(err.to_string()))?;
+        let token0_metadata = tokens
+            .get(client, token0)
+            .await
+            .map_err(HindsightError::rpc)?;
+        let token1_metadata = tokens
+            .get(client, token1)
+            .await
+            .map_err(HindsightError::rpc)?;
+        // prices on the fork are scaled by token0's `decimals()` too, so there's no safe default
+        let token0_decimals =
+            token0_metadata
+                .decimals
+                .map(U256::from)
+                .ok_or(HindsightError::DecodeError(format!(
+                    "token {:?} doesn't report its decimals",
+                    token0
+                )))?;
 
         // if a Sync event (UniV2) is detected from the tx logs, it can be used to get the new price
         let sync_log: Option<_> = tx_receipt
             .logs
             .iter()
-            .find(|log| log.topics[0] == sync_topic && log.address == pool_address);
+            .find(|log| log.topics.first() == Some(&sync_topic) && log.address == pool_address);
 
         // derive user's trade amounts & post-tx price from log data
         let (amount0_sent, amount1_sent, new_price) = match pool_variant {
             PoolVariant::UniswapV3 => {
-                let amount0 = I256::from_raw(U256::from_big_endian(&swap_log.data[0..32]));
-                let amount1 = I256::from_raw(U256::from_big_endian(&swap_log.data[32..64]));
-                let sqrt_price = U256::from_big_endian(&swap_log.data[64..96]); // u160
-                let liquidity = U256::from_big_endian(&swap_log.data[96..128]); // u128
-                let new_price = get_price_v3(liquidity, sqrt_price, token0_decimals)?;
+                let amount0 = I256::from_raw(log_word(&swap_log.data, 0)?);
+                let amount1 = I256::from_raw(log_word(&swap_log.data, 1)?);
+                let sqrt_price = log_word(&swap_log.data, 2)?; // u160
+                let liquidity = log_word(&swap_log.data, 3)?; // u128
+                let new_price = get_price_v3(liquidity, sqrt_price, token0_decimals)
+                    .map_err(HindsightError::math)?;
                 (
                     /* amount0_sent */
                     if amount0.le(&0.into()) {
@@ -150,13 +217,14 @@ async fn derive_trade_params(
                 )
             }
             PoolVariant::UniswapV2 => {
-                let amount0_out = I256::from_raw(U256::from_big_endian(&swap_log.data[64..96]));
-                let amount1_out = I256::from_raw(U256::from_big_endian(&swap_log.data[96..128]));
+                let amount0_out = I256::from_raw(log_word(&swap_log.data, 2)?);
+                let amount1_out = I256::from_raw(log_word(&swap_log.data, 3)?);
                 let mut new_price = U256::zero();
                 if let Some(sync_log) = sync_log {
-                    let reserve0 = U256::from_big_endian(&sync_log.data[0..32]);
-                    let reserve1 = U256::from_big_endian(&sync_log.data[32..64]);
-                    new_price = get_price_v2(reserve0, reserve1, token0_decimals)?;
+                    let reserve0 = log_word(&sync_log.data, 0)?;
+                    let reserve1 = log_word(&sync_log.data, 1)?;
+                    new_price = get_price_v2(reserve0, reserve1, token0_decimals)
+                        .map_err(HindsightError::math)?;
                 }
                 (amount0_out, amount1_out, new_price)
             }
@@ -172,7 +240,8 @@ async fn derive_trade_params(
         let token_out = if swap_0_for_1 { token1 } else { token0 };
         // find all pairs that aren't the one that the user swapped on
         let arb_pools: Vec<PairPool> = get_all_trading_pools(client, (token_in, token_out))
-            .await?
+            .await
+            .map_err(HindsightError::rpc)?
             .into_iter()
             .filter(|pool| !pool.address.is_zero())
             .filter(|pool| pool.address != pool_address)
@@ -190,6 +259,16 @@ async fn derive_trade_params(
             tokens: TokenPair {
                 weth: if token0_is_weth { token0 } else { token1 },
                 token: if token0_is_weth { token1 } else { token0 },
+                weth_metadata: Some(if token0_is_weth {
+                    token0_metadata.to_owned()
+                } else {
+                    token1_metadata.to_owned()
+                }),
+                token_metadata: Some(if token0_is_weth {
+                    token1_metadata
+                } else {
+                    token0_metadata
+                }),
             },
         })
     }
@@ -200,9 +279,10 @@ async fn derive_trade_params(
 #[async_recursion]
 #[allow(clippy::too_many_arguments)]
 async fn step_arb(
-    client: WsClient,
+    backend: SimBackendRef,
     user_tx: Transaction,
     block_info: BlockInfo,
+    prefetched: Arc<PrefetchedState>,
     params: UserTradeParams,
     best_amount_in_out: Option<(U256, U256)>,
     range: [U256; 2],
@@ -210,7 +290,7 @@ async fn step_arb(
     depth: Option<usize>,
     start_pair_variant: (Address, PoolVariant),
     end_pair_variant: (Address, PoolVariant),
-) -> Result<(U256, U256)> {
+) -> Result<(U256, U256), HindsightError> {
     info!(
         "step_arb
         best (weth_in, weth_bal)\t{:?}
@@ -235,7 +315,7 @@ async fn step_arb(
     ============================================================  */
     if params.arb_pools.is_empty() {
         // returning an error here will halt the whole sim branch
-        return Err(HindsightError::PoolNotFound(params.pool).into());
+        return Err(HindsightError::PoolNotFound(params.pool));
     }
     // if the ranges get tight enough together, we can quit early
     // we'll call a 0.1% difference "tight enough"
@@ -248,9 +328,10 @@ async fn step_arb(
     */
     if depth.is_none() {
         return step_arb(
-            client,
+            backend,
             user_tx,
             block_info,
+            prefetched,
             params,
             Some((best_amount_in, best_amount_out)),
             range,
@@ -289,21 +370,22 @@ async fn step_arb(
         let amount_in = range[0] + band_width * U256::from(i);
         let user_tx = user_tx.clone();
         let block_info = block_info.clone();
+        let prefetched = prefetched.clone();
         let params = params.clone();
-        let client = client.clone();
+        let backend = backend.clone();
         // spawn the task, hold on to its handle
         handles.push(tokio::task::spawn(async move {
-            let evm = fork_evm(&client, &block_info).await?;
-            sim_arb_single(
-                evm,
-                user_tx,
-                &block_info,
-                &params,
-                amount_in,
-                start_pair_variant,
-                end_pair_variant,
-            )
-            .await
+            backend
+                .sim_arb(
+                    &block_info,
+                    &prefetched,
+                    &user_tx,
+                    &params.tokens,
+                    amount_in,
+                    start_pair_variant,
+                    end_pair_variant,
+                )
+                .await
         }));
     }
 
@@ -315,9 +397,8 @@ async fn step_arb(
     let mut num_reverts = 0;
     // pick best result and update best_amount_in & best_amount_out
     for result in revenues {
-        if let Ok(result) = result {
-            if let Ok(result) = result {
-                let (amount_in, balance_out) = result;
+        match result.map_err(HindsightError::from_join_error)? {
+            Ok((amount_in, balance_out)) => {
                 if balance_out > best_amount_out {
                     best_amount_in = amount_in;
                     best_amount_out = balance_out;
@@ -326,25 +407,22 @@ async fn step_arb(
                         best_amount_in_out
                     );
                 }
-            } else {
-                // TODO: use real error types, not this garbage
-                let err = result.as_ref().unwrap_err().to_string();
-                debug!("{}", err);
-                if err.contains("no other pool found") {
-                    // fail the whole batch by returning this error immediately
-                    return result;
-                } else if err.contains("swap reverted") {
-                    num_reverts += 1;
-                }
             }
-        } else {
-            return Err(anyhow::anyhow!(
-                "system error in step_arb. error in a sim_arb_single result: {}",
-                result.as_ref().unwrap_err().to_string() // TODO: use a more idiomatic approach to returning the error w/ custom tagging data
-            ));
+            Err(HindsightError::SwapReverted(reason)) => {
+                debug!("swap reverted: {}", reason);
+                num_reverts += 1;
+            }
+            // e.g. out of gas; the swap can't be done w/ this amount, same as a revert
+            Err(HindsightError::Halted(reason)) => {
+                debug!("swap halted: {}", reason);
+                num_reverts += 1;
+            }
+            // anything else (pool not found, RPC/EVM/decode errors) means the sim itself failed,
+            // so fail the whole batch by returning the error immediately
+            Err(err) => return Err(err),
         }
         if num_reverts == revenue_len {
-            return Err(anyhow::anyhow!("all swaps reverted"));
+            return Err(HindsightError::AllSwapsReverted);
         }
     }
 
@@ -365,9 +443,10 @@ async fn step_arb(
         },
     ];
     step_arb(
-        client,
+        backend,
         user_tx,
         block_info,
+        prefetched,
         params,
         Some((best_amount_in, best_amount_out)),
         range,
@@ -382,12 +461,30 @@ async fn step_arb(
 /// Find the optimal backrun for a given tx.
 pub async fn find_optimal_backrun_amount_in_out(
     client: &WsClient,
+    tokens: &TokenCache,
     user_tx: Transaction,
     event: &EventHistory,
     block_info: &BlockInfo,
-) -> Result<Vec<SimArbResult>> {
+) -> Result<Vec<SimArbResult>, HindsightError> {
+    let params = derive_trade_params(client, tokens, user_tx.to_owned(), event).await?;
+    let prefetched = prefetch_state(client, &params, block_info).await?;
+    let backend: SimBackendRef = Arc::new(RevmBackend::new(client.clone()));
+    find_optimal_backrun_for_params(&backend, user_tx, params, block_info, Arc::new(prefetched))
+        .await
+}
+
+/// Find the optimal backrun for a given tx, using trade params already derived
+/// with `derive_trade_params`, and the state they touch (see `prefetch_state`).
+///
+/// Simulations run on `backend`.
+pub async fn find_optimal_backrun_for_params(
+    backend: &SimBackendRef,
+    user_tx: Transaction,
+    params: Vec<UserTradeParams>,
+    block_info: &BlockInfo,
+    prefetched: Arc<PrefetchedState>,
+) -> Result<Vec<SimArbResult>, HindsightError> {
     let start_balance = braindance_starting_balance();
-    let params = derive_trade_params(client, user_tx.to_owned(), event).await?;
     info!("params {:?}", params);
 
     // look at price (TKN/ETH) on each exchange to determine which exchange to arb on
@@ -428,45 +525,31 @@ pub async fn find_optimal_backrun_amount_in_out(
             continue;
         }
         for other_pool in params.arb_pools.iter().copied() {
-            let client = client.clone();
+            let backend = backend.clone();
             let user_tx = user_tx.clone();
             let block_info = block_info.clone();
+            let prefetched = prefetched.clone();
             let params = params.clone();
             /* SPAWN A NEW (GREEN) THREAD */
             let handle = tokio::task::spawn(async move {
-                let mut evm = fork_evm(&client, &block_info)
-                    .await
-                    .expect("failed to fork evm");
-
                 // find price on other exchange
-                let alt_price = match other_pool.variant {
-                    PoolVariant::UniswapV2 => sim_price_v2(
-                        other_pool.address,
+                let alt_price = backend
+                    .pool_price(
+                        &block_info,
+                        &prefetched,
+                        (other_pool.address, other_pool.variant),
                         params.token_in,
                         params.token_out,
-                        &mut evm,
                     )
                     .await
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "sim_price_v2 panicked. address={:?} token_in={:?} token_out={:?}",
-                            other_pool.address, params.token_in, params.token_out
-                        )
-                    }),
-                    PoolVariant::UniswapV3 => sim_price_v3(
-                        other_pool.address,
-                        params.token_in,
-                        params.token_out,
-                        &mut evm,
-                    )
-                    .await
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "sim_price_v3 panicked. address={:?} token_in={:?} token_out={:?}",
-                            other_pool.address, params.token_in, params.token_out
-                        )
-                    }),
-                };
+                    .map_err(|err| {
+                        // one weird pool shouldn't fail the other pools, so just report it
+                        debug!(
+                            "failed to price pool. address={:?} token_in={:?} token_out={:?}: {}",
+                            other_pool.address, params.token_in, params.token_out, err
+                        );
+                        err
+                    })?;
                 debug!("alt price {:?}", alt_price);
 
                 let (start_pool, start_pool_variant, end_pool, end_pool_variant) =
@@ -511,9 +594,10 @@ pub async fn find_optimal_backrun_amount_in_out(
 
                 // a new EVM is spawned inside this function, where the user tx is executed on a fresh fork before our backrun
                 let res = step_arb(
-                    client.clone(),
+                    backend.clone(),
                     user_tx,
                     block_info,
+                    prefetched,
                     params.to_owned(),
                     None,
                     initial_range,
@@ -524,104 +608,161 @@ pub async fn find_optimal_backrun_amount_in_out(
                 )
                 .await;
                 debug!("*** step_arb complete: {:?}", res);
-                if let Ok(res) = res {
-                    Some(SimArbResult {
-                        user_trade: params,
-                        backrun_trade: BackrunResult {
-                            amount_in: res.0,
-                            balance_end: res.1,
-                            profit: if res.1 > start_balance {
-                                res.1 - start_balance
-                            } else {
-                                0.into()
-                            },
-                            start_pool,
-                            end_pool,
-                            start_variant: start_pool_variant,
-                            end_variant: end_pool_variant,
+                res.map(|res| SimArbResult {
+                    user_trade: params,
+                    backrun_trade: BackrunResult {
+                        amount_in: res.0,
+                        balance_end: res.1,
+                        profit: if res.1 > start_balance {
+                            res.1 - start_balance
+                        } else {
+                            0.into()
                         },
-                    })
-                } else {
-                    None
-                }
+                        start_pool,
+                        end_pool,
+                        start_variant: start_pool_variant,
+                        end_variant: end_pool_variant,
+                    },
+                })
             });
             pool_handles.push(handle);
         }
     }
 
-    // Collect all the results for this batch, filter out any errors or empty results before returning.
-    let results: Vec<_> = future::join_all(pool_handles).await;
-    Ok(results
-        .into_iter()
-        .filter_map(|res| res.ok())
-        .flatten()
-        .collect::<Vec<_>>())
-}
-
-/// Simulate a two-step arbitrage on a forked EVM with fixed trade amount & path.
-///
-/// 1. Buy `amount_in` WETH worth of token on start_pair
-///
-/// 2. Sell balance of token on end_pair for WETH, completing the arb.
-async fn sim_arb_single(
-    mut evm: EVM<ForkDB>,
-    user_tx: Transaction,
-    block_info: &BlockInfo,
-    params: &UserTradeParams,
-    amount_in: U256,
-    start_pair_variant: (Address, PoolVariant),
-    end_pair_variant: (Address, PoolVariant),
-) -> Result<(U256, U256)> {
-    let (start_pool, start_variant) = start_pair_variant;
-    let (end_pool, end_variant) = end_pair_variant;
-    sim_bundle(&mut evm, vec![user_tx.to_owned()]).await?;
-
-    /*
-    - if the price is denoted in TKN/ETH, we want to buy where the price is highest
-    - if the price is denoted in ETH/TKN, we want to buy where the price is lowest
-    - price is always denoted in tkn1/tkn0
-    */
-
-    /* Buy tokens on one exchange. */
-    let res = commit_braindance_swap(
-        &mut evm,
-        start_variant,
-        amount_in,
-        start_pool,
-        params.tokens.weth,
-        params.tokens.token,
-        block_info.base_fee,
-        None,
-    );
-    debug!("braindance 1 completed. {:?}", res);
-    let amount_received = res.unwrap_or(0.into());
-    debug!("amount received {:?}", amount_received);
-
-    /* Sell them on other exchange. */
-    let res = commit_braindance_swap(
-        &mut evm,
-        end_variant,
-        amount_received,
-        end_pool,
-        params.tokens.token,
-        params.tokens.weth,
-        block_info.base_fee + (block_info.base_fee * 2500) / 10000,
-        None,
-    )?;
-    debug!("braindance 2 completed. {:?}", res);
-    Ok((amount_in, res))
+    // Collect all the results for this batch. Pools that failed are only an error
+    // if no pool succeeded; then the first failure is returned as the reason.
+    let num_pools = pool_handles.len();
+    let mut results = vec![];
+    let mut failures = vec![];
+    for res in future::join_all(pool_handles).await {
+        match res.map_err(HindsightError::from_join_error) {
+            Ok(Ok(res)) => results.push(res),
+            Ok(Err(err)) | Err(err) => failures.push(err),
+        }
+    }
+    if !failures.is_empty() {
+        debug!(
+            "{}/{} pools failed: {:?}",
+            failures.len(),
+            num_pools,
+            failures
+        );
+    }
+    if results.is_empty() && !failures.is_empty() {
+        return Err(failures.remove(0));
+    }
+    Ok(results)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::sim::evm::{commit_braindance_swap, sim_bundle};
     use crate::util::{get_all_trading_pools, get_block_info, test::get_test_ws_client, ETH};
     use anyhow::Result;
     use ethers::providers::Middleware;
 
+    /// Fails every sim w/ the error returned by `error`.
+    #[derive(Debug)]
+    struct FailingBackend(fn() -> HindsightError);
+
+    #[async_trait::async_trait]
+    impl crate::sim::backend::SimBackend for FailingBackend {
+        fn kind(&self) -> crate::sim::backend::SimBackendKind {
+            crate::sim::backend::SimBackendKind::Revm
+        }
+
+        async fn pool_price(
+            &self,
+            _block_info: &BlockInfo,
+            _prefetched: &PrefetchedState,
+            _pool: (Address, PoolVariant),
+            _token_in: Address,
+            _token_out: Address,
+        ) -> Result<U256, HindsightError> {
+            Err((self.0)())
+        }
+
+        async fn sim_arb(
+            &self,
+            _block_info: &BlockInfo,
+            _prefetched: &PrefetchedState,
+            _user_tx: &Transaction,
+            _tokens: &TokenPair,
+            _amount_in: U256,
+            _start_pool: (Address, PoolVariant),
+            _end_pool: (Address, PoolVariant),
+        ) -> Result<(U256, U256), HindsightError> {
+            Err((self.0)())
+        }
+    }
+
+    async fn step_arb_failing(
+        error: fn() -> HindsightError,
+    ) -> Result<(U256, U256), HindsightError> {
+        let pool = (Address::random(), PoolVariant::UniswapV2);
+        let params = UserTradeParams {
+            pool_variant: pool.1,
+            token_in: Address::random(),
+            token_out: Address::random(),
+            amount0_sent: I256::zero(),
+            amount1_sent: I256::zero(),
+            token0_is_weth: true,
+            pool: pool.0,
+            price: U256::zero(),
+            tokens: TokenPair {
+                weth: Address::random(),
+                token: Address::random(),
+                weth_metadata: None,
+                token_metadata: None,
+            },
+            arb_pools: vec![PairPool {
+                variant: PoolVariant::UniswapV3,
+                address: Address::random(),
+            }],
+        };
+        step_arb(
+            Arc::new(FailingBackend(error)),
+            Transaction::default(),
+            BlockInfo {
+                number: 1.into(),
+                timestamp: 1.into(),
+                base_fee: 1.into(),
+            },
+            Arc::new(PrefetchedState::default()),
+            params,
+            None,
+            [0.into(), braindance_starting_balance()],
+            STEP_INTERVALS,
+            None,
+            pool,
+            (Address::random(), PoolVariant::UniswapV3),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_treats_halts_like_reverts() {
+        let res = step_arb_failing(|| HindsightError::Halted("OutOfGas".to_owned())).await;
+        assert!(
+            matches!(res, Err(HindsightError::AllSwapsReverted)),
+            "{:?}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_sim_errors() {
+        let res =
+            step_arb_failing(|| HindsightError::RpcError("connection dropped".to_owned())).await;
+        assert!(matches!(res, Err(HindsightError::RpcError(_))), "{:?}", res);
+        let res = step_arb_failing(|| HindsightError::EvmError("missing state".to_owned())).await;
+        assert!(matches!(res, Err(HindsightError::EvmError(_))), "{:?}", res);
+    }
+
     async fn setup_test_evm(client: &WsClient, block_num: u64) -> Result<EVM<ForkDB>> {
         let block_info = get_block_info(client, block_num).await?;
-        fork_evm(client, &block_info).await
+        Ok(fork_evm(client, &block_info).await?)
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
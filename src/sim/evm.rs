@@ -1,5 +1,9 @@
 use crate::{
-    debug, error::HindsightError, interfaces::PoolVariant, util::get_price_v3, Error, Result,
+    debug,
+    error::{HindsightError, RevertReason},
+    interfaces::PoolVariant,
+    util::{get_price_v2, get_price_v3},
+    Result,
 };
 use ethers::{
     abi::{self, ParamType},
@@ -13,26 +17,28 @@ use revm::{
 use rusty_sando::{
     prelude::fork_db::ForkDB,
     simulate::{braindance_address, braindance_controller_address},
-    types::SimulationError,
     utils::{constants::get_eth_dev, tx_builder::braindance},
 };
-use std::{ops::Mul, str::FromStr};
+use std::str::FromStr;
 
-/// Execute a braindance swap on the forked EVM, commiting its state changes to the EVM's ForkDB.
-///
-/// Returns balance of token_out after tx is executed.
-#[allow(clippy::too_many_arguments)]
-pub fn commit_braindance_swap(
-    evm: &mut EVM<ForkDB>,
+/// Selector of `slot0()` (UniswapV3 pools).
+pub(crate) const SLOT0_SELECTOR: &str = "0x3850c7bd";
+/// Selector of `liquidity()` (UniswapV3 pools).
+pub(crate) const LIQUIDITY_SELECTOR: &str = "0x1a686502";
+/// Selector of `getReserves()` (UniswapV2 pairs).
+pub(crate) const GET_RESERVES_SELECTOR: &str = "0x0902f1ac";
+/// Selector of `decimals()` (ERC20 tokens).
+pub(crate) const DECIMALS_SELECTOR: &str = "0x313ce567";
+
+/// Calldata for a braindance swap of `amount_in` `token_in` for `token_out` on `target_pool`.
+pub(crate) fn braindance_swap_data(
     pool_variant: PoolVariant,
     amount_in: U256,
     target_pool: Address,
     token_in: Address,
     token_out: Address,
-    base_fee: U256,
-    _nonce: Option<u64>,
-) -> Result<U256> {
-    let swap_data = match pool_variant {
+) -> Bytes {
+    match pool_variant {
         PoolVariant::UniswapV2 => {
             braindance::build_swap_v2_data(amount_in, target_pool, token_in, token_out)
         }
@@ -42,7 +48,38 @@ pub fn commit_braindance_swap(
             token_in,
             token_out,
         ),
+    }
+}
+
+/// Decodes the output of a braindance swap; returns the balance of token_out after the swap.
+pub(crate) fn decode_braindance_swap(
+    pool_variant: PoolVariant,
+    output: Bytes,
+) -> Result<U256, HindsightError> {
+    let (_amount_out, balance) = match pool_variant {
+        PoolVariant::UniswapV2 => braindance::decode_swap_v2_result(output)
+            .map_err(|err| HindsightError::DecodeError(format!("swap result: {:?}", err)))?,
+        PoolVariant::UniswapV3 => braindance::decode_swap_v3_result(output)
+            .map_err(|err| HindsightError::DecodeError(format!("swap result: {:?}", err)))?,
     };
+    Ok(balance)
+}
+
+/// Execute a braindance swap on the forked EVM, commiting its state changes to the EVM's ForkDB.
+///
+/// Returns balance of token_out after tx is executed.
+#[allow(clippy::too_many_arguments)]
+pub fn commit_braindance_swap(
+    evm: &mut EVM<ForkDB>,
+    pool_variant: PoolVariant,
+    amount_in: U256,
+    target_pool: Address,
+    token_in: Address,
+    token_out: Address,
+    base_fee: U256,
+    _nonce: Option<u64>,
+) -> Result<U256, HindsightError> {
+    let swap_data = braindance_swap_data(pool_variant, amount_in, target_pool, token_in, token_out);
 
     evm.env.tx.caller = braindance_controller_address();
     evm.env.tx.transact_to = TransactTo::Call(braindance_address().0.into());
@@ -51,37 +88,49 @@ pub fn commit_braindance_swap(
     evm.env.tx.gas_price = base_fee.into();
     evm.env.tx.value = rU256::ZERO;
 
-    let res = match evm.transact_commit() {
-        Ok(res) => res,
-        Err(e) => return Err(anyhow::anyhow!("failed to commit swap: {:?}", e)),
-    };
+    let res = evm
+        .transact_commit()
+        .map_err(|err| HindsightError::EvmError(format!("failed to commit swap: {:?}", err)))?;
     let output = match res {
         ExecutionResult::Success { output, .. } => match output {
             Output::Call(o) => o,
             Output::Create(o, _) => o,
         },
-        ExecutionResult::Revert { output, gas_used } => {
-            return Err(anyhow::anyhow!(
-                "swap reverted: {:?} (gas used: {:?})",
-                output,
-                gas_used
-            ))
+        ExecutionResult::Revert { output, .. } => {
+            return Err(HindsightError::SwapReverted(RevertReason::decode(&output)))
         }
         ExecutionResult::Halt { reason, .. } => {
-            return Err(anyhow::anyhow!("swap halted: {:?}", reason))
+            return Err(HindsightError::Halted(format!("{:?}", reason)))
         }
     };
-    let (_amount_out, balance) = match pool_variant {
-        PoolVariant::UniswapV2 => match braindance::decode_swap_v2_result(output.into()) {
-            Ok(output) => output,
-            Err(e) => return Err(anyhow::anyhow!("failed to decode swap result: {:?}", e)),
-        },
-        PoolVariant::UniswapV3 => match braindance::decode_swap_v3_result(output.into()) {
-            Ok(output) => output,
-            Err(e) => return Err(anyhow::anyhow!("failed to decode swap result: {:?}", e)),
+    decode_braindance_swap(pool_variant, output.into())
+}
+
+/// Returns the output of a successful execution, or the reason it failed.
+fn call_output(result: ExecutionResult) -> Result<Bytes, HindsightError> {
+    match result {
+        ExecutionResult::Success { output, .. } => match output {
+            Output::Call(o) => Ok(o.into()),
+            Output::Create(o, _) => Ok(o.into()),
         },
-    };
-    Ok(balance)
+        ExecutionResult::Revert { output, .. } => {
+            Err(HindsightError::CallReverted(RevertReason::decode(&output)))
+        }
+        ExecutionResult::Halt { reason, .. } => {
+            Err(HindsightError::Halted(format!("{:?}", reason)))
+        }
+    }
+}
+
+/// Decodes a single uint from `tokens[index]`.
+fn decode_uint(tokens: &[abi::Token], index: usize, name: &str) -> Result<U256, HindsightError> {
+    tokens
+        .get(index)
+        .and_then(|token| token.clone().into_uint())
+        .ok_or(HindsightError::DecodeError(format!(
+            "failed to decode {} (tokens={:?})",
+            name, tokens
+        )))
 }
 
 /// returns price of token1/token0 in forked EVM.
@@ -90,7 +139,7 @@ pub async fn sim_price_v3(
     input_token: Address,
     output_token: Address,
     evm: &mut EVM<ForkDB>,
-) -> Result<U256> {
+) -> Result<U256, HindsightError> {
     abigen!(
         IUniswapV3Pool,
         r#"[
@@ -98,7 +147,34 @@ pub async fn sim_price_v3(
         ]"#
     );
 
-    let output = call_function(evm, "0x3850c7bd", target_pool)?; // slot0()
+    let slot0 = call_function(evm, SLOT0_SELECTOR, target_pool)?;
+    let liquidity = call_function(evm, LIQUIDITY_SELECTOR, target_pool)?;
+    let decimals = call_function(evm, DECIMALS_SELECTOR, token0_of(input_token, output_token))?;
+    decode_price_v3(&slot0, &liquidity, &decimals)
+}
+
+/// Returns whichever of the two tokens is token0 of their pools.
+pub(crate) fn token0_of(token_a: Address, token_b: Address) -> Address {
+    if token_a < token_b {
+        token_a
+    } else {
+        token_b
+    }
+}
+
+/// Decodes token0's decimals from the output of `decimals()`.
+fn decode_decimals(output: &[u8]) -> Result<U256, HindsightError> {
+    let token0_decimals_tokens = abi::decode(&[ParamType::Uint(8)], output)?;
+    decode_uint(&token0_decimals_tokens, 0, "token0_decimals")
+}
+
+/// Price (token1/token0) of a UniswapV3 pool, from the outputs of `slot0()` & `liquidity()`
+/// on the pool, and `decimals()` on token0.
+pub(crate) fn decode_price_v3(
+    slot0: &[u8],
+    liquidity: &[u8],
+    token0_decimals: &[u8],
+) -> Result<U256, HindsightError> {
     let slot0_tokens = abi::decode(
         &vec![
             ParamType::Uint(160), // sqrtPriceX96
@@ -109,26 +185,14 @@ pub async fn sim_price_v3(
             ParamType::Uint(8),   // feeProtocol
             ParamType::Bool,      // unlocked
         ],
-        &output,
+        slot0,
     )?;
-    let sqrt_price = slot0_tokens[0].clone().into_uint().expect("sqrt_price");
+    let sqrt_price = decode_uint(&slot0_tokens, 0, "sqrt_price")?;
+    let liquidity_tokens = abi::decode(&[ParamType::Uint(128)], liquidity)?;
+    let liquidity = decode_uint(&liquidity_tokens, 0, "liquidity")?;
+    let token0_decimals = decode_decimals(token0_decimals)?;
 
-    let output = call_function(evm, "0x1a686502", target_pool)?; // liquidity()
-    let liquidity_tokens = abi::decode(&[ParamType::Uint(128)], &output)?;
-    let liquidity = liquidity_tokens[0].clone().into_uint().expect("liquidity");
-
-    let token0 = match input_token < output_token {
-        true => input_token,
-        false => output_token,
-    };
-    let output = call_function(evm, "0x313ce567", token0)?; // decimals()
-    let token0_decimals_tokens = abi::decode(&[ParamType::Uint(8)], &output)?;
-    let token0_decimals = token0_decimals_tokens[0]
-        .clone()
-        .into_uint()
-        .expect("token0_decimals");
-
-    get_price_v3(liquidity, sqrt_price, token0_decimals)
+    get_price_v3(liquidity, sqrt_price, token0_decimals).map_err(HindsightError::math)
 }
 
 /// returns price of token1/token0 in forked EVM.
@@ -137,80 +201,55 @@ pub async fn sim_price_v2(
     input_token: Address,
     output_token: Address,
     evm: &mut EVM<ForkDB>,
-) -> Result<U256> {
+) -> Result<U256, HindsightError> {
     // getReserves
     evm.env.tx.transact_to = TransactTo::Call(target_pool.0.into());
     evm.env.tx.caller = get_eth_dev().0.into();
     evm.env.tx.value = rU256::ZERO;
-    evm.env.tx.data = Bytes::from_str("0x0902f1ac")?.0; // getReserves()
+    evm.env.tx.data = bytes_from_str(GET_RESERVES_SELECTOR)?.0;
     evm.env.tx.gas_price = rU256::from(100_000_000_000_i64);
     evm.env.tx.gas_limit = 900_000_u64;
     evm.env.tx.gas_priority_fee = Some(rU256::from(13_000_000_000_u64));
-    let result = match evm.transact_ref() {
-        Ok(result) => result.result,
-        Err(e) => return Err(anyhow::format_err!(SimulationError::EvmError(e))),
-    };
-    let output: Bytes = match result {
-        ExecutionResult::Success { output, .. } => match output {
-            Output::Call(o) => o.into(),
-            Output::Create(o, _) => o.into(),
-        },
-        ExecutionResult::Revert { output, .. } => {
-            return Err(anyhow::format_err!(SimulationError::EvmReverted(output)))
-        }
-        ExecutionResult::Halt { reason, .. } => {
-            return Err(anyhow::format_err!(SimulationError::EvmHalted(reason)))
-        }
-    };
+    let result = evm
+        .transact_ref()
+        .map_err(|err| HindsightError::EvmError(format!("{:?}", err)))?
+        .result;
+    let reserves = call_output(result)?;
+    let decimals = call_function(evm, DECIMALS_SELECTOR, token0_of(input_token, output_token))?;
+    decode_price_v2(&reserves, &decimals)
+}
 
+/// Price (token1/token0) of a UniswapV2 pair, from the outputs of `getReserves()` on the pair,
+/// and `decimals()` on token0.
+pub(crate) fn decode_price_v2(
+    reserves: &[u8],
+    token0_decimals: &[u8],
+) -> Result<U256, HindsightError> {
     let tokens = abi::decode(
         &[
             ParamType::Uint(128),
             ParamType::Uint(128),
             ParamType::Uint(32),
         ],
-        &output,
+        reserves,
     )?;
 
-    let reserves_0 = tokens[0].clone().into_uint().ok_or::<Error>(
-        HindsightError::MathError(format!(
-            "reserves_0 failed to cast token to uint (token={})",
-            tokens[0]
-        ))
-        .into(),
-    )?;
-    let reserves_1 = tokens[1].clone().into_uint().ok_or::<Error>(
-        HindsightError::MathError(format!(
-            "reserves_1 failed to cast token to uint (token={})",
-            tokens[1]
-        ))
-        .into(),
-    )?;
+    let reserves_0 = decode_uint(&tokens, 0, "reserves_0")?;
+    let reserves_1 = decode_uint(&tokens, 1, "reserves_1")?;
+    let token0_decimals = decode_decimals(token0_decimals)?;
 
-    let token0 = match input_token < output_token {
-        true => input_token,
-        false => output_token,
-    };
-    let output = call_function(evm, "0x313ce567", token0)?; // decimals()
-    let token0_decimals_tokens = abi::decode(&[ParamType::Uint(8)], &output)?;
-    let token0_decimals = token0_decimals_tokens[0]
-        .clone()
-        .into_uint()
-        .ok_or::<Error>(HindsightError::CallError("token decimals not found".to_owned()).into())?;
+    get_price_v2(reserves_0, reserves_1, token0_decimals).map_err(HindsightError::math)
+}
 
-    reserves_1
-        .mul(U256::from(10).pow(token0_decimals))
-        .checked_div(reserves_0)
-        .ok_or::<Error>(
-            HindsightError::MathError(format!(
-                "failed to divide reserves (reserves_0, reserves_1)=({},{})",
-                reserves_0, reserves_1
-            ))
-            .into(),
-        )
+pub(crate) fn bytes_from_str(data: &str) -> Result<Bytes, HindsightError> {
+    Bytes::from_str(data).map_err(|err| HindsightError::EvmParseError(err.to_string()))
 }
 
-pub fn call_function(evm: &mut EVM<ForkDB>, method: &str, contract: Address) -> Result<Bytes> {
+pub fn call_function(
+    evm: &mut EVM<ForkDB>,
+    method: &str,
+    contract: Address,
+) -> Result<Bytes, HindsightError> {
     debug!("calling method {:?}", method);
     let tx: TransactionRequest = TransactionRequest {
         from: Some(get_eth_dev()),
@@ -218,64 +257,53 @@ pub fn call_function(evm: &mut EVM<ForkDB>, method: &str, contract: Address) ->
         gas: Some(U256::from(900_000_u64)),
         gas_price: Some(U256::from(1_000_000_000_000_u64)),
         value: None,
-        data: Some(Bytes::from_str(method)?),
+        data: Some(bytes_from_str(method)?),
         nonce: None,
         chain_id: Some(U64::from(1)),
     };
     sim_tx_request(evm, tx)
 }
 
-pub fn sim_tx_request(evm: &mut EVM<ForkDB>, tx: TransactionRequest) -> Result<Bytes> {
+pub fn sim_tx_request(
+    evm: &mut EVM<ForkDB>,
+    tx: TransactionRequest,
+) -> Result<Bytes, HindsightError> {
     evm.env.tx.caller = B160::from(tx.from.unwrap_or(get_eth_dev()));
     evm.env.tx.transact_to = TransactTo::Call(B160::from(
         tx.to
             .to_owned()
-            .ok_or::<Error>(
-                HindsightError::EvmParseError(format!("tx.to invalid ({:?})", tx.to)).into(),
-            )?
+            .ok_or(HindsightError::EvmParseError(format!(
+                "tx.to invalid ({:?})",
+                tx.to
+            )))?
             .as_address()
-            .ok_or::<Error>(
-                // TODO: find cleaner way to do this
-                HindsightError::EvmParseError(format!(
-                    "tx.to could not parse address ({:?})",
-                    tx.to
-                ))
-                .into(),
-            )?
+            .ok_or(HindsightError::EvmParseError(format!(
+                "tx.to could not parse address ({:?})",
+                tx.to
+            )))?
             .0,
     ));
     evm.env.tx.data = tx
         .data
         .to_owned()
-        .ok_or::<Error>(
-            HindsightError::EvmParseError(format!("tx.data invalid ({:?})", tx.data)).into(),
-        )?
+        .ok_or(HindsightError::EvmParseError(format!(
+            "tx.data invalid ({:?})",
+            tx.data
+        )))?
         .0;
     evm.env.tx.value = tx.value.unwrap_or_default().into();
     evm.env.tx.gas_price = tx.gas_price.unwrap_or_default().into();
     evm.env.tx.gas_limit = tx.gas.unwrap_or_default().as_u64();
-    let res = match evm.transact_ref() {
-        Ok(res) => res.result,
-        Err(err) => {
-            return Err(anyhow::anyhow!("failed to simulate tx request: {:?}", err));
-        }
-    };
-    let output: Bytes = match res {
-        ExecutionResult::Success { output, .. } => match output {
-            Output::Call(o) => o.into(),
-            Output::Create(o, _) => o.into(),
-        },
-        ExecutionResult::Revert { output, .. } => {
-            return Err(anyhow::format_err!(SimulationError::EvmReverted(output)))
-        }
-        ExecutionResult::Halt { reason, .. } => {
-            return Err(anyhow::format_err!(SimulationError::EvmHalted(reason)))
-        }
-    };
-    Ok(output)
+    let res = evm
+        .transact_ref()
+        .map_err(|err| {
+            HindsightError::EvmError(format!("failed to simulate tx request: {:?}", err))
+        })?
+        .result;
+    call_output(res)
 }
 
-fn inject_tx(evm: &mut EVM<ForkDB>, tx: &Transaction) -> Result<()> {
+fn inject_tx(evm: &mut EVM<ForkDB>, tx: &Transaction) -> Result<(), HindsightError> {
     evm.env.tx.caller = B160::from(tx.from);
     evm.env.tx.transact_to = TransactTo::Call(B160::from(tx.to.unwrap_or_default().0));
     evm.env.tx.data = tx.input.to_owned().0;
@@ -305,7 +333,7 @@ fn inject_tx(evm: &mut EVM<ForkDB>, tx: &Transaction) -> Result<()> {
 pub async fn sim_bundle(
     evm: &mut EVM<ForkDB>,
     signed_txs: Vec<Transaction>,
-) -> Result<Vec<ExecutionResult>> {
+) -> Result<Vec<ExecutionResult>, HindsightError> {
     let mut results = vec![];
     for tx in signed_txs {
         let res = commit_tx(evm, tx).await;
@@ -318,16 +346,26 @@ pub async fn sim_bundle(
 }
 
 /// Execute a transaction on the forked EVM, commiting its state changes to the EVM's ForkDB.
-pub async fn commit_tx(evm: &mut EVM<ForkDB>, tx: Transaction) -> Result<ExecutionResult> {
+pub async fn commit_tx(
+    evm: &mut EVM<ForkDB>,
+    tx: Transaction,
+) -> Result<ExecutionResult, HindsightError> {
     inject_tx(evm, &tx)?;
     let res = evm.transact_commit();
-    res.map_err(|err| anyhow::anyhow!("failed to simulate tx {:?}: {:?}", tx.hash, err))
+    res.map_err(|err| {
+        HindsightError::EvmError(format!("failed to simulate tx {:?}: {:?}", tx.hash, err))
+    })
 }
 
-pub async fn call_tx(evm: &mut EVM<ForkDB>, tx: Transaction) -> Result<ResultAndState> {
+pub async fn call_tx(
+    evm: &mut EVM<ForkDB>,
+    tx: Transaction,
+) -> Result<ResultAndState, HindsightError> {
     inject_tx(evm, &tx)?;
     let res = evm.transact();
-    res.map_err(|err| anyhow::anyhow!("failed to simulate tx {:?}: {:?}", tx.hash, err))
+    res.map_err(|err| {
+        HindsightError::EvmError(format!("failed to simulate tx {:?}: {:?}", tx.hash, err))
+    })
 }
 
 #[cfg(test)]
@@ -335,6 +373,7 @@ mod tests {
     use std::str::FromStr;
 
     use crate::{
+        error::HindsightError,
         sim::core::fork_evm,
         util::{get_block_info, test::get_test_ws_client},
         Result,
@@ -344,6 +383,34 @@ mod tests {
         types::{Address, U256},
     };
 
+    #[test]
+    fn it_errors_instead_of_overflowing_prices() {
+        let encode = |values: &[U256]| {
+            ethers::abi::encode(
+                &values
+                    .iter()
+                    .map(|value| ethers::abi::Token::Uint(*value))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let max_reserves = U256::from(2).pow(112.into()) - 1;
+        let reserves = encode(&[max_reserves, max_reserves, 0.into()]);
+        assert_eq!(
+            super::decode_price_v2(&reserves, &encode(&[18.into()])).ok(),
+            Some(U256::exp10(18))
+        );
+        // 10^60 fits, but not once it's multiplied by the reserves
+        assert!(matches!(
+            super::decode_price_v2(&reserves, &encode(&[60.into()])),
+            Err(HindsightError::MathError(_))
+        ));
+        // 10^255 doesn't fit at all
+        assert!(matches!(
+            super::decode_price_v2(&reserves, &encode(&[255.into()])),
+            Err(HindsightError::MathError(_))
+        ));
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn it_gets_sim_price_v2() -> Result<()> {
         let client = get_test_ws_client().await?;
@@ -1,3 +1,6 @@
+pub mod backend;
 pub mod core;
 pub mod evm;
+pub mod prefetch;
+pub mod pricing;
 pub mod processor;
@@ -0,0 +1,181 @@
+use crate::{
+    debug,
+    error::HindsightError,
+    interfaces::{PoolVariant, UserTradeParams},
+    util::WsClient,
+};
+use ethers::{
+    providers::Middleware,
+    types::{Address, BlockId, BlockNumber, H256},
+};
+use futures::future;
+use revm::{
+    db::{CacheDB, EmptyDB},
+    primitives::{AccountInfo, Bytecode, B160, B256, U256 as rU256},
+};
+use rusty_sando::types::BlockInfo;
+use std::collections::BTreeMap;
+
+/// Storage slots read when simulating a swap on a UniswapV2 pair:
+/// factory, token0, token1, reserves, price0/1CumulativeLast, kLast & the reentrancy lock.
+const V2_POOL_SLOTS: [u64; 8] = [5, 6, 7, 8, 9, 10, 11, 12];
+/// Storage slots read when simulating a swap on a UniswapV3 pool:
+/// slot0, feeGrowthGlobal0/1X128, protocolFees & liquidity.
+/// Ticks live in mappings, so they're still loaded lazily.
+const V3_POOL_SLOTS: [u64; 5] = [0, 1, 2, 3, 4];
+
+/// An account & some of its storage, read at the fork block.
+#[derive(Clone, Debug)]
+struct PrefetchedAccount {
+    address: Address,
+    info: AccountInfo,
+    storage: Vec<(rU256, rU256)>,
+}
+
+/// State of the pools & tokens a tx's backruns will touch, loaded in bulk before simulating.
+///
+/// Without it, every forked EVM lazily loads each account & slot it reads, one round trip
+/// at a time, and `step_arb` forks many EVMs per tx.
+#[derive(Clone, Debug, Default)]
+pub struct PrefetchedState {
+    accounts: Vec<PrefetchedAccount>,
+}
+
+impl PrefetchedState {
+    /// Number of accounts loaded.
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Loads the prefetched accounts & storage into `db`.
+    pub fn apply(&self, db: &mut CacheDB<EmptyDB>) {
+        for account in &self.accounts {
+            let address = B160::from(account.address.0);
+            db.insert_account_info(address, account.info.to_owned());
+            for (slot, value) in &account.storage {
+                db.insert_account_storage(address, *slot, *value)
+                    .unwrap_or_else(|never| match never {});
+            }
+        }
+    }
+}
+
+/// Returns the accounts (and their known storage slots) that backruns for `params` will touch.
+fn accounts_to_prefetch(params: &[UserTradeParams]) -> BTreeMap<Address, Vec<u64>> {
+    let pool_slots = |variant: PoolVariant| match variant {
+        PoolVariant::UniswapV2 => V2_POOL_SLOTS.to_vec(),
+        PoolVariant::UniswapV3 => V3_POOL_SLOTS.to_vec(),
+    };
+    let mut accounts = BTreeMap::new();
+    for params in params {
+        accounts.insert(params.pool, pool_slots(params.pool_variant));
+        for pool in &params.arb_pools {
+            accounts.insert(pool.address, pool_slots(pool.variant));
+        }
+        // token balances live in mappings, so only the token contracts themselves are loaded
+        accounts.entry(params.tokens.weth).or_default();
+        accounts.entry(params.tokens.token).or_default();
+    }
+    accounts
+}
+
+async fn fetch_account(
+    client: &WsClient,
+    address: Address,
+    slots: Vec<u64>,
+    block: BlockId,
+) -> Result<PrefetchedAccount, HindsightError> {
+    let slots = slots
+        .into_iter()
+        .map(H256::from_low_u64_be)
+        .collect::<Vec<_>>();
+    let (proof, code) = future::try_join(
+        client.get_proof(address, slots.to_owned(), Some(block)),
+        client.get_code(address, Some(block)),
+    )
+    .await?;
+    // storage proofs come back in the order the slots were requested
+    let storage = slots
+        .iter()
+        .zip(proof.storage_proof)
+        .map(|(slot, proof)| {
+            (
+                rU256::from_be_bytes(slot.to_fixed_bytes()),
+                proof.value.into(),
+            )
+        })
+        .collect();
+    Ok(PrefetchedAccount {
+        address,
+        info: AccountInfo {
+            balance: proof.balance.into(),
+            nonce: proof.nonce.as_u64(),
+            code_hash: B256::from(proof.code_hash.0),
+            code: Some(Bytecode::new_raw(code.0)),
+        },
+        storage,
+    })
+}
+
+/// Loads the pools & tokens that backruns for `params` will touch, as of `block_info`,
+/// with all requests in flight at once.
+pub async fn prefetch_state(
+    client: &WsClient,
+    params: &[UserTradeParams],
+    block_info: &BlockInfo,
+) -> Result<PrefetchedState, HindsightError> {
+    let block = BlockId::Number(BlockNumber::Number(block_info.number));
+    let accounts = future::try_join_all(
+        accounts_to_prefetch(params)
+            .into_iter()
+            .map(|(address, slots)| fetch_account(client, address, slots, block)),
+    )
+    .await?;
+    debug!(
+        "prefetched {} accounts at block {}",
+        accounts.len(),
+        block_info.number
+    );
+    Ok(PrefetchedState { accounts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::{PairPool, TokenPair};
+
+    #[test]
+    fn it_collects_accounts_to_prefetch() {
+        let (weth, token) = (Address::random(), Address::random());
+        let (v2_pool, v3_pool) = (Address::random(), Address::random());
+        let params = UserTradeParams {
+            pool_variant: PoolVariant::UniswapV2,
+            token_in: weth,
+            token_out: token,
+            amount0_sent: 1.into(),
+            amount1_sent: 0.into(),
+            token0_is_weth: true,
+            pool: v2_pool,
+            price: 1.into(),
+            tokens: TokenPair {
+                weth,
+                token,
+                weth_metadata: None,
+                token_metadata: None,
+            },
+            arb_pools: vec![PairPool {
+                address: v3_pool,
+                variant: PoolVariant::UniswapV3,
+            }],
+        };
+        let accounts = accounts_to_prefetch(&[params.to_owned(), params]);
+        assert_eq!(accounts.len(), 4);
+        assert_eq!(accounts[&v2_pool], V2_POOL_SLOTS.to_vec());
+        assert_eq!(accounts[&v3_pool], V3_POOL_SLOTS.to_vec());
+        assert!(accounts[&weth].is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    error::HindsightError,
+    sim::{core::fork_evm, evm::call_function},
+    util::WsClient,
+    Error, Result,
+};
+use ethers::{
+    abi::{self, ParamType},
+    types::{Address, H160, U256},
+    utils::format_ether,
+};
+use revm::EVM;
+use rusty_sando::{prelude::fork_db::ForkDB, types::BlockInfo};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+use tokio::sync::RwLock;
+
+/// Max number of blocks whose ETH price is kept in an `EthPriceCache`.
+const MAX_CACHED_PRICES: usize = 1024;
+/// Chainlink ETH/USD aggregator (mainnet).
+const CHAINLINK_ETH_USD: &str = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419";
+/// Uniswap V3 USDC/WETH 0.05% pool (mainnet).
+const UNIV3_USDC_WETH: &str = "0x88e6A0c2dDD26FEEB64F039a2c41296FcB3f5640";
+
+/// On-chain source for the price of ETH in USD.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EthPriceSource {
+    /// Chainlink aggregator; reads `latestRoundData()`.
+    Chainlink(Address),
+    /// Uniswap V3 pool trading WETH against a USD stablecoin; reads `slot0()`.
+    UniswapV3(Address),
+}
+
+impl Default for EthPriceSource {
+    fn default() -> Self {
+        EthPriceSource::Chainlink(
+            CHAINLINK_ETH_USD
+                .parse::<H160>()
+                .expect("bad chainlink address"),
+        )
+    }
+}
+
+/// Parses `<chainlink|univ3>[:<address>]`, e.g. `chainlink` or `univ3:0x88e6...5640`.
+/// If the address is omitted, the mainnet default for the source is used.
+impl FromStr for EthPriceSource {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, address) = match s.split_once(':') {
+            Some((kind, address)) => (kind, Some(address)),
+            None => (s, None),
+        };
+        let parse_address = |default: &str| {
+            address
+                .unwrap_or(default)
+                .parse::<H160>()
+                .map_err(|err| format!("invalid price source address: {}", err))
+        };
+        match kind {
+            "chainlink" => Ok(EthPriceSource::Chainlink(parse_address(CHAINLINK_ETH_USD)?)),
+            "univ3" => Ok(EthPriceSource::UniswapV3(parse_address(UNIV3_USDC_WETH)?)),
+            _ => Err(format!("invalid eth price source: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for EthPriceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EthPriceSource::Chainlink(address) => write!(f, "chainlink:{:?}", address),
+            EthPriceSource::UniswapV3(address) => write!(f, "univ3:{:?}", address),
+        }
+    }
+}
+
+fn u256_to_f64(value: U256) -> f64 {
+    value.to_string().parse::<f64>().unwrap_or(0.0)
+}
+
+fn decode_uint(output: &[u8], kind: ParamType, name: &str) -> Result<U256> {
+    abi::decode(&[kind], output)?[0]
+        .clone()
+        .into_uint()
+        .ok_or::<Error>(HindsightError::DecodeError(name.to_owned()).into())
+}
+
+/// Reads ETH/USD from a Chainlink aggregator.
+fn chainlink_price(evm: &mut EVM<ForkDB>, aggregator: Address) -> Result<f64> {
+    let output = call_function(evm, "0xfeaf968c", aggregator)?; // latestRoundData()
+    let round_data = abi::decode(
+        &[
+            ParamType::Uint(80),  // roundId
+            ParamType::Int(256),  // answer
+            ParamType::Uint(256), // startedAt
+            ParamType::Uint(256), // updatedAt
+            ParamType::Uint(80),  // answeredInRound
+        ],
+        &output,
+    )?;
+    // answer is an int256, but a negative ETH price means something is very wrong anyways
+    let answer = round_data[1]
+        .clone()
+        .into_int()
+        .ok_or::<Error>(HindsightError::DecodeError("chainlink answer".to_owned()).into())?;
+    let output = call_function(evm, "0x313ce567", aggregator)?; // decimals()
+    let decimals = decode_uint(&output, ParamType::Uint(8), "aggregator decimals")?;
+    Ok(u256_to_f64(answer) / 10f64.powi(decimals.as_u32() as i32))
+}
+
+/// Reads ETH/USD from the spot price of a WETH/stablecoin Uniswap V3 pool.
+fn univ3_price(evm: &mut EVM<ForkDB>, pool: Address) -> Result<f64> {
+    let weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".parse::<H160>()?;
+    let output = call_function(evm, "0x3850c7bd", pool)?; // slot0()
+    let sqrt_price = decode_uint(&output, ParamType::Uint(160), "sqrtPriceX96")?;
+    let output = call_function(evm, "0x0dfe1681", pool)?; // token0()
+    let token0 = abi::decode(&[ParamType::Address], &output)?[0]
+        .clone()
+        .into_address()
+        .ok_or::<Error>(HindsightError::DecodeError("token0".to_owned()).into())?;
+    let output = call_function(evm, "0xd21220a7", pool)?; // token1()
+    let token1 = abi::decode(&[ParamType::Address], &output)?[0]
+        .clone()
+        .into_address()
+        .ok_or::<Error>(HindsightError::DecodeError("token1".to_owned()).into())?;
+    let output = call_function(evm, "0x313ce567", token0)?; // decimals()
+    let decimals0 = decode_uint(&output, ParamType::Uint(8), "token0 decimals")?;
+    let output = call_function(evm, "0x313ce567", token1)?; // decimals()
+    let decimals1 = decode_uint(&output, ParamType::Uint(8), "token1 decimals")?;
+
+    // price of token0, denominated in token1
+    let sqrt_price = u256_to_f64(sqrt_price) / 2f64.powi(96);
+    let price0 =
+        sqrt_price.powi(2) * 10f64.powi(decimals0.as_u32() as i32 - decimals1.as_u32() as i32);
+    if token0 == weth {
+        Ok(price0)
+    } else if token1 == weth {
+        Ok(1.0 / price0)
+    } else {
+        Err(HindsightError::CallError(format!("pool {:?} does not trade WETH", pool)).into())
+    }
+}
+
+/// Returns the price of ETH in USD at the given block, read from `source` on a forked EVM.
+pub async fn eth_price_usd(
+    client: &WsClient,
+    block_info: &BlockInfo,
+    source: EthPriceSource,
+) -> Result<f64> {
+    let mut evm = fork_evm(client, block_info).await?;
+    match source {
+        EthPriceSource::Chainlink(aggregator) => chainlink_price(&mut evm, aggregator),
+        EthPriceSource::UniswapV3(pool) => univ3_price(&mut evm, pool),
+    }
+}
+
+/// ETH prices read from `source`, cached by block so each block's price is only read once.
+#[derive(Clone, Debug)]
+pub struct EthPriceCache {
+    source: EthPriceSource,
+    prices: Arc<RwLock<BTreeMap<u64, f64>>>,
+}
+
+impl EthPriceCache {
+    pub fn new(source: EthPriceSource) -> Self {
+        Self {
+            source,
+            prices: Arc::new(RwLock::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn source(&self) -> EthPriceSource {
+        self.source
+    }
+
+    /// Returns the price of ETH in USD at the given block, reading it from the chain
+    /// the first time it's asked for.
+    pub async fn get(&self, client: &WsClient, block_info: &BlockInfo) -> Result<f64> {
+        let block = block_info.number.as_u64();
+        if let Some(price) = self.prices.read().await.get(&block) {
+            return Ok(*price);
+        }
+        let price = eth_price_usd(client, block_info, self.source).await?;
+        let mut prices = self.prices.write().await;
+        prices.insert(block, price);
+        // blocks are mostly simulated in order, so the oldest ones are the least likely to come up again
+        while prices.len() > MAX_CACHED_PRICES {
+            prices.pop_first();
+        }
+        Ok(price)
+    }
+}
+
+/// Converts an amount of wei to USD, given the price of ETH in USD.
+pub fn wei_to_usd(amount: U256, eth_price_usd: f64) -> f64 {
+    format_ether(amount).parse::<f64>().unwrap_or(0.0) * eth_price_usd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{get_block_info, test::get_test_ws_client, ETH};
+    use ethers::providers::Middleware;
+
+    #[test]
+    fn it_parses_price_sources() {
+        assert_eq!(
+            EthPriceSource::from_str("chainlink").unwrap(),
+            EthPriceSource::default()
+        );
+        assert_eq!(
+            EthPriceSource::from_str("univ3").unwrap(),
+            EthPriceSource::UniswapV3(UNIV3_USDC_WETH.parse().unwrap())
+        );
+        let pool = "0x8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8";
+        assert_eq!(
+            EthPriceSource::from_str(&format!("univ3:{}", pool)).unwrap(),
+            EthPriceSource::UniswapV3(pool.parse().unwrap())
+        );
+        assert!(EthPriceSource::from_str("coingecko").is_err());
+        // Display round-trips through FromStr
+        let source = EthPriceSource::from_str("univ3").unwrap();
+        assert_eq!(
+            EthPriceSource::from_str(&source.to_string()).unwrap(),
+            source
+        );
+    }
+
+    #[test]
+    fn it_converts_wei_to_usd() {
+        assert_eq!(wei_to_usd(ETH * 2, 1500.0), 3000.0);
+        assert_eq!(wei_to_usd(0.into(), 1500.0), 0.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn it_reads_eth_price() -> Result<()> {
+        let client = get_test_ws_client().await?;
+        let block_info = get_block_info(&client, client.get_block_number().await?.as_u64()).await?;
+        let chainlink = eth_price_usd(&client, &block_info, EthPriceSource::default()).await?;
+        let univ3 = eth_price_usd(
+            &client,
+            &block_info,
+            EthPriceSource::from_str("univ3").unwrap(),
+        )
+        .await?;
+        assert!(chainlink > 0.0);
+        // the two sources should roughly agree
+        assert!((chainlink - univ3).abs() / chainlink < 0.05);
+
+        let cache = EthPriceCache::new(EthPriceSource::default());
+        assert_eq!(cache.get(&client, &block_info).await?, chainlink);
+        assert_eq!(
+            cache.prices.read().await.get(&block_info.number.as_u64()),
+            Some(&chainlink)
+        );
+        assert_eq!(cache.get(&client, &block_info).await?, chainlink);
+        Ok(())
+    }
+}
@@ -1,25 +1,59 @@
 use crate::error::HindsightError;
-use crate::interfaces::SimArbResultBatch;
-use crate::{info, Error, Result};
-use crate::{sim::core::find_optimal_backrun_amount_in_out, util::WsClient};
+use crate::interfaces::{SimArbResultBatch, UserTradeParams};
+use crate::sim::backend::SimBackendRef;
+use crate::sim::prefetch::{prefetch_state, PrefetchedState};
+use crate::sim::pricing::{wei_to_usd, EthPriceCache};
+use crate::{info, warn, Error, Result};
+use crate::{
+    sim::core::{derive_trade_params, find_optimal_backrun_for_params},
+    tokens::TokenCache,
+    util::WsClient,
+};
 use ethers::{
     providers::Middleware,
     types::{Transaction, H256, U256},
 };
 use mev_share_sse::EventHistory;
 use rusty_sando::types::BlockInfo;
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 pub type H256Map<T> = HashMap<H256, T>;
 
+/// Everything needed to simulate backruns for one landed tx.
+#[derive(Clone, Debug)]
+pub struct SimJob {
+    pub tx: Transaction,
+    pub event: EventHistory,
+    /// Block prior to the one the tx landed in.
+    pub block_info: BlockInfo,
+    pub params: Vec<UserTradeParams>,
+    /// State of the pools & tokens the backruns will touch.
+    pub prefetched: Arc<PrefetchedState>,
+}
+
 pub async fn simulate_backrun_arbs(
     client: &WsClient,
+    backend: &SimBackendRef,
+    tokens: &TokenCache,
+    eth_prices: &EthPriceCache,
     tx: Transaction,
     event_map: &H256Map<EventHistory>,
 ) -> Result<SimArbResultBatch> {
     let event = event_map
         .get(&tx.hash)
         .ok_or::<Error>(HindsightError::EventNotCached(tx.hash).into())?;
+    let job = prepare_sim(client, tokens, tx, event).await?;
+    run_sim(client, backend, eth_prices, job).await
+}
+
+/// Fetches the block to simulate on, derives the user's trades from `tx`,
+/// and prefetches the state the backruns will touch.
+pub async fn prepare_sim(
+    client: &WsClient,
+    tokens: &TokenCache,
+    tx: Transaction,
+    event: &EventHistory,
+) -> Result<SimJob> {
     let sim_block_num = tx
         .block_number
         .ok_or::<Error>(HindsightError::TxNotLanded(tx.hash).into())?;
@@ -35,8 +69,39 @@ pub async fn simulate_backrun_arbs(
         timestamp: block.timestamp,
         base_fee: block.base_fee_per_gas.unwrap_or(1_000_000_000.into()),
     };
+    let params = derive_trade_params(client, tokens, tx.to_owned(), event).await?;
+    // a failed prefetch only costs us the round trips it would've saved
+    let prefetched = prefetch_state(client, &params, &block_info)
+        .await
+        .unwrap_or_else(|err| {
+            warn!("failed to prefetch state for tx {:?}: {}", tx.hash, err);
+            PrefetchedState::default()
+        });
+    Ok(SimJob {
+        tx,
+        event: event.to_owned(),
+        block_info,
+        params,
+        prefetched: Arc::new(prefetched),
+    })
+}
 
-    let res = find_optimal_backrun_amount_in_out(client, tx, event, &block_info).await?;
+/// Simulates backruns for a prepared job on `backend` & prices the profit in USD.
+pub async fn run_sim(
+    client: &WsClient,
+    backend: &SimBackendRef,
+    eth_prices: &EthPriceCache,
+    job: SimJob,
+) -> Result<SimArbResultBatch> {
+    let SimJob {
+        tx,
+        event,
+        block_info,
+        params,
+        prefetched,
+    } = job;
+    let sim_block_num = block_info.number.as_u64();
+    let res = find_optimal_backrun_for_params(backend, tx, params, &block_info, prefetched).await?;
     let mut max_profit = U256::from(0);
     /*
        Sum up the profit from each result. Generally there should only be one result, but if
@@ -51,9 +116,26 @@ pub async fn simulate_backrun_arbs(
             max_profit = res.backrun_trade.profit;
         }
     }
+    // a missing USD price shouldn't throw away the sim results
+    let max_profit_usd = if max_profit.is_zero() {
+        Some(0.0)
+    } else {
+        match eth_prices.get(client, &block_info).await {
+            Ok(price) => Some(wei_to_usd(max_profit, price)),
+            Err(err) => {
+                warn!(
+                    "failed to get ETH price at block {}: {:?}",
+                    sim_block_num, err
+                );
+                None
+            }
+        }
+    };
     Ok(SimArbResultBatch {
-        event: event.to_owned(),
+        event,
         max_profit,
+        max_profit_usd,
+        run_id: None,
         results: res,
     })
 }
@@ -0,0 +1,166 @@
+use crate::{
+    data::tokens::TokenDatabase, debug, interfaces::TokenMetadata, rpc::endpoints::is_answer,
+    util::WsClient, warn, Result,
+};
+use ethers::{
+    abi::{self, ParamType},
+    providers::Middleware,
+    types::{Address, Bytes, TransactionRequest, U256},
+};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+use tokio::sync::RwLock;
+
+const SYMBOL_SELECTOR: &str = "0x95d89b41";
+const NAME_SELECTOR: &str = "0x06fdde03";
+const DECIMALS_SELECTOR: &str = "0x313ce567";
+
+/// In-memory token metadata cache, optionally backed by a DB.
+///
+/// Metadata is fetched from the chain the first time a token is requested,
+/// then served from memory (and saved to the DB, if one is attached).
+#[derive(Clone, Default)]
+pub struct TokenCache {
+    tokens: Arc<RwLock<HashMap<Address, TokenMetadata>>>,
+    db: Option<TokenDatabase>,
+}
+
+impl std::fmt::Debug for TokenCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenCache")
+            .field("db", &self.db.is_some())
+            .finish()
+    }
+}
+
+impl TokenCache {
+    /// Creates a new cache, preloaded with all tokens saved in `db`.
+    pub async fn new(db: Option<TokenDatabase>) -> Result<Self> {
+        let mut tokens = HashMap::new();
+        if let Some(db) = &db {
+            for token in db.read_tokens().await? {
+                tokens.insert(token.address, token);
+            }
+        }
+        debug!("loaded {} tokens into cache", tokens.len());
+        Ok(Self {
+            tokens: Arc::new(RwLock::new(tokens)),
+            db,
+        })
+    }
+
+    /// Get metadata for `token`, fetching it from the chain if it's not cached yet.
+    /// RPC errors are returned rather than cached, so the token is fetched again next time.
+    pub async fn get(&self, client: &WsClient, token: Address) -> Result<TokenMetadata> {
+        if let Some(metadata) = self.tokens.read().await.get(&token) {
+            return Ok(metadata.to_owned());
+        }
+        let metadata = fetch_token_metadata(client, token).await?;
+        self.tokens.write().await.insert(token, metadata.to_owned());
+        if let Some(db) = &self.db {
+            // a failed write only costs us a refetch later; don't fail the sim over it
+            if let Err(err) = db.write_tokens(&[metadata.to_owned()]).await {
+                warn!("failed to save token metadata for {:?}: {:?}", token, err);
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+/// Calls a no-arg view function on `contract`. Returns None if the call reverts or returns
+/// nothing (i.e. the token doesn't implement it); RPC errors are returned as errors.
+async fn call_view(client: &WsClient, contract: Address, selector: &str) -> Result<Option<Bytes>> {
+    let tx = TransactionRequest::new()
+        .to(contract)
+        .data(Bytes::from_str(selector)?);
+    match client.call(&tx.into(), None).await {
+        Ok(output) if !output.is_empty() => Ok(Some(output)),
+        Ok(_) => Ok(None),
+        Err(err) if is_answer(&err) => {
+            debug!("call {} reverted on {:?}: {:?}", selector, contract, err);
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Decodes a `string` return value, falling back to `bytes32` (e.g. MKR's `symbol()`).
+fn decode_string_or_bytes32(output: &[u8]) -> Option<String> {
+    if let Ok(tokens) = abi::decode(&[ParamType::String], output) {
+        if let Some(s) = tokens[0].clone().into_string() {
+            return Some(s);
+        }
+    }
+    if output.len() == 32 {
+        let end = output.iter().position(|b| *b == 0).unwrap_or(32);
+        return Some(String::from_utf8_lossy(&output[..end]).into_owned());
+    }
+    None
+}
+
+/// Decodes a `decimals()` return value. Some tokens return uint256, so we only
+/// accept values that actually fit in a u8.
+fn decode_decimals(output: &[u8]) -> Option<u8> {
+    if output.len() < 32 {
+        return None;
+    }
+    let decimals = U256::from_big_endian(&output[0..32]);
+    if decimals > U256::from(u8::MAX) {
+        return None;
+    }
+    Some(decimals.as_u32() as u8)
+}
+
+/// Fetches symbol, name & decimals from the token contract.
+/// Methods the token doesn't implement are left as None.
+pub async fn fetch_token_metadata(client: &WsClient, token: Address) -> Result<TokenMetadata> {
+    let (symbol, name, decimals) = futures::try_join!(
+        call_view(client, token, SYMBOL_SELECTOR),
+        call_view(client, token, NAME_SELECTOR),
+        call_view(client, token, DECIMALS_SELECTOR),
+    )?;
+    Ok(TokenMetadata {
+        address: token,
+        symbol: symbol.and_then(|output| decode_string_or_bytes32(&output)),
+        name: name.and_then(|output| decode_string_or_bytes32(&output)),
+        decimals: decimals.and_then(|output| decode_decimals(&output)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::get_test_ws_client;
+    use ethers::abi::Token;
+
+    #[test]
+    fn it_decodes_string_and_bytes32_symbols() {
+        let encoded = abi::encode(&[Token::String("WETH".to_owned())]);
+        assert_eq!(decode_string_or_bytes32(&encoded), Some("WETH".to_owned()));
+
+        let mut bytes32 = [0u8; 32];
+        bytes32[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_string_or_bytes32(&bytes32), Some("MKR".to_owned()));
+
+        assert_eq!(decode_string_or_bytes32(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn it_decodes_decimals() {
+        let encoded = abi::encode(&[Token::Uint(18.into())]);
+        assert_eq!(decode_decimals(&encoded), Some(18));
+        let encoded = abi::encode(&[Token::Uint(U256::MAX)]);
+        assert_eq!(decode_decimals(&encoded), None);
+        assert_eq!(decode_decimals(&[]), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn it_fetches_token_metadata() -> Result<()> {
+        let client = get_test_ws_client().await?;
+        let cache = TokenCache::new(None).await?;
+        let mkr = Address::from_str("0x9f8F72aA9304c8B593d555F12eF6589cC3A579A2")?;
+        let metadata = cache.get(&client, mkr).await?;
+        assert_eq!(metadata.symbol, Some("MKR".to_owned()));
+        assert_eq!(metadata.decimals, Some(18));
+        Ok(())
+    }
+}
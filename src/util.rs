@@ -1,64 +1,74 @@
 use crate::{
     config::Config,
     info,
-    interfaces::{PairPool, PoolVariant},
+    interfaces::{PairPool, PoolFactory, PoolVariant},
+    rpc::{endpoints::EndpointPool, RpcLimits, ThrottledTransport},
     Result,
 };
 use ethers::{
+    abi::Token,
+    contract::Multicall,
     prelude::{abigen, H160},
-    providers::{Middleware, Provider, Ws},
-    types::{transaction::eip2718::TypedTransaction, Address, Transaction, H256, U256},
+    providers::{Middleware, Provider},
+    types::{transaction::eip2718::TypedTransaction, Address, H256, U256},
 };
-use futures::future;
 use mev_share_sse::EventHistory;
 use rusty_sando::types::BlockInfo;
 use std::sync::Arc;
 use uniswap_v3_math::{full_math::mul_div, sqrt_price_math::Q96};
 
 pub use ethers::utils::WEI_IN_ETHER as ETH;
-pub type WsClient = Arc<Provider<Ws>>;
-
-pub async fn get_ws_client(rpc_url: Option<String>, max_reconnects: usize) -> Result<WsClient> {
-    let rpc_url = if let Some(rpc_url) = rpc_url {
-        rpc_url
-    } else {
-        Config::default().rpc_url_ws
+/// Client for the RPC node(s) configured in `Config::rpc_urls`. Despite the name, endpoints may be WS or HTTP.
+pub type WsClient = Arc<Provider<ThrottledTransport<EndpointPool>>>;
+
+const UNISWAP_V2_FACTORY: &str = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f";
+const SUSHISWAP_FACTORY: &str = "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac";
+const UNISWAP_V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+/// Fee tier of the V3 pools we trade on.
+const UNISWAP_V3_FEE: u32 = 3000;
+/// Multicall3, deployed at the same address on most chains.
+pub const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// All factories that trading pools are looked up from.
+pub fn pool_registry() -> Vec<PoolFactory> {
+    let factory = |name: &str, variant, address: &str, fee| PoolFactory {
+        name: name.to_owned(),
+        variant,
+        address: address.parse::<H160>().expect("bad factory address"),
+        fee,
     };
-    let provider = Provider::<Ws>::connect_with_reconnects(rpc_url, max_reconnects).await?;
-    Ok(Arc::new(provider))
+    vec![
+        factory(
+            "uniswap_v3",
+            PoolVariant::UniswapV3,
+            UNISWAP_V3_FACTORY,
+            Some(UNISWAP_V3_FEE),
+        ),
+        factory(
+            "uniswap_v2",
+            PoolVariant::UniswapV2,
+            UNISWAP_V2_FACTORY,
+            None,
+        ),
+        factory("sushiswap", PoolVariant::UniswapV2, SUSHISWAP_FACTORY, None),
+    ]
 }
 
-pub async fn fetch_txs(client: &WsClient, events: &[EventHistory]) -> Result<Vec<Transaction>> {
-    let tx_hashes: Vec<H256> = events.iter().map(|e: &EventHistory| e.hint.hash).collect();
-    let mut handles = vec![];
-
-    for tx_hash in tx_hashes.into_iter() {
-        let client = client.clone();
-        handles.push(tokio::task::spawn(async move {
-            let tx = &client.get_transaction(tx_hash.to_owned()).await;
-            if let Ok(tx) = tx {
-                if let Some(tx) = tx {
-                    info!("tx found onchain\t{:?}", tx_hash.to_owned());
-                    Some(tx.clone())
-                } else {
-                    info!("tx not found onchain\t{:?}", tx_hash.to_owned());
-                    None
-                }
-            } else {
-                info!("error fetching tx: {:?}", tx);
-                None
-            }
-        }));
-    }
-    let results = future::join_all(handles)
-        .await
-        .into_iter()
-        .filter_map(|r| r.ok())
-        .flatten()
-        .collect::<Vec<_>>();
-    Ok(results)
+pub async fn get_ws_client(rpc_url: Option<String>, max_reconnects: usize) -> Result<WsClient> {
+    let config = Config::default();
+    // an explicit url replaces all configured endpoints
+    let rpc_urls = rpc_url
+        .map(|url| vec![url])
+        .unwrap_or(config.rpc_urls.to_owned());
+    let endpoints = EndpointPool::connect(&rpc_urls, max_reconnects).await?;
+    info!("rpc endpoints: {:?}", endpoints.hosts());
+    Ok(Arc::new(Provider::new(ThrottledTransport::new(
+        endpoints,
+        RpcLimits::from(&config),
+    ))))
 }
 
+/// Returns (token0, token1) of a Uniswap pool, in one Multicall3 call.
 pub async fn get_pair_tokens(client: &WsClient, pair: Address) -> Result<(Address, Address)> {
     abigen!(
         IPairTokens,
@@ -68,9 +78,15 @@ pub async fn get_pair_tokens(client: &WsClient, pair: Address) -> Result<(Addres
         ]"#
     );
     let contract = IPairTokens::new(pair, client.clone());
-    let token0 = contract.token_0().call().await?;
-    let token1 = contract.token_1().call().await?;
-    Ok((token0, token1))
+    let mut multicall = Multicall::new(client.clone(), Some(MULTICALL3.parse::<H160>()?)).await?;
+    multicall
+        .add_call(contract.token_0(), false)
+        .add_call(contract.token_1(), false);
+    let tokens: Vec<Address> = multicall.call_array().await?;
+    match tokens[..] {
+        [token0, token1] => Ok((token0, token1)),
+        _ => Err(anyhow::format_err!("bad multicall response for {:?}", pair)),
+    }
 }
 
 pub async fn get_block_info(client: &WsClient, block_num: u64) -> Result<BlockInfo> {
@@ -85,95 +101,87 @@ pub async fn get_block_info(client: &WsClient, block_num: u64) -> Result<BlockIn
     })
 }
 
-async fn get_v2_pairs(client: &WsClient, pair_tokens: (Address, Address)) -> Result<Vec<Address>> {
+/// Get pair address from all supported factories, including the given pair.
+/// Filter what I return if you need to.
+///
+/// All factories are queried in one Multicall3 call; factories that revert are skipped.
+pub async fn get_all_trading_pools(
+    client: &WsClient,
+    pair_tokens: (Address, Address),
+) -> Result<Vec<PairPool>> {
     abigen!(
         IUniswapV2Factory,
         r#"[
             function getPair(address tokenA, address tokenB) external view returns (address pair)
         ]"#
     );
-    let uni_factory = IUniswapV2Factory::new(
-        "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f".parse::<H160>()?,
-        client.clone(),
-    );
-    let sushi_factory = IUniswapV2Factory::new(
-        "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac".parse::<H160>()?,
-        client.clone(),
-    );
-
-    let uni_pair: Result<Address, _> = uni_factory
-        .get_pair(pair_tokens.0, pair_tokens.1)
-        .call()
-        .await;
-    let sushi_pair: Result<Address, _> = sushi_factory
-        .get_pair(pair_tokens.0, pair_tokens.1)
-        .call()
-        .await;
-    let mut pairs = vec![];
-    if let Ok(uni_pair) = uni_pair {
-        pairs.push(uni_pair);
-    }
-    if let Ok(sushi_pair) = sushi_pair {
-        pairs.push(sushi_pair);
-    }
-
-    Ok(pairs)
-}
-
-async fn get_v3_pair(client: &WsClient, pair_tokens: (Address, Address)) -> Result<Address> {
     abigen!(
         IUniswapV3Factory,
         r#"[
             function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool)
         ]"#
     );
-    let contract = IUniswapV3Factory::new(
-        "0x1F98431c8aD98523631AE4a59f267346ea31F984".parse::<H160>()?,
-        client.clone(),
-    );
-    Ok(contract
-        .get_pool(pair_tokens.0, pair_tokens.1, 3000)
-        .call()
-        .await?)
+    let mut multicall = Multicall::new(client.clone(), Some(MULTICALL3.parse::<H160>()?)).await?;
+    let mut variants = vec![];
+    for factory in pool_registry() {
+        match factory.variant {
+            PoolVariant::UniswapV3 => {
+                let contract = IUniswapV3Factory::new(factory.address, client.clone());
+                // there should only be one pool for a given fee
+                multicall.add_call(
+                    contract.get_pool(
+                        pair_tokens.0,
+                        pair_tokens.1,
+                        factory.fee.unwrap_or(UNISWAP_V3_FEE),
+                    ),
+                    true,
+                );
+            }
+            PoolVariant::UniswapV2 => {
+                let contract = IUniswapV2Factory::new(factory.address, client.clone());
+                multicall.add_call(contract.get_pair(pair_tokens.0, pair_tokens.1), true);
+            }
+        }
+        variants.push(factory.variant);
+    }
+    let all_pairs = multicall
+        .call_raw()
+        .await?
+        .into_iter()
+        .zip(variants)
+        .filter_map(|(res, variant)| match res {
+            Ok(Token::Address(address)) => Some(PairPool { address, variant }),
+            _ => None,
+        })
+        .collect();
+    Ok(all_pairs)
 }
 
-/// Get pair address from all supported factories, including the given pair.
-/// Filter what I return if you need to.
-pub async fn get_all_trading_pools(
-    client: &WsClient,
-    pair_tokens: (Address, Address),
-) -> Result<Vec<PairPool>> {
-    let mut all_pairs = vec![];
-    // push v3 pair (there should only be one for a given fee, which we hard-code to 3000 in get_v3_pair)
-    all_pairs.push(PairPool {
-        address: get_v3_pair(client, pair_tokens).await?,
-        variant: PoolVariant::UniswapV3,
-    });
-    // v2 pairs pull from multiple v2 clones
-    let v2_pairs = get_v2_pairs(client, pair_tokens).await?;
-    all_pairs.append(
-        &mut v2_pairs
-            .into_iter()
-            .map(|pair| PairPool {
-                address: pair,
-                variant: PoolVariant::UniswapV2,
-            })
-            .collect::<Vec<_>>(),
-    );
-    Ok(all_pairs)
+/// Returns `reserves1 * 10^token0_decimals / reserves0`; errors instead of overflowing
+/// on tokens w/ unusual decimals or huge reserves.
+fn price_from_reserves(reserves0: U256, reserves1: U256, token0_decimals: U256) -> Result<U256> {
+    U256::from(10)
+        .checked_pow(token0_decimals)
+        .and_then(|scale| reserves1.checked_mul(scale))
+        .ok_or(anyhow::format_err!(
+            "price overflows (reserves1={}, token0_decimals={})",
+            reserves1,
+            token0_decimals
+        ))?
+        .checked_div(reserves0)
+        .ok_or(anyhow::format_err!("pool has no reserves of token0"))
 }
 
 /// Returns the price (token1 per token0).
 pub fn get_price_v2(reserves0: U256, reserves1: U256, token0_decimals: U256) -> Result<U256> {
-    Ok((reserves1 * U256::from(10).pow(token0_decimals)) / reserves0)
+    price_from_reserves(reserves0, reserves1, token0_decimals)
 }
 
 /// Returns the price (token1 per token0).
 pub fn get_price_v3(liquidity: U256, sqrt_price_x96: U256, token0_decimals: U256) -> Result<U256> {
     let reserves0 = mul_div(liquidity, Q96, sqrt_price_x96)?;
     let reserves1 = mul_div(liquidity, sqrt_price_x96, Q96)?;
-
-    Ok((reserves1 * U256::from(10).pow(token0_decimals)) / reserves0)
+    price_from_reserves(reserves0, reserves1, token0_decimals)
 }
 
 pub async fn get_decimals(client: &WsClient, token: Address) -> Result<U256> {